@@ -14,4 +14,27 @@ abstract contract SapphireTest is Test, Precompiles {
     function setUp() public virtual {
         binaryHandler = new BinaryHandler();
     }
+
+    /// Calls `to` like a Sapphire transaction with the given gas limit, which includes the
+    /// intrinsic gas of the transaction. Within the call the GAS_USED and PAD_GAS precompiles
+    /// account for the intrinsic gas and the execution gas used since its start. Call depths are
+    /// found by recording the state diff of the call, so it cannot be recorded by the test.
+    /// Outside of it PAD_GAS reverts, since the gas used by the transaction is unknown.
+    function sapphireCall(address to, bytes memory data, uint64 gasLimit)
+        internal
+        returns (bool success, bytes memory result)
+    {
+        uint256 intrinsic = intrinsicGas(data);
+        require(gasLimit > intrinsic, "sapphireCall: gas limit below intrinsic gas");
+        uint256 frameGas = gasLimit - intrinsic;
+        require(gasleft() - gasleft() / 64 > frameGas + 10_000, "sapphireCall: gas limit too high");
+        vm.store(GAS_METER, FRAME_GAS_SLOT, bytes32(frameGas));
+        vm.store(GAS_METER, INTRINSIC_GAS_SLOT, bytes32(intrinsic));
+        vm.store(GAS_METER, FRAME_ADDRESS_SLOT, bytes32(uint256(uint160(to))));
+        vm.store(GAS_METER, FRAME_DEPTH_SLOT, bytes32(0));
+        vm.startStateDiffRecording();
+        (success, result) = to.call{gas: frameGas}(data);
+        vm.stopAndReturnStateDiff();
+        vm.store(GAS_METER, FRAME_GAS_SLOT, bytes32(0));
+    }
 }
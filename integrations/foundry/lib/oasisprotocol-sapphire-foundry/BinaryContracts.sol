@@ -4,18 +4,179 @@ pragma solidity ^0.8.11;
 import {Vm} from "forge-std/Vm.sol";
 import {console} from "forge-std/console.sol";
 
+// Address whose storage describes the emulated Sapphire transaction (see
+// `SapphireTest.sapphireCall`). Its code consumes all gas it is called with.
+address constant GAS_METER = address(uint160(uint256(keccak256("oasisprotocol-sapphire-foundry gas meter"))));
+
+// Storage slot of GAS_METER holding the gas the called contract starts with, zero outside of an
+// emulated transaction.
+bytes32 constant FRAME_GAS_SLOT = bytes32(uint256(0));
+
+// Storage slot of GAS_METER holding the intrinsic gas of the emulated transaction.
+bytes32 constant INTRINSIC_GAS_SLOT = bytes32(uint256(1));
+
+// Storage slot of GAS_METER holding the address of the called contract.
+bytes32 constant FRAME_ADDRESS_SLOT = bytes32(uint256(2));
+
+// Storage slot of GAS_METER holding the call depth of the called contract, zero until known.
+bytes32 constant FRAME_DEPTH_SLOT = bytes32(uint256(3));
+
+// Intrinsic gas of a transaction calling a contract with the given data.
+function intrinsicGas(bytes memory data) pure returns (uint256 gas) {
+    gas = 21_000;
+    for (uint256 i = 0; i < data.length; i++) {
+        gas += data[i] == 0 ? 4 : 16;
+    }
+}
+
+// Accounting input of the GAS_USED and PAD_GAS handlers for a call of `precompile` entered with
+// `gasLeft`: the intrinsic gas, the gas the called contract started with, `gasLeft` and the
+// number of calls between the called contract and the precompile. The emulation is not metered.
+function meterGas(address precompile, uint256 gasLeft) returns (bytes memory) {
+    Vm vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
+    vm.pauseGasMetering();
+    uint256 frameGas = uint256(vm.load(GAS_METER, FRAME_GAS_SLOT));
+    uint256 depth;
+    if (frameGas != 0) {
+        uint256 precompileDepth = callDepth(precompile);
+        depth = precompileDepth - uint256(vm.load(GAS_METER, FRAME_DEPTH_SLOT));
+    }
+    bytes memory params = abi.encode(uint256(vm.load(GAS_METER, INTRINSIC_GAS_SLOT)), frameGas, gasLeft, depth);
+    vm.resumeGasMetering();
+    return params;
+}
+
+// Call depth of the running `precompile` frame, taken from the state diff recorded since the
+// start of the emulated transaction or the last call of this function. Recording restarts
+// afterwards, so the depth of the called contract is kept in GAS_METER once it has been found.
+function callDepth(address precompile) returns (uint256 depth) {
+    Vm vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
+    Vm.AccountAccess[] memory accesses = vm.stopAndReturnStateDiff();
+    vm.startStateDiffRecording();
+    address frame = address(uint160(uint256(vm.load(GAS_METER, FRAME_ADDRESS_SLOT))));
+    bool frameKnown = vm.load(GAS_METER, FRAME_DEPTH_SLOT) != 0;
+    for (uint256 i = 0; i < accesses.length; i++) {
+        Vm.AccountAccess memory access = accesses[i];
+        if (access.kind != Vm.AccountAccessKind.Call && access.kind != Vm.AccountAccessKind.StaticCall) {
+            continue;
+        }
+        // The first call of the called contract is the emulated transaction itself.
+        if (!frameKnown && access.account == frame) {
+            vm.store(GAS_METER, FRAME_DEPTH_SLOT, bytes32(uint256(access.depth)));
+            frameKnown = true;
+        }
+        // The running frame is the last call of the precompile.
+        if (access.account == precompile) {
+            depth = access.depth;
+        }
+    }
+}
+
+// Environment variable holding the URL of the precompile server (see
+// `precompiles/src/server.rs`), e.g. `http://127.0.0.1:8555`.
+string constant SERVER_ENV = "SAPPHIRE_PRECOMPILES_SERVER";
+
+// Directory of the precompile binaries.
+string constant BINARY_DIR = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/";
+
+// Runs a precompile handler, returning its output and the gas Sapphire charges for the call. The
+// one-shot binary of the handler is run unless SAPPHIRE_PRECOMPILES_SERVER is set, in which case
+// the call is sent to the running precompile server over JSON-RPC without starting a process. The
+// emulation itself is not metered. Reverts with the error of the handler if it fails.
+function callPrecompile(string memory name, bytes memory params) returns (bytes memory output, uint256 cost) {
+    Vm vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
+    vm.pauseGasMetering();
+    string memory server = vm.envOr(SERVER_ENV, string(""));
+    bytes memory result;
+    bool success;
+    string memory err;
+    if (bytes(server).length != 0) {
+        string memory rpcParams = string.concat('["', vm.toString(params), '"]');
+        try vm.rpc(server, string.concat("sapphire_", name), rpcParams) returns (bytes memory data) {
+            (result, success) = (data, true);
+        } catch Error(string memory reason) {
+            err = reason;
+        } catch {
+            err = "precompile server call failed";
+        }
+    } else {
+        string[] memory inputs = new string[](2);
+        inputs[0] = string.concat(BINARY_DIR, name);
+        inputs[1] = vm.toString(params);
+        Vm.FfiResult memory ffiResult = vm.tryFfi(inputs);
+        (result, success, err) = (ffiResult.stdout, ffiResult.exitCode == 0, string(ffiResult.stderr));
+    }
+    if (success) {
+        (cost, output) = abi.decode(result, (uint256, bytes));
+    }
+    vm.resumeGasMetering();
+    require(success, err);
+}
+
+// Runs a precompile handler and charges the gas Sapphire would for the call.
+function runPrecompile(string memory name, bytes memory params) returns (bytes memory output) {
+    uint256 start = gasleft();
+    uint256 cost;
+    (output, cost) = callPrecompile(name, params);
+    burnGasTo(start > cost ? start - cost : 0);
+}
+
+// Burns gas until `gasEnd` is left, up to the few gas spent around the burning calls.
+function burnGasTo(uint256 gasEnd) {
+    require(GAS_METER.code.length > 0, "gas meter not deployed");
+    address meter = GAS_METER;
+    assembly {
+        // Measure the cost of a call apart from the gas it forwards. The first call warms up the
+        // address so that both cost the same.
+        pop(staticcall(0, meter, 0, 0, 0, 0))
+        let overhead := gas()
+        pop(staticcall(0, meter, 0, 0, 0, 0))
+        overhead := sub(overhead, gas())
+        // Calls forward at most 63/64 of the gas left, large amounts take several calls.
+        for {} gt(gas(), add(gasEnd, overhead)) {} {
+            pop(staticcall(sub(sub(gas(), gasEnd), overhead), meter, 0, 0, 0, 0))
+        }
+    }
+}
+
+// Environment variable holding the random seed (32 hex-encoded bytes) of every test.
+string constant RANDOM_SEED_ENV = "SAPPHIRE_RANDOM_SEED";
+
+// Storage slot of the random bytes precompile holding the random seed of the running test.
+bytes32 constant RANDOM_SEED_SLOT = keccak256("oasisprotocol-sapphire-foundry random seed");
+
 // Random Bytes Precompile
+//
+// Each caller draws from its own stream, derived from the seed of the test and the number of its
+// previous calls. Both are kept in the storage of the precompile, so Forge resets them for every
+// test. Storage is accessed through cheatcodes as the precompile is usually called with
+// staticcall.
 contract RandomBytesPrecompile {
     Vm constant vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
 
     fallback(bytes calldata input) external returns (bytes memory) {
         (uint256 numBytes, bytes memory pers) = abi.decode(input, (uint256, bytes));
         require(numBytes <= 1024, "Random: too many bytes requested");
-        bytes memory params = abi.encode(numBytes, pers);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/random_bytes";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        bytes32 counterSlot = bytes32(uint256(uint160(msg.sender)));
+        uint256 counter = uint256(vm.load(address(this), counterSlot));
+        vm.store(address(this), counterSlot, bytes32(counter + 1));
+        bytes memory params = abi.encode(numBytes, pers, seed(), msg.sender, counter);
+        return runPrecompile("random_bytes", params);
+    }
+
+    // Seed of the running test, taken from SAPPHIRE_RANDOM_SEED or drawn from the Forge RNG on the
+    // first call. It is always logged so that the test can be replayed.
+    function seed() internal returns (bytes32 value) {
+        value = vm.load(address(this), RANDOM_SEED_SLOT);
+        if (value != 0) {
+            return value;
+        }
+        value = vm.envOr(RANDOM_SEED_ENV, bytes32(0));
+        if (value == 0) {
+            value = bytes32(vm.randomUint());
+        }
+        vm.store(address(this), RANDOM_SEED_SLOT, value);
+        console.log("Random seed: %s (set %s to replay)", vm.toString(value), RANDOM_SEED_ENV);
     }
 
     receive() external payable {
@@ -30,10 +191,7 @@ contract X25519DerivePrecompile {
     fallback(bytes calldata input) external returns (bytes memory) {
         (bytes32 publicKey, bytes32 privateKey) = abi.decode(input, (bytes32, bytes32));
         bytes memory params = abi.encodePacked(publicKey, privateKey);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/x25519_derive";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("x25519_derive", params);
     }
 
     receive() external payable {
@@ -49,10 +207,7 @@ contract DeoxysiiSealPrecompile {
         (bytes32 key, bytes32 nonce, bytes memory plaintext, bytes memory ad) =
             abi.decode(input, (bytes32, bytes32, bytes, bytes));
         bytes memory params = abi.encode(key, nonce, plaintext, ad);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/deoxysii_seal";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("deoxysii_seal", params);
     }
 
     receive() external payable {
@@ -68,10 +223,7 @@ contract DeoxysiiOpenPrecompile {
         (bytes32 key, bytes32 nonce, bytes memory ciphertext, bytes memory ad) =
             abi.decode(input, (bytes32, bytes32, bytes, bytes));
         bytes memory params = abi.encode(key, nonce, ciphertext, ad);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/deoxysii_open";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("deoxysii_open", params);
     }
 
     receive() external payable {
@@ -85,10 +237,7 @@ contract Curve25519ComputePublicPrecompile {
 
     fallback(bytes calldata input) external returns (bytes memory) {
         bytes32 privateKey = abi.decode(input, (bytes32));
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/curve25519_compute_public";
-        inputs[1] = vm.toString(abi.encodePacked(privateKey));
-        return vm.ffi(inputs);
+        return runPrecompile("curve25519_compute_public", abi.encodePacked(privateKey));
     }
 
     receive() external payable {
@@ -103,10 +252,7 @@ contract KeypairGeneratePrecompile {
     fallback(bytes calldata input) external returns (bytes memory) {
         (uint256 sigType, bytes memory seed) = abi.decode(input, (uint256, bytes));
         bytes memory params = abi.encode(sigType, seed);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/keypair_generate";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("keypair_generate", params);
     }
 
     receive() external payable {
@@ -122,10 +268,7 @@ contract SignPrecompile {
         (uint256 sigType, bytes memory privateKey, bytes memory context, bytes memory message) =
             abi.decode(input, (uint256, bytes, bytes, bytes));
         bytes memory params = abi.encode(sigType, privateKey, context, message);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/sign";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("sign", params);
     }
 
     receive() external payable {
@@ -141,10 +284,7 @@ contract VerifyPrecompile {
         (uint256 sigType, bytes memory publicKey, bytes memory context, bytes memory message, bytes memory signature) =
             abi.decode(input, (uint256, bytes, bytes, bytes, bytes));
         bytes memory params = abi.encode(sigType, publicKey, context, message, signature);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/verify";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("verify", params);
     }
 
     receive() external payable {
@@ -154,13 +294,23 @@ contract VerifyPrecompile {
 
 // Gas Used Precompile
 contract GasUsedPrecompile {
+    // Sapphire.gasUsed calls with empty calldata, so there is no receive function.
+    fallback(bytes calldata) external returns (bytes memory) {
+        uint256 gasLeft = gasleft();
+        return runPrecompile("gas_used", meterGas(address(this), gasLeft));
+    }
+}
+
+// Pad Gas Precompile
+contract PadGasPrecompile {
     Vm constant vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
 
-    fallback(bytes calldata) external returns (bytes memory) {
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/gas_used";
-        inputs[1] = vm.toString(bytes(""));
-        return vm.ffi(inputs);
+    fallback(bytes calldata input) external returns (bytes memory) {
+        uint256 gasLeft = gasleft();
+        uint128 target = abi.decode(input, (uint128));
+        (bytes memory gasEnd, ) = callPrecompile("pad_gas", bytes.concat(abi.encode(target), meterGas(address(this), gasLeft)));
+        burnGasTo(abi.decode(gasEnd, (uint256)));
+        return "";
     }
 
     receive() external payable {
@@ -168,16 +318,32 @@ contract GasUsedPrecompile {
     }
 }
 
-// Pad Gas Precompile
-contract PadGasPrecompile {
-    Vm constant vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
+// SHA512_256 Precompile
+contract Sha512_256Precompile {
+    fallback(bytes calldata input) external returns (bytes memory) {
+        return runPrecompile("sha512_256", input);
+    }
 
+    receive() external payable {
+        revert("No ether accepted");
+    }
+}
+
+// SHA512 Precompile
+contract Sha512Precompile {
     fallback(bytes calldata input) external returns (bytes memory) {
-        uint128 target = abi.decode(input, (uint128));
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/pad_gas";
-        inputs[1] = vm.toString(abi.encode(target));
-        return vm.ffi(inputs);
+        return runPrecompile("sha512", input);
+    }
+
+    receive() external payable {
+        revert("No ether accepted");
+    }
+}
+
+// SHA384 Precompile
+contract Sha384Precompile {
+    fallback(bytes calldata input) external returns (bytes memory) {
+        return runPrecompile("sha384", input);
     }
 
     receive() external payable {
@@ -185,6 +351,14 @@ contract PadGasPrecompile {
     }
 }
 
+// Storage slot of the SUBCALL precompile holding the identifier of the emulated consensus ledger
+// (see `precompiles/src/ledger.rs`).
+bytes32 constant LEDGER_ID_SLOT = bytes32(0);
+
+// Storage slot of the SUBCALL precompile holding the version of the emulated consensus ledger
+// matching the EVM state. Forge reverts it together with the calls that changed the ledger.
+bytes32 constant LEDGER_VERSION_SLOT = bytes32(uint256(1));
+
 // Subcall Precompile
 contract SubcallPrecompile {
     Vm constant vm = Vm(address(bytes20(uint160(uint256(keccak256("hevm cheat code"))))));
@@ -193,11 +367,22 @@ contract SubcallPrecompile {
         (string memory method, bytes memory body) = abi.decode(input, (string, bytes));
         uint256 blockNumber = uint256(vm.getBlockNumber());
         bytes32 privateKey = 0x1234567890123456789012345678901234567890123456789012345678901234;
-        bytes memory params = abi.encode(blockNumber, method, body, privateKey);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/subcall";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        bytes32 ledgerId = vm.load(address(this), LEDGER_ID_SLOT);
+        uint256 ledgerVersion = uint256(vm.load(address(this), LEDGER_VERSION_SLOT));
+        bytes memory params =
+            abi.encode(blockNumber, method, body, privateKey, msg.sender, msg.sender.balance, ledgerId, ledgerVersion);
+        (uint64 status, bytes memory data, uint256 debit, address[] memory accounts, uint256[] memory amounts, uint256 version) =
+            abi.decode(runPrecompile("subcall", params), (uint64, bytes, uint256, address[], uint256[], uint256));
+        vm.store(address(this), LEDGER_VERSION_SLOT, bytes32(version));
+
+        // Move the tokens the emulated ledger took from or released to EVM accounts.
+        if (debit > 0) {
+            vm.deal(msg.sender, msg.sender.balance - debit);
+        }
+        for (uint256 i = 0; i < accounts.length; i++) {
+            vm.deal(accounts[i], accounts[i].balance + amounts[i]);
+        }
+        return abi.encode(status, data);
     }
 
     receive() external payable {
@@ -212,10 +397,7 @@ contract DecodePrecompile {
         (bytes memory data) = abi.decode(input, (bytes));
         bytes32 privateKey = 0x1234567890123456789012345678901234567890123456789012345678901234;
         bytes memory params = abi.encode(data, privateKey);
-        string[] memory inputs = new string[](2);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/decode";
-        inputs[1] = vm.toString(params);
-        return vm.ffi(inputs);
+        return runPrecompile("decode", params);
     }
 
     receive() external payable {
@@ -229,11 +411,8 @@ abstract contract SapphireDecryptor {
 
     fallback(bytes calldata encryptedData) external payable returns (bytes memory) {
         // Try to decrypt using rust binary
-        string[] memory inputs = new string[](2);
         bytes memory params = abi.encode(encryptedData, privateKey_);
-        inputs[0] = "lib/oasisprotocol-sapphire-foundry/precompiles/target/release/decode";
-        inputs[1] = vm.toString(params);
-        bytes memory decryptedData = vm.ffi(inputs);
+        bytes memory decryptedData = runPrecompile("decode", params);
 
         // If data was encrypted (different after decryption)
         if (keccak256(encryptedData) != keccak256(decryptedData)) {
@@ -18,6 +18,9 @@ contract BinaryHandler is Precompiles {
     VerifyPrecompile verifyPrecompile;
     GasUsedPrecompile gasUsedPrecompile;
     PadGasPrecompile padGasPrecompile;
+    Sha512_256Precompile sha512_256Precompile;
+    Sha512Precompile sha512Precompile;
+    Sha384Precompile sha384Precompile;
     SubcallPrecompile subcallPrecompile;
     DecodePrecompile decodePrecompile;
 
@@ -53,12 +56,29 @@ contract BinaryHandler is Precompiles {
         vm.etch(PAD_GAS, type(PadGasPrecompile).runtimeCode);
         vm.label(PAD_GAS, "PAD_GAS");
 
+        vm.etch(SHA512_256, type(Sha512_256Precompile).runtimeCode);
+        vm.label(SHA512_256, "SHA512_256");
+
+        vm.etch(SHA512, type(Sha512Precompile).runtimeCode);
+        vm.label(SHA512, "SHA512");
+
+        vm.etch(SHA384, type(Sha384Precompile).runtimeCode);
+        vm.label(SHA384, "SHA384");
+
         vm.etch(SUBCALL, type(SubcallPrecompile).runtimeCode);
         vm.label(SUBCALL, "SUBCALL");
+        // Each deployment gets its own emulated consensus ledger, named independently of the seed
+        // of RANDOM_BYTES so that replaying a test does not reuse the ledger of an earlier run.
+        (bytes memory ledgerId,) = callPrecompile("ledger_id", "");
+        vm.store(SUBCALL, LEDGER_ID_SLOT, bytes32(ledgerId));
 
         vm.etch(
             DECODE, type(DecodePrecompile).runtimeCode
         );
         vm.label(DECODE, "DECODE");
+
+        // Consumes all gas it is called with (INVALID).
+        vm.etch(GAS_METER, hex"fe");
+        vm.label(GAS_METER, "GAS_METER");
     }
 }
@@ -0,0 +1,303 @@
+//! Gas accounting of the emulated precompiles.
+//!
+//! The emulated precompiles run through `vm.ffi`, so the gas Forge meters for them has nothing to
+//! do with what Sapphire charges. The precompile contracts therefore do not meter the emulation and
+//! charge the cost computed here instead, which follows the gas schedule of Sapphire.
+//!
+//! `gas_used` and `pad_gas` work with the gas used by the transaction so far, which Forge does not
+//! expose. The calling contract passes a [`Meter`] describing the emulated transaction (see
+//! `SapphireTest.sapphireCall`), from which the gas used is reconstructed.
+use ethabi::ParamType;
+
+/// Cost of the `gas_used` precompile.
+pub const GAS_USED_COST: u64 = 10;
+
+/// Cost of the `pad_gas` precompile, before padding.
+pub const PAD_GAS_COST: u64 = 10;
+
+const WORD: usize = 32;
+
+/// Gas charged by Sapphire for calling the given precompile with the given input.
+pub fn cost(name: &str, input: &[u8]) -> Result<u64, String> {
+    let cost = match name {
+        "random_bytes" => {
+            let args = decode(&[ParamType::Uint(256), ParamType::Bytes], input)?;
+            let num_bytes = args[0].clone().into_uint().unwrap().min(1024.into()).as_usize();
+            let pers = args[1].clone().into_bytes().unwrap();
+            10_000 + 240 * words(num_bytes) + 60 * words(pers.len())
+        }
+        "x25519_derive" | "curve25519_compute_public" => 100_000,
+        "deoxysii_seal" | "deoxysii_open" => {
+            let args = decode(
+                &[
+                    ParamType::FixedBytes(32),
+                    ParamType::FixedBytes(32),
+                    ParamType::Bytes,
+                    ParamType::Bytes,
+                ],
+                input,
+            )?;
+            50_000 + 100 * words(args[2].clone().into_bytes().unwrap().len())
+        }
+        "keypair_generate" => {
+            let args = decode(&[ParamType::Uint(256), ParamType::Bytes], input)?;
+            match signature_type(&args[0])? {
+                0 | 1 | 2 | 6 => 1_000,
+                3..=5 => 1_500,
+                7 => 4_000,
+                8 => 18_000,
+                _ => return Err("unknown signature type".into()),
+            }
+        }
+        "sign" => {
+            let args = decode(
+                &[ParamType::Uint(256), ParamType::Bytes, ParamType::Bytes, ParamType::Bytes],
+                input,
+            )?;
+            let (base, per_word) = match signature_type(&args[0])? {
+                0 | 1 | 6 => (1_500, true),
+                2 => (1_500, false),
+                3 => (3_000, true),
+                4 | 5 => (3_000, false),
+                7 => (9_000, false),
+                8 => (43_200, false),
+                _ => return Err("unknown signature type".into()),
+            };
+            base + message_cost(per_word, &args[2], &args[3])
+        }
+        "verify" => {
+            let args = decode(
+                &[
+                    ParamType::Uint(256),
+                    ParamType::Bytes,
+                    ParamType::Bytes,
+                    ParamType::Bytes,
+                    ParamType::Bytes,
+                ],
+                input,
+            )?;
+            let (base, per_word) = match signature_type(&args[0])? {
+                0 | 1 | 6 => (2_000, true),
+                2 => (2_000, false),
+                3 => (3_000, true),
+                4 | 5 => (3_000, false),
+                7 => (7_900, false),
+                8 => (37_920, false),
+                _ => return Err("unknown signature type".into()),
+            };
+            base + message_cost(per_word, &args[2], &args[3])
+        }
+        "gas_used" => GAS_USED_COST,
+        "pad_gas" => PAD_GAS_COST,
+        "sha512_256" | "sha512" | "sha384" => 115 + 13 * words(input.len()),
+        // The gas of a subcall depends on the dispatched method, which is not emulated. Decoding
+        // and generating ledger identifiers are not Sapphire precompiles.
+        "subcall" | "decode" | "ledger_id" => 0,
+        _ => return Err("Unknown precompile".into()),
+    };
+    Ok(cost)
+}
+
+/// Gas accounting input passed by the `gas_used` and `pad_gas` precompile contracts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Meter {
+    /// Intrinsic gas of the transaction.
+    pub intrinsic: u64,
+    /// Gas the called contract started with, zero outside of an emulated transaction.
+    pub frame_gas: u64,
+    /// Gas left when the precompile was entered.
+    pub gas_left: u64,
+    /// Number of calls between the called contract and the precompile.
+    pub depth: u64,
+}
+
+impl Meter {
+    /// ABI types of the meter, following any arguments of the precompile.
+    pub const TYPES: [ParamType; 4] = [
+        ParamType::Uint(256),
+        ParamType::Uint(256),
+        ParamType::Uint(256),
+        ParamType::Uint(256),
+    ];
+
+    /// Decode the meter from the given tokens.
+    pub fn from_tokens(tokens: &[ethabi::Token]) -> Self {
+        let value = |i: usize| -> u64 {
+            tokens[i].clone().into_uint().unwrap().try_into().unwrap_or(u64::MAX)
+        };
+        Self {
+            intrinsic: value(0),
+            frame_gas: value(1),
+            gas_left: value(2),
+            depth: value(3),
+        }
+    }
+
+    /// Gas the called contract had left when it made the call leading to the precompile.
+    ///
+    /// Each call retains 1/64 of the gas of the caller, so the 63/64 rule is inverted once per
+    /// call depth. Contracts in between are assumed to forward all of their gas. The gas they use
+    /// before calling is attributed to their callers, which skews the reconstructed gas by 1/63 of
+    /// it.
+    fn frame_gas_left(&self) -> u64 {
+        (0..self.depth).fold(self.gas_left, |gas, _| caller_gas(gas))
+    }
+}
+
+/// Gas the caller had left when it called a precompile, forwarding all of its gas, that was
+/// entered with `gas_left`.
+///
+/// The caller retains 1/64 of its gas, so the precompile is entered with `gas - gas / 64`.
+pub fn caller_gas(gas_left: u64) -> u64 {
+    let mut gas = gas_left.saturating_add(gas_left / 63);
+    while gas - gas / 64 > gas_left {
+        gas -= 1;
+    }
+    while gas - gas / 64 < gas_left {
+        gas += 1;
+    }
+    gas
+}
+
+/// Gas used by the transaction, including its intrinsic gas and the cost of the precompile.
+///
+/// Outside of an emulated transaction only the cost of the precompile is accounted for.
+pub fn used(meter: &Meter, cost: u64) -> u64 {
+    if meter.frame_gas == 0 {
+        return cost;
+    }
+    meter
+        .intrinsic
+        .saturating_add(meter.frame_gas.saturating_sub(meter.frame_gas_left()))
+        .saturating_add(cost)
+}
+
+/// Gas the `pad_gas` precompile must have left when returning, so that the transaction has used
+/// exactly `amount` gas.
+///
+/// Fails like Sapphire if the transaction has already used more than `amount` gas. Fails outside
+/// of an emulated transaction, where the gas used by the transaction is unknown.
+pub fn pad(amount: u64, meter: &Meter) -> Result<u64, String> {
+    if meter.frame_gas == 0 {
+        return Err("gas pad outside of an emulated transaction, call through sapphireCall".into());
+    }
+    let used = used(meter, PAD_GAS_COST);
+    if amount < used {
+        return Err("gas pad amount less than already used gas".into());
+    }
+    // The precompile itself spends what the caller does not keep.
+    let caller_used = used - PAD_GAS_COST;
+    Ok(meter.gas_left.saturating_sub(amount - caller_used))
+}
+
+fn decode(types: &[ParamType], input: &[u8]) -> Result<Vec<ethabi::Token>, String> {
+    ethabi::decode(types, input).map_err(|e| e.to_string())
+}
+
+fn signature_type(token: &ethabi::Token) -> Result<u64, String> {
+    token
+        .clone()
+        .into_uint()
+        .unwrap()
+        .try_into()
+        .map_err(|_| "signature type identifier out of bounds".to_string())
+}
+
+/// Per-word cost of the context and message of signature methods that hash them.
+fn message_cost(per_word: bool, context: &ethabi::Token, message: &ethabi::Token) -> u64 {
+    if !per_word {
+        return 0;
+    }
+    let len = context.clone().into_bytes().unwrap().len() + message.clone().into_bytes().unwrap().len();
+    8 * words(len)
+}
+
+fn words(len: usize) -> u64 {
+    len.div_ceil(WORD) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethabi::Token;
+
+    #[test]
+    fn test_caller_gas() {
+        for caller in [0u64, 1, 63, 64, 65, 127, 128, 1_000, 999_999, 30_000_000] {
+            let gas_left = caller - caller / 64;
+            let gas = caller_gas(gas_left);
+            assert_eq!(gas - gas / 64, gas_left, "caller gas {caller}");
+            // Gas amounts just below a multiple of 64 cannot be told apart from the multiple.
+            assert!(gas.abs_diff(caller) <= 1, "caller gas {caller}");
+        }
+    }
+
+    #[test]
+    fn test_pad() {
+        // The called contract started with 1_000_000 gas and used 50_000 before calling the
+        // precompile directly.
+        let frame_gas = 1_000_000;
+        let caller = frame_gas - 50_000;
+        let meter = Meter {
+            intrinsic: 21_000,
+            frame_gas,
+            gas_left: caller - caller / 64,
+            depth: 1,
+        };
+
+        assert_eq!(used(&meter, GAS_USED_COST), 71_010);
+        assert_eq!(used(&Meter { frame_gas: 0, ..meter }, GAS_USED_COST), GAS_USED_COST);
+
+        // Padding to 80_000 leaves the caller with 930_000 gas.
+        let end = pad(80_000, &meter).unwrap();
+        assert_eq!(caller - (meter.gas_left - end), frame_gas - 59_000);
+        // Padding only fails once the gas used including the cost of the call exceeds the amount.
+        assert_eq!(pad(71_010, &meter).unwrap(), meter.gas_left - 10);
+        assert!(pad(71_009, &meter).is_err());
+        // Outside of an emulated transaction padding fails.
+        let outside = Meter { frame_gas: 0, ..meter };
+        assert!(pad(u64::MAX, &outside).is_err());
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        // The called contract used 50_000 gas before calling another contract, which used 10_000
+        // gas before calling the precompile.
+        let frame_gas = 1_000_000;
+        let caller = frame_gas - 50_000;
+        let inner = caller - caller / 64 - 10_000;
+        let meter = Meter {
+            intrinsic: 21_000,
+            frame_gas,
+            gas_left: inner - inner / 64,
+            depth: 2,
+        };
+        // The gas retained by the callers is not used, up to 1/63 of the gas used in between.
+        assert!(used(&meter, GAS_USED_COST).abs_diff(81_010) <= 10_000 / 63 + 1);
+        // Inverting a single step would count the gas retained by the called contract.
+        let single = used(&Meter { depth: 1, ..meter }, GAS_USED_COST);
+        assert!(single > 81_010 + 10_000);
+    }
+
+    #[test]
+    fn test_cost() {
+        let random = ethabi::encode(&[
+            Token::Uint(33.into()),
+            Token::Bytes(vec![0; 32]),
+            Token::FixedBytes(vec![1; 32]),
+            Token::Address([2; 20].into()),
+            Token::Uint(3.into()),
+        ]);
+        assert_eq!(cost("random_bytes", &random).unwrap(), 10_000 + 2 * 240 + 60);
+        let sign = ethabi::encode(&[
+            Token::Uint(0.into()),
+            Token::Bytes(vec![0; 32]),
+            Token::Bytes(vec![0; 10]),
+            Token::Bytes(vec![0; 30]),
+        ]);
+        assert_eq!(cost("sign", &sign).unwrap(), 1_500 + 2 * 8);
+        assert_eq!(cost("sha512_256", &[0; 33]).unwrap(), 115 + 2 * 13);
+        assert_eq!(cost("sha384", &[]).unwrap(), 115);
+        assert!(cost("unknown", &[]).is_err());
+    }
+}
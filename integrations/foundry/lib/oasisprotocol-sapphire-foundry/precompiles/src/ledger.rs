@@ -0,0 +1,954 @@
+//! Emulated consensus ledger backing the `consensus.*` and `accounts.Transfer` subcalls.
+//!
+//! The ledger tracks what Forge cannot: balances of consensus accounts and of runtime accounts
+//! that are not EVM accounts, escrow pools with their delegations, debonding delegations and
+//! receipts. Balances of EVM accounts stay in the EVM, the caller passes the balance of the calling
+//! contract and applies the returned [`Effects`].
+//!
+//! Unlike on Sapphire, where delegations take effect once the consensus layer processes the
+//! emitted messages, delegations take effect immediately. Debonding delegations are released at
+//! the first call in or after the epoch the debonding ends. Bodies, results and receipts have the
+//! same CBOR encoding as in the runtime.
+//!
+//! Every call stores a new version of the ledger (see [`Ledger::with_file`]), so that the ledger
+//! follows the EVM state when Forge reverts calls.
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use oasis_cbor::Value;
+use oasis_runtime_sdk::types::{address::Address, token::BaseUnits};
+use rand::{rngs::OsRng, RngCore};
+
+/// Environment variable overriding the number of epochs undelegated tokens are debonding for.
+pub const DEBONDING_INTERVAL_ENV: &str = "SAPPHIRE_DEBONDING_INTERVAL";
+
+/// Default number of epochs undelegated tokens are debonding for.
+const DEFAULT_DEBONDING_INTERVAL: u64 = 1;
+
+/// Prefix of the names of ledger files in the temporary directory.
+const FILE_PREFIX: &str = "sapphire-ledger-";
+
+/// Time after which a ledger file that has not been used is removed (see [`remove_stale`]).
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// CBOR encoding of the unit result of successful calls.
+const CBOR_NULL: &[u8] = &[0xf6];
+
+// Receipt kinds of `consensus.TakeReceipt`.
+const RECEIPT_KIND_DELEGATE: u8 = 1;
+const RECEIPT_KIND_UNDELEGATE_START: u8 = 2;
+const RECEIPT_KIND_UNDELEGATE_DONE: u8 = 3;
+
+/// First identifier of `UndelegateDone` receipts. `Subcall.sol` can only take receipts with
+/// identifiers of at least 2^32.
+const FIRST_DONE_RECEIPT: u64 = 1 << 32;
+
+/// Error of a failed call, reported like by the runtime module that would have failed it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CallError {
+    pub module: &'static str,
+    pub code: u32,
+}
+
+const ERR_MALFORMED: CallError = CallError {
+    module: "core",
+    code: 1,
+};
+const ERR_INVALID_ARGUMENT: CallError = CallError {
+    module: "consensus_accounts",
+    code: 1,
+};
+const ERR_INVALID_DENOMINATION: CallError = CallError {
+    module: "consensus_accounts",
+    code: 2,
+};
+const ERR_INSUFFICIENT_BALANCE: CallError = CallError {
+    module: "accounts",
+    code: 2,
+};
+
+/// Consensus error of a failed delegation or undelegation, as recorded in receipts.
+const STAKING_MODULE: &str = "staking";
+const STAKING_ERR_INSUFFICIENT_BALANCE: u32 = 3;
+
+#[derive(Clone, Debug, Default, oasis_cbor::Decode)]
+struct Delegate {
+    to: Address,
+    amount: BaseUnits,
+    #[cbor(optional)]
+    receipt: u64,
+}
+
+#[derive(Clone, Debug, Default, oasis_cbor::Decode)]
+struct Undelegate {
+    from: Address,
+    shares: u128,
+    #[cbor(optional)]
+    receipt: u64,
+}
+
+#[derive(Clone, Debug, Default, oasis_cbor::Decode)]
+struct TakeReceipt {
+    id: u64,
+    kind: u8,
+}
+
+#[derive(Clone, Debug, Default, oasis_cbor::Decode)]
+struct Withdraw {
+    #[cbor(optional)]
+    to: Option<Address>,
+    amount: BaseUnits,
+}
+
+#[derive(Clone, Debug, Default, oasis_cbor::Decode)]
+struct Transfer {
+    to: Address,
+    amount: BaseUnits,
+}
+
+/// Error of a consensus operation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+pub struct ConsensusError {
+    #[cbor(optional)]
+    pub module: String,
+    #[cbor(optional)]
+    pub code: u32,
+}
+
+/// Receipt of a delegation or undelegation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+pub struct Receipt {
+    #[cbor(optional)]
+    pub shares: u128,
+    #[cbor(optional)]
+    pub epoch: u64,
+    #[cbor(optional)]
+    pub receipt: u64,
+    #[cbor(optional)]
+    pub amount: u128,
+    #[cbor(optional)]
+    pub error: Option<ConsensusError>,
+}
+
+/// Escrow pool of a consensus account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+struct Escrow {
+    balance: u128,
+    total_shares: u128,
+    /// Shares of each delegator.
+    delegations: BTreeMap<Address, u128>,
+}
+
+impl Escrow {
+    /// Shares issued for delegating the given amount.
+    fn shares_for_tokens(&self, amount: u128) -> u128 {
+        if self.total_shares == 0 || self.balance == 0 {
+            return amount;
+        }
+        mul_div(amount, self.total_shares, self.balance)
+    }
+
+    /// Tokens released for undelegating the given shares.
+    fn tokens_for_shares(&self, shares: u128) -> u128 {
+        if self.total_shares == 0 {
+            return 0;
+        }
+        mul_div(shares, self.balance, self.total_shares)
+    }
+}
+
+/// Tokens debonding until the given epoch.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+struct Debonding {
+    owner: Address,
+    amount: u128,
+    end: u64,
+    /// Identifier of the `UndelegateDone` receipt, zero for none.
+    receipt: u64,
+}
+
+/// Receipt that has not been taken yet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+struct StoredReceipt {
+    owner: Address,
+    kind: u8,
+    id: u64,
+    receipt: Receipt,
+}
+
+/// Persistent state of the emulated ledger.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+pub struct Ledger {
+    /// Balances of consensus accounts.
+    consensus_balances: BTreeMap<Address, u128>,
+    /// Balances of runtime accounts that are not EVM accounts.
+    runtime_balances: BTreeMap<Address, u128>,
+    /// EVM accounts that called the ledger, by runtime address.
+    evm_accounts: BTreeMap<Address, Vec<u8>>,
+    escrows: BTreeMap<Address, Escrow>,
+    debonding: Vec<Debonding>,
+    receipts: Vec<StoredReceipt>,
+    /// Last assigned identifier of an `UndelegateDone` receipt.
+    last_done_receipt: u64,
+}
+
+/// Changes to the balances of EVM accounts caused by a call.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Effects {
+    /// Amount taken from the calling contract.
+    pub debit: u128,
+    /// Amounts credited to EVM accounts.
+    pub credits: Vec<([u8; 20], u128)>,
+}
+
+/// Outcome of a call handled by the ledger.
+pub type CallResult = Result<Vec<u8>, CallError>;
+
+impl Ledger {
+    /// Whether the ledger handles the given method.
+    pub fn handles(method: &str) -> bool {
+        matches!(
+            method,
+            "consensus.Delegate"
+                | "consensus.Undelegate"
+                | "consensus.TakeReceipt"
+                | "consensus.Withdraw"
+                | "accounts.Transfer"
+        )
+    }
+
+    /// Dispatch a call by the given EVM account with the given balance in the given epoch.
+    pub fn call(
+        &mut self,
+        epoch: u64,
+        caller: [u8; 20],
+        balance: u128,
+        method: &str,
+        body: &[u8],
+    ) -> (CallResult, Effects) {
+        let mut effects = Effects::default();
+        let owner = Address::from_eth(&caller);
+        self.evm_accounts.insert(owner, caller.to_vec());
+        self.release_debonding(epoch, &mut effects);
+
+        let result = match method {
+            "consensus.Delegate" => {
+                decode(body).and_then(|body| self.delegate(owner, balance, body, &mut effects))
+            }
+            "consensus.Undelegate" => decode(body).map(|body| self.undelegate(epoch, owner, body)),
+            "consensus.TakeReceipt" => decode(body).and_then(|body| self.take_receipt(owner, body)),
+            "consensus.Withdraw" => decode(body).and_then(|body: Withdraw| {
+                let amount = native_amount(&body.amount)?;
+                debit(balance, amount, &mut effects)?;
+                *self
+                    .consensus_balances
+                    .entry(body.to.unwrap_or(owner))
+                    .or_default() += amount;
+                Ok(CBOR_NULL.to_vec())
+            }),
+            "accounts.Transfer" => decode(body).and_then(|body: Transfer| {
+                let amount = native_amount(&body.amount)?;
+                debit(balance, amount, &mut effects)?;
+                self.credit_runtime(body.to, amount, &mut effects);
+                Ok(CBOR_NULL.to_vec())
+            }),
+            _ => Err(ERR_INVALID_ARGUMENT),
+        };
+        if result.is_err() {
+            effects.debit = 0;
+        }
+        (result, effects)
+    }
+
+    fn delegate(
+        &mut self,
+        owner: Address,
+        balance: u128,
+        body: Delegate,
+        effects: &mut Effects,
+    ) -> CallResult {
+        let amount = native_amount(&body.amount)?;
+        debit(balance, amount, effects)?;
+
+        let escrow = self.escrows.entry(body.to).or_default();
+        let shares = escrow.shares_for_tokens(amount);
+        escrow.balance += amount;
+        escrow.total_shares += shares;
+        *escrow.delegations.entry(owner).or_default() += shares;
+
+        self.store_receipt(
+            owner,
+            RECEIPT_KIND_DELEGATE,
+            body.receipt,
+            Receipt {
+                shares,
+                ..Default::default()
+            },
+        );
+        Ok(CBOR_NULL.to_vec())
+    }
+
+    fn undelegate(&mut self, epoch: u64, owner: Address, body: Undelegate) -> Vec<u8> {
+        let escrow = self.escrows.entry(body.from).or_default();
+        let delegated = escrow.delegations.get(&owner).copied().unwrap_or_default();
+        if body.shares > delegated {
+            // The runtime accepts the call, the consensus layer then fails to reclaim the escrow.
+            self.store_receipt(
+                owner,
+                RECEIPT_KIND_UNDELEGATE_START,
+                body.receipt,
+                Receipt {
+                    error: Some(ConsensusError {
+                        module: STAKING_MODULE.to_string(),
+                        code: STAKING_ERR_INSUFFICIENT_BALANCE,
+                    }),
+                    ..Default::default()
+                },
+            );
+            return CBOR_NULL.to_vec();
+        }
+
+        let amount = escrow.tokens_for_shares(body.shares);
+        escrow.balance -= amount;
+        escrow.total_shares -= body.shares;
+        if delegated == body.shares {
+            escrow.delegations.remove(&owner);
+        } else {
+            escrow.delegations.insert(owner, delegated - body.shares);
+        }
+
+        let end = epoch.saturating_add(debonding_interval());
+        let done_receipt = if body.receipt != 0 {
+            self.last_done_receipt = self.last_done_receipt.max(FIRST_DONE_RECEIPT - 1) + 1;
+            self.last_done_receipt
+        } else {
+            0
+        };
+        self.debonding.push(Debonding {
+            owner,
+            amount,
+            end,
+            receipt: done_receipt,
+        });
+        self.store_receipt(
+            owner,
+            RECEIPT_KIND_UNDELEGATE_START,
+            body.receipt,
+            Receipt {
+                epoch: end,
+                receipt: done_receipt,
+                ..Default::default()
+            },
+        );
+        CBOR_NULL.to_vec()
+    }
+
+    fn take_receipt(&mut self, owner: Address, body: TakeReceipt) -> CallResult {
+        if !(RECEIPT_KIND_DELEGATE..=RECEIPT_KIND_UNDELEGATE_DONE).contains(&body.kind) {
+            return Err(ERR_INVALID_ARGUMENT);
+        }
+        let position = self
+            .receipts
+            .iter()
+            .position(|r| r.owner == owner && r.kind == body.kind && r.id == body.id);
+        Ok(match position {
+            Some(position) => oasis_cbor::to_vec(self.receipts.remove(position).receipt),
+            None => CBOR_NULL.to_vec(),
+        })
+    }
+
+    /// Release debonding tokens whose debonding ended by the given epoch.
+    fn release_debonding(&mut self, epoch: u64, effects: &mut Effects) {
+        let (released, debonding) = std::mem::take(&mut self.debonding)
+            .into_iter()
+            .partition(|d| d.end <= epoch);
+        self.debonding = debonding;
+        for debonding in released {
+            self.credit_runtime(debonding.owner, debonding.amount, effects);
+            self.store_receipt(
+                debonding.owner,
+                RECEIPT_KIND_UNDELEGATE_DONE,
+                debonding.receipt,
+                Receipt {
+                    amount: debonding.amount,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    fn credit_runtime(&mut self, to: Address, amount: u128, effects: &mut Effects) {
+        match self.evm_accounts.get(&to) {
+            Some(eth) => {
+                let mut eth_address = [0u8; 20];
+                eth_address.copy_from_slice(eth);
+                effects.credits.push((eth_address, amount));
+            }
+            None => *self.runtime_balances.entry(to).or_default() += amount,
+        }
+    }
+
+    fn store_receipt(&mut self, owner: Address, kind: u8, id: u64, receipt: Receipt) {
+        if id == 0 {
+            return;
+        }
+        self.receipts
+            .retain(|r| !(r.owner == owner && r.kind == kind && r.id == id));
+        self.receipts.push(StoredReceipt {
+            owner,
+            kind,
+            id,
+            receipt,
+        });
+    }
+
+    /// Load the given version of the ledger from the given file, run `f` on it and store the
+    /// result as the next version.
+    ///
+    /// Versions later than the given one belong to calls Forge has since reverted and are
+    /// discarded. Fails if the file does not hold the given version.
+    pub fn with_file<R>(
+        path: &Path,
+        version: u64,
+        f: impl FnOnce(&mut Ledger) -> R,
+    ) -> Result<R, String> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| format!("ledger {}: {}", path.display(), e))?;
+        file.lock()
+            .map_err(|e| format!("ledger {}: {}", path.display(), e))?;
+
+        let mut history =
+            load(&mut file).map_err(|e| format!("ledger {}: {}", path.display(), e))?;
+        let mut ledger = history
+            .checkout(version)
+            .map_err(|e| format!("ledger {}: {}", path.display(), e))?;
+        let result = f(&mut ledger);
+        history.push(ledger);
+        store(&mut file, &history).map_err(|e| format!("ledger {}: {}", path.display(), e))?;
+        Ok(result)
+    }
+}
+
+/// Versions of the ledger kept in its file.
+///
+/// The version of the ledger is kept in the storage of the emulated precompile (see
+/// `SubcallPrecompile` in `BinaryContracts.sol`), so that Forge reverts it together with the calls
+/// that changed the ledger, with the state between fuzz runs and with snapshots. Keeping every
+/// version allows going back to the one matching the EVM state.
+///
+/// Only the latest version is kept in full. Earlier versions are kept as the changes undoing each
+/// call, so that the file grows with what the calls change rather than with the size of the ledger.
+#[derive(Clone, Debug, Default, PartialEq, Eq, oasis_cbor::Encode, oasis_cbor::Decode)]
+struct History {
+    /// Ledger after the last call.
+    latest: Ledger,
+    /// Changes undoing each call, so that the latest version is their number. The empty ledger is
+    /// version zero.
+    undo: Vec<Value>,
+}
+
+impl History {
+    /// Drop the versions after the given one and return it.
+    fn checkout(&mut self, version: u64) -> Result<Ledger, String> {
+        let latest = self.undo.len() as u64;
+        if version > latest {
+            return Err(format!(
+                "version {} does not match the ledger (latest version {})",
+                version, latest
+            ));
+        }
+        let mut ledger = oasis_cbor::to_value(self.latest.clone());
+        for changes in self.undo.drain(version as usize..).rev() {
+            ledger = apply_changes(ledger, changes);
+        }
+        self.latest =
+            oasis_cbor::from_value(ledger).map_err(|e| format!("malformed ledger: {}", e))?;
+        Ok(self.latest.clone())
+    }
+
+    /// Store the ledger after a call as the next version.
+    fn push(&mut self, ledger: Ledger) {
+        let undo = changed_fields(
+            &oasis_cbor::to_value(ledger.clone()),
+            &oasis_cbor::to_value(self.latest.clone()),
+        );
+        self.undo.push(undo.unwrap_or(Value::Map(vec![])));
+        self.latest = ledger;
+    }
+}
+
+/// Fields of `new` that differ from `old`, descending into nested maps.
+///
+/// Fields missing from `new` are recorded as undefined. Returns `None` when nothing changed.
+fn changed_fields(old: &Value, new: &Value) -> Option<Value> {
+    let (Value::Map(old), Value::Map(new)) = (old, new) else {
+        return (old != new).then(|| new.clone());
+    };
+
+    let mut changes = Vec::new();
+    for (key, value) in new {
+        match old.iter().find(|(k, _)| k == key) {
+            Some((_, old_value)) => {
+                if let Some(change) = changed_fields(old_value, value) {
+                    changes.push((key.clone(), change));
+                }
+            }
+            None => changes.push((key.clone(), value.clone())),
+        }
+    }
+    for (key, _) in old {
+        if !new.iter().any(|(k, _)| k == key) {
+            changes.push((key.clone(), undefined()));
+        }
+    }
+    (!changes.is_empty()).then_some(Value::Map(changes))
+}
+
+/// Apply the given changes on top of `base`, descending into nested maps and removing the fields
+/// marked as undefined.
+fn apply_changes(base: Value, changes: Value) -> Value {
+    match (base, changes) {
+        (Value::Map(mut base), Value::Map(changes)) => {
+            for (key, change) in changes {
+                let position = base.iter().position(|(k, _)| *k == key);
+                match (position, change == undefined()) {
+                    (Some(i), true) => {
+                        base.remove(i);
+                    }
+                    (Some(i), false) => base[i].1 = apply_changes(base[i].1.clone(), change),
+                    (None, true) => {}
+                    (None, false) => base.push((key, change)),
+                }
+            }
+            Value::Map(base)
+        }
+        (_, changes) => changes,
+    }
+}
+
+fn undefined() -> Value {
+    Value::Simple(oasis_cbor::SimpleValue::Undefined)
+}
+
+/// Generate a fresh ledger identifier from the OS random number generator.
+///
+/// Each test deploys its own precompiles with a fresh ledger identifier (see `BinaryHandler`), so
+/// that tests do not observe each other's delegations. The identifier does not depend on the seed
+/// of the `random_bytes` precompile, which replaying a test reuses.
+pub fn new_id() -> [u8; 32] {
+    let mut id = [0u8; 32];
+    OsRng.fill_bytes(&mut id);
+    id
+}
+
+/// Path of the file holding the ledger with the given identifier.
+pub fn path(id: &[u8]) -> PathBuf {
+    env::temp_dir().join(format!("{}{}.cbor", FILE_PREFIX, hex::encode(id)))
+}
+
+/// Remove the ledger files that have not been used for [`STALE_AFTER`].
+///
+/// Forge has no hook at the end of a test, so the ledgers of earlier tests are removed once a new
+/// ledger is created instead (see `BinaryHandler`). Files that cannot be inspected are left alone.
+pub fn remove_stale() {
+    let Ok(entries) = fs::read_dir(env::temp_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(FILE_PREFIX) || !name.ends_with(".cbor") {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if stale {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn load(file: &mut File) -> Result<History, String> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    if data.is_empty() {
+        return Ok(History::default());
+    }
+    oasis_cbor::from_slice(&data).map_err(|e| format!("malformed ledger: {}", e))
+}
+
+fn store(file: &mut File, history: &History) -> Result<(), String> {
+    let data = oasis_cbor::to_vec(history.clone());
+    file.set_len(0).map_err(|e| e.to_string())?;
+    file.rewind().map_err(|e| e.to_string())?;
+    file.write_all(&data).map_err(|e| e.to_string())
+}
+
+fn decode<T: oasis_cbor::Decode>(body: &[u8]) -> Result<T, CallError> {
+    oasis_cbor::from_slice(body).map_err(|_| ERR_MALFORMED)
+}
+
+fn native_amount(amount: &BaseUnits) -> Result<u128, CallError> {
+    if !amount.denomination().is_native() {
+        return Err(ERR_INVALID_DENOMINATION);
+    }
+    Ok(amount.amount())
+}
+
+fn debit(balance: u128, amount: u128, effects: &mut Effects) -> Result<(), CallError> {
+    if amount > balance {
+        return Err(ERR_INSUFFICIENT_BALANCE);
+    }
+    effects.debit = amount;
+    Ok(())
+}
+
+fn debonding_interval() -> u64 {
+    env::var(DEBONDING_INTERVAL_ENV)
+        .ok()
+        .and_then(|interval| interval.parse().ok())
+        .unwrap_or(DEFAULT_DEBONDING_INTERVAL)
+}
+
+/// Compute `a * b / c` without overflowing in the intermediate product where possible.
+fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    match a.checked_mul(b) {
+        Some(product) => product / c,
+        None => (a / c).saturating_mul(b) + (a % c).saturating_mul(b) / c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use oasis_cbor::Value;
+    use oasis_runtime_sdk::types::token::Denomination;
+
+    const CALLER: [u8; 20] = [1; 20];
+
+    fn body(fields: Vec<(&str, Value)>) -> Vec<u8> {
+        oasis_cbor::to_vec(Value::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (Value::TextString(key.into()), value))
+                .collect(),
+        ))
+    }
+
+    fn address(address: Address) -> Value {
+        Value::ByteString(address.as_ref().to_vec())
+    }
+
+    fn native(amount: u128) -> Value {
+        oasis_cbor::to_value(BaseUnits::new(amount, Denomination::NATIVE))
+    }
+
+    fn delegate(to: Address, amount: u128, receipt: u64) -> Vec<u8> {
+        body(vec![
+            ("to", address(to)),
+            ("amount", native(amount)),
+            ("receipt", Value::Unsigned(receipt)),
+        ])
+    }
+
+    fn undelegate(from: Address, shares: u128, receipt: u64) -> Vec<u8> {
+        body(vec![
+            ("from", address(from)),
+            ("shares", oasis_cbor::to_value(shares)),
+            ("receipt", Value::Unsigned(receipt)),
+        ])
+    }
+
+    fn transfer(to: Address, amount: u128) -> Vec<u8> {
+        body(vec![("to", address(to)), ("amount", native(amount))])
+    }
+
+    fn take_receipt(
+        ledger: &mut Ledger,
+        epoch: u64,
+        kind: u8,
+        id: u64,
+    ) -> (Option<Receipt>, Effects) {
+        let body = body(vec![
+            ("id", Value::Unsigned(id)),
+            ("kind", Value::Unsigned(kind.into())),
+        ]);
+        let (result, effects) = ledger.call(epoch, CALLER, 0, "consensus.TakeReceipt", &body);
+        let result = result.unwrap();
+        if result == CBOR_NULL {
+            return (None, effects);
+        }
+        (Some(oasis_cbor::from_slice(&result).unwrap()), effects)
+    }
+
+    #[test]
+    fn test_delegation_lifecycle() {
+        let mut ledger = Ledger::default();
+        let validator = Address::from_eth(&[2; 20]);
+
+        let (result, effects) = ledger.call(
+            10,
+            CALLER,
+            1_000,
+            "consensus.Delegate",
+            &delegate(validator, 600, 7),
+        );
+        assert_eq!(result.unwrap(), CBOR_NULL);
+        assert_eq!(effects.debit, 600);
+        let (receipt, _) = take_receipt(&mut ledger, 10, RECEIPT_KIND_DELEGATE, 7);
+        assert_eq!(receipt.unwrap().shares, 600);
+        // Receipts can only be taken once.
+        assert_eq!(
+            take_receipt(&mut ledger, 10, RECEIPT_KIND_DELEGATE, 7).0,
+            None
+        );
+
+        // Rewards make each share worth more tokens.
+        ledger.escrows.get_mut(&validator).unwrap().balance += 600;
+        let (result, _) = ledger.call(
+            10,
+            CALLER,
+            400,
+            "consensus.Delegate",
+            &delegate(validator, 400, 8),
+        );
+        assert!(result.is_ok());
+        let (receipt, _) = take_receipt(&mut ledger, 10, RECEIPT_KIND_DELEGATE, 8);
+        assert_eq!(receipt.unwrap().shares, 200);
+
+        let (result, effects) = ledger.call(
+            11,
+            CALLER,
+            0,
+            "consensus.Undelegate",
+            &undelegate(validator, 400, 9),
+        );
+        assert_eq!(result.unwrap(), CBOR_NULL);
+        assert_eq!(effects, Effects::default());
+        let (start, _) = take_receipt(&mut ledger, 11, RECEIPT_KIND_UNDELEGATE_START, 9);
+        let start = start.unwrap();
+        assert_eq!(start.epoch, 11 + DEFAULT_DEBONDING_INTERVAL);
+        assert_eq!(start.receipt, FIRST_DONE_RECEIPT);
+        let (done, effects) =
+            take_receipt(&mut ledger, 11, RECEIPT_KIND_UNDELEGATE_DONE, start.receipt);
+        assert_eq!(done, None);
+        assert!(effects.credits.is_empty());
+
+        // Debonding tokens are released at the end of the debonding interval.
+        let (done, effects) =
+            take_receipt(&mut ledger, 12, RECEIPT_KIND_UNDELEGATE_DONE, start.receipt);
+        assert_eq!(done.unwrap().amount, 800);
+        assert_eq!(effects.credits, vec![(CALLER, 800)]);
+
+        // Undelegating more shares than delegated fails in the consensus layer.
+        let (result, _) = ledger.call(
+            12,
+            CALLER,
+            0,
+            "consensus.Undelegate",
+            &undelegate(validator, 401, 10),
+        );
+        assert!(result.is_ok());
+        let (start, _) = take_receipt(&mut ledger, 12, RECEIPT_KIND_UNDELEGATE_START, 10);
+        assert_eq!(start.unwrap().error.unwrap().module, STAKING_MODULE);
+
+        let (result, _) = ledger.call(12, CALLER, 0, "consensus.TakeReceipt", &[0xf6]);
+        assert_eq!(result.unwrap_err(), ERR_MALFORMED);
+        let (result, _) = ledger.call(
+            12,
+            CALLER,
+            0,
+            "consensus.TakeReceipt",
+            &body(vec![
+                ("id", Value::Unsigned(1)),
+                ("kind", Value::Unsigned(4)),
+            ]),
+        );
+        assert_eq!(result.unwrap_err(), ERR_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn test_transfers() {
+        let mut ledger = Ledger::default();
+        let other = [3; 20];
+        let account = Address::from_eth(&[4; 20]);
+
+        // Transfers to EVM accounts known to the ledger are credited in the EVM.
+        ledger.call(1, other, 0, "consensus.Withdraw", &transfer(account, 0));
+        let (result, effects) = ledger.call(
+            1,
+            CALLER,
+            100,
+            "accounts.Transfer",
+            &transfer(Address::from_eth(&other), 60),
+        );
+        assert!(result.is_ok());
+        assert_eq!(effects.debit, 60);
+        assert_eq!(effects.credits, vec![(other, 60)]);
+
+        let (result, _) = ledger.call(1, CALLER, 40, "accounts.Transfer", &transfer(account, 40));
+        assert!(result.is_ok());
+        assert_eq!(ledger.runtime_balances[&account], 40);
+
+        let (result, _) = ledger.call(1, CALLER, 40, "consensus.Withdraw", &transfer(account, 30));
+        assert!(result.is_ok());
+        assert_eq!(ledger.consensus_balances[&account], 30);
+
+        let (result, effects) =
+            ledger.call(1, CALLER, 10, "consensus.Withdraw", &transfer(account, 30));
+        assert_eq!(result.unwrap_err(), ERR_INSUFFICIENT_BALANCE);
+        assert_eq!(effects, Effects::default());
+    }
+
+    #[test]
+    fn test_file() {
+        let path = path(&std::process::id().to_be_bytes());
+        let _ = std::fs::remove_file(&path);
+
+        let validator = Address::from_eth(&[2; 20]);
+        let (result, _) = Ledger::with_file(&path, 0, |ledger| {
+            ledger.call(
+                1,
+                CALLER,
+                100,
+                "consensus.Delegate",
+                &delegate(validator, 100, 1),
+            )
+        })
+        .unwrap();
+        assert!(result.is_ok());
+        let receipt = Ledger::with_file(&path, 1, |ledger| {
+            take_receipt(ledger, 1, RECEIPT_KIND_DELEGATE, 1).0
+        });
+        assert_eq!(receipt.unwrap().unwrap().shares, 100);
+
+        // Versions ahead of the file are rejected.
+        assert!(Ledger::with_file(&path, 3, |_| ()).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_revert() {
+        let path = path(&new_id());
+        let validator = Address::from_eth(&[2; 20]);
+
+        // The delegation at version 0 is reverted, the call at version 0 starts over.
+        let (result, _) = Ledger::with_file(&path, 0, |ledger| {
+            ledger.call(
+                1,
+                CALLER,
+                100,
+                "consensus.Delegate",
+                &delegate(validator, 100, 1),
+            )
+        })
+        .unwrap();
+        assert!(result.is_ok());
+        let escrows = Ledger::with_file(&path, 0, |ledger| ledger.escrows.clone()).unwrap();
+        assert!(escrows.is_empty());
+
+        // The reverted version is discarded.
+        assert!(Ledger::with_file(&path, 2, |_| ()).is_err());
+        assert!(Ledger::with_file(&path, 1, |_| ()).is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_history() {
+        let validator = Address::from_eth(&[2; 20]);
+        let mut history = History::default();
+        let mut versions = vec![Ledger::default()];
+        let mut ledger = Ledger::default();
+        let calls = [
+            ("consensus.Delegate", delegate(validator, 100, 1)),
+            ("consensus.Undelegate", undelegate(validator, 40, 2)),
+            ("accounts.Transfer", transfer(validator, 10)),
+            // Failed calls leave the ledger unchanged.
+            ("consensus.Withdraw", transfer(validator, 1_000)),
+            ("consensus.Undelegate", undelegate(validator, 60, 3)),
+        ];
+        for (epoch, (method, body)) in calls.iter().enumerate() {
+            ledger.call(epoch as u64, CALLER, 100, method, body);
+            history.push(ledger.clone());
+            versions.push(ledger.clone());
+        }
+        // Debonding ends, the receipt of the delegation is taken.
+        take_receipt(&mut ledger, 10, RECEIPT_KIND_DELEGATE, 1);
+        history.push(ledger.clone());
+        versions.push(ledger.clone());
+        assert_eq!(history.undo[3], Value::Map(vec![]));
+
+        // Every version can be restored from the undo changes, dropping the later ones.
+        for version in (0..versions.len()).rev() {
+            let restored = history.checkout(version as u64).unwrap();
+            assert_eq!(restored, versions[version], "version {version}");
+            assert_eq!(history.undo.len(), version);
+        }
+
+        // The file holds the changes of each call rather than a copy of the ledger.
+        let mut history = History::default();
+        let mut ledger = Ledger::default();
+        for i in 0..100u8 {
+            let owner = [i; 20];
+            ledger.call(0, owner, 100, "consensus.Withdraw", &transfer(validator, 1));
+            history.push(ledger.clone());
+        }
+        let size = |history: &History| oasis_cbor::to_vec(history.clone()).len();
+        let before = size(&history);
+        ledger.call(
+            0,
+            [100; 20],
+            100,
+            "consensus.Withdraw",
+            &transfer(validator, 1),
+        );
+        history.push(ledger);
+        assert!(size(&history) - before < 200);
+    }
+
+    #[test]
+    fn test_remove_stale() {
+        let stale = path(&new_id());
+        let fresh = path(&new_id());
+        let day = Duration::from_secs(24 * 60 * 60);
+        File::create(&stale)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - STALE_AFTER - day)
+            .unwrap();
+        File::create(&fresh).unwrap();
+
+        remove_stale();
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+
+        std::fs::remove_file(&fresh).unwrap();
+    }
+
+    #[test]
+    fn test_new_id() {
+        assert_ne!(new_id(), new_id());
+    }
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div(10, 3, 4), 7);
+        assert_eq!(mul_div(u128::MAX, 2, 4), u128::MAX / 2);
+    }
+}
@@ -1,11 +1,10 @@
 use std::sync::Arc;
 use ethabi::{ParamType, Token};
-use rand::Rng;
 use hex;
 use x25519_dalek; 
 use hmac::{Hmac, Mac};
-use sha2::Sha512_256;
-use std::{env, process};
+use sha2::{Digest, Sha384, Sha512, Sha512_256};
+use std::{env, io, process};
 use oasis_cbor;
 
 use oasis_runtime_sdk::{
@@ -46,6 +45,11 @@ use evm::{
     executor::stack::{PrecompileFailure, PrecompileHandle, PrecompileOutput},
     ExitError, ExitRevert, ExitSucceed,
 };
+mod gas;
+mod ledger;
+mod random;
+mod server;
+
 const WORD: usize = 32;
 
 #[derive(oasis_cbor::Encode)]
@@ -90,21 +94,25 @@ fn decode_deoxysii_args(input: &[u8]) -> Result<([u8; KEY_SIZE], [u8; NONCE_SIZE
 }
 
 fn handle_random_bytes(input: &[u8]) -> Result<Vec<u8>, String> {
+    // The precompile arguments followed by the seed, caller and counter kept by the contract.
     let call_args = ethabi::decode(
-        &[ParamType::Uint(256), ParamType::Bytes],
+        &[
+            ParamType::Uint(256),
+            ParamType::Bytes,
+            ParamType::FixedBytes(32),
+            ParamType::Address,
+            ParamType::Uint(256),
+        ],
         input,
     ).map_err(|e| e.to_string())?;
 
-    // let pers_str = call_args[1].clone().into_bytes().unwrap();
+    let pers_str = call_args[1].clone().into_bytes().unwrap();
     let num_bytes: u64 = call_args[0].clone().into_uint().unwrap().try_into().unwrap_or(u64::MAX);
+    let seed = call_args[2].clone().into_fixed_bytes().unwrap();
+    let caller = call_args[3].clone().into_address().unwrap();
+    let counter: u64 = call_args[4].clone().into_uint().unwrap().try_into().unwrap_or(u64::MAX);
 
-    let mut rng = rand::thread_rng();
-    let mut result = Vec::with_capacity(num_bytes as usize);
-    for _ in 0..num_bytes {
-        result.push(rng.gen());
-    }
-
-    Ok(result)
+    Ok(random::derive(&seed, caller.as_bytes(), counter, &pers_str, num_bytes as usize))
 }
 
 fn handle_x25519_derive(input: &[u8]) -> Result<Vec<u8>, String> {
@@ -261,20 +269,19 @@ fn handle_verify(input: &[u8]) -> Result<Vec<u8>, String> {
 }
 
 fn handle_gas_used(input: &[u8]) -> Result<Vec<u8>, String> {
-    // Simply return a fixed gas cost for now since we can't 
-    // actually track gas usage in the standalone binary
-    let used_gas: u64 = 10;
-    
+    // Sapphire ignores the input, the emulated precompile passes the gas it needs for accounting
+    let call_args = ethabi::decode(&gas::Meter::TYPES, input).map_err(|e| e.to_string())?;
+    let used_gas = gas::used(&gas::Meter::from_tokens(&call_args), gas::GAS_USED_COST);
+
     // Return the gas usage encoded as uint256
     Ok(ethabi::encode(&[Token::Uint(used_gas.into())]))
 }
 
 fn handle_pad_gas(input: &[u8]) -> Result<Vec<u8>, String> {
-    // Decode the target gas amount
-    let call_args = ethabi::decode(
-        &[ParamType::Uint(128)],
-        input,
-    ).map_err(|e| e.to_string())?;
+    // Decode the target gas amount, followed by the gas needed for accounting
+    let mut types = vec![ParamType::Uint(128)];
+    types.extend(gas::Meter::TYPES);
+    let call_args = ethabi::decode(&types, input).map_err(|e| e.to_string())?;
 
     let gas_amount: u64 = call_args[0]
         .clone()
@@ -283,16 +290,26 @@ fn handle_pad_gas(input: &[u8]) -> Result<Vec<u8>, String> {
         .try_into()
         .unwrap_or(u64::MAX);
 
-    // For simulation purposes, assume we've used 10 gas so far
-    let used_gas: u64 = 10;
+    // Fails if more gas than desired padding was already used
+    let gas_end = gas::pad(gas_amount, &gas::Meter::from_tokens(&call_args[1..]))?;
 
-    // Fail if more gas than desired padding was already used
-    if gas_amount < used_gas {
-        return Err("gas pad amount less than already used gas".into());
-    }
+    // Return the gas the precompile must have left after padding
+    Ok(ethabi::encode(&[Token::Uint(gas_end.into())]))
+}
+
+// Like on Sapphire, the hash precompiles take the raw message as input and return the raw digest.
+// The input is only bounded by the gas cost of 115 gas plus 13 gas per word.
 
-    // Return empty output since pad_gas doesn't return anything
-    Ok(Vec::new())
+fn handle_sha512_256(input: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(Sha512_256::digest(input).to_vec())
+}
+
+fn handle_sha512(input: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(Sha512::digest(input).to_vec())
+}
+
+fn handle_sha384(input: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(Sha384::digest(input).to_vec())
 }
 
 
@@ -304,6 +321,10 @@ fn handle_subcall(input: &[u8]) -> Result<Vec<u8>, String> {
             ParamType::String,    // method
             ParamType::Bytes,     // body (CBOR)
             ParamType::FixedBytes(32), // private key
+            ParamType::Address,   // caller
+            ParamType::Uint(256), // caller balance
+            ParamType::FixedBytes(32), // ledger id
+            ParamType::Uint(256), // ledger version
         ],
         &input,
     ).map_err(|e| e.to_string())?;
@@ -312,12 +333,23 @@ fn handle_subcall(input: &[u8]) -> Result<Vec<u8>, String> {
     let method = String::from_utf8(call_args[1].clone().into_string().unwrap().into_bytes())
         .map_err(|_| "method is malformed".to_string())?;
     let epoch = call_args[0].clone().into_uint().unwrap().try_into().unwrap_or(u64::MAX);
+    let version: u64 = call_args[7].clone().into_uint().unwrap().try_into().unwrap_or(u64::MAX);
 
     if method.starts_with("evm.") {
-        return Ok(ethabi::encode(&[
-            Token::Uint(1.into()),    // Error status
-            Token::Bytes("core".into()) // Module name
-        ]));
+        return Ok(subcall_output(1, "core".into(), &ledger::Effects::default(), version));
+    }
+
+    if ledger::Ledger::handles(&method) {
+        let caller = call_args[4].clone().into_address().unwrap();
+        let balance = call_args[5].clone().into_uint().unwrap().try_into().unwrap_or(u128::MAX);
+        let path = ledger::path(&call_args[6].clone().into_fixed_bytes().unwrap());
+        let (result, effects) = ledger::Ledger::with_file(&path, version, |ledger| {
+            ledger.call(epoch, caller.0, balance, &method, &body)
+        })?;
+        return Ok(match result {
+            Ok(data) => subcall_output(0, data, &effects, version + 1),
+            Err(e) => subcall_output(e.code.into(), e.module.into(), &effects, version + 1),
+        });
     }
  
     match method.as_str() {
@@ -357,10 +389,7 @@ fn handle_subcall(input: &[u8]) -> Result<Vec<u8>, String> {
 
             let response_bytes = oasis_cbor::to_vec(response);
 
-            Ok(ethabi::encode(&[
-                Token::Uint(0.into()),    // Success status
-                Token::Bytes(response_bytes),
-            ]))
+            Ok(subcall_output(0, response_bytes, &ledger::Effects::default(), version))
         },
  
         "core.CurrentEpoch" => {
@@ -368,10 +397,7 @@ fn handle_subcall(input: &[u8]) -> Result<Vec<u8>, String> {
                 return Err("invalid body format".into()); 
             }
  
-            Ok(ethabi::encode(&[
-                Token::Uint(0.into()),
-                Token::Bytes(epoch.to_be_bytes().to_vec()),
-            ]))
+            Ok(subcall_output(0, epoch.to_be_bytes().to_vec(), &ledger::Effects::default(), version))
         },
 
         "rofl.IsAuthorizedOrigin" => {
@@ -380,10 +406,8 @@ fn handle_subcall(input: &[u8]) -> Result<Vec<u8>, String> {
                 return Err("invalid body format".into());
             }
 
-            Ok(ethabi::encode(&[
-                Token::Uint(0.into()),    // Success status
-                Token::Bytes(vec![0xf5]), // CBOR-encoded true
-            ]))
+            // CBOR-encoded true
+            Ok(subcall_output(0, vec![0xf5], &ledger::Effects::default(), version))
         },
 
         "rofl.OriginApp" => {
@@ -391,21 +415,29 @@ fn handle_subcall(input: &[u8]) -> Result<Vec<u8>, String> {
                 return Err("invalid body format".into());
             }
 
-            Ok(ethabi::encode(&[
-                Token::Uint(0.into()),    // Success status
-                Token::Bytes(vec![0x55, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), // CBOR-encoded 21 elements array of zeros
-            ]))
+            // CBOR-encoded 21 bytes of zeros
+            Ok(subcall_output(0, vec![0x55, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &ledger::Effects::default(), version))
         },
 
         _ => {
-            Ok(ethabi::encode(&[
-                Token::Uint(1.into()),
-                Token::Bytes("unknown".into())
-            ]))
+            Ok(subcall_output(1, "unknown".into(), &ledger::Effects::default(), version))
         }
     }
 }
 
+/// Encode the result of a subcall together with its effects on EVM balances and the version of the
+/// ledger after the call.
+fn subcall_output(status: u64, data: Vec<u8>, effects: &ledger::Effects, version: u64) -> Vec<u8> {
+    ethabi::encode(&[
+        Token::Uint(status.into()),
+        Token::Bytes(data),
+        Token::Uint(effects.debit.into()),
+        Token::Array(effects.credits.iter().map(|(to, _)| Token::Address((*to).into())).collect()),
+        Token::Array(effects.credits.iter().map(|(_, amount)| Token::Uint((*amount).into())).collect()),
+        Token::Uint(version.into()),
+    ])
+}
+
 fn handle_decode(input: &[u8]) -> Result<Vec<u8>, String> {
     // Add debug prints for input data
     // println!("Raw input: {}", hex::encode(input));
@@ -476,8 +508,62 @@ fn handle_decode(input: &[u8]) -> Result<Vec<u8>, String> {
 }
 
 
+fn dispatch(name: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+    match name {
+        "random_bytes" => handle_random_bytes(input),
+        "x25519_derive" => handle_x25519_derive(input),
+        "curve25519_compute_public" => handle_curve25519_compute_public(input),
+        "deoxysii_seal" => handle_deoxysii_seal(input),
+        "deoxysii_open" => handle_deoxysii_open(input),
+        "keypair_generate" => handle_keypair_generate(input),
+        "sign" => handle_sign(input),
+        "verify" => handle_verify(input),
+        "gas_used" => handle_gas_used(input),
+        "pad_gas" => handle_pad_gas(input),
+        "sha512_256" => handle_sha512_256(input),
+        "sha512" => handle_sha512(input),
+        "sha384" => handle_sha384(input),
+        "subcall" => handle_subcall(input),
+        "decode" => handle_decode(input),
+        "ledger_id" => {
+            ledger::remove_stale();
+            Ok(ledger::new_id().to_vec())
+        }
+        _ => Err("Unknown precompile".into()),
+    }
+}
+
+/// Run the given handler, returning its output together with the gas Sapphire charges for the
+/// call, ABI-encoded as `(uint256, bytes)`.
+fn run(name: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+    let output = dispatch(name, input)?;
+    let cost = gas::cost(name, input)?;
+    Ok(ethabi::encode(&[Token::Uint(cost.into()), Token::Bytes(output)]))
+}
+
+/// Serve calls over JSON-RPC (see [`server`]) on the given address, or on the default one, until
+/// terminated.
+fn serve(args: &[String]) -> io::Result<()> {
+    match args {
+        [] => server::listen(&server::addr(), run),
+        [addr] => server::listen(addr, run),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "usage: server [<address>]")),
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let binary_name = args[0].clone();
+    let binary_name = binary_name.split('/').last().unwrap_or(&binary_name);
+
+    if binary_name == "server" {
+        if let Err(e) = serve(&args[1..]) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     if args.len() != 2 {
         eprintln!("Usage: {} <hex-encoded input>", args[0]);
         process::exit(1);
@@ -490,22 +576,7 @@ fn main() {
         process::exit(1);
     });
 
-    let binary_name = args[0].clone();
-    let result = match binary_name.split('/').last().unwrap_or(&binary_name) {
-        "random_bytes" => handle_random_bytes(&input),
-        "x25519_derive" => handle_x25519_derive(&input),
-        "curve25519_compute_public" => handle_curve25519_compute_public(&input),
-        "deoxysii_seal" => handle_deoxysii_seal(&input),
-        "deoxysii_open" => handle_deoxysii_open(&input),
-        "keypair_generate" => handle_keypair_generate(&input),
-        "sign" => handle_sign(&input),
-        "verify" => handle_verify(&input),
-        "gas_used" => handle_gas_used(&input),
-        "pad_gas" => handle_pad_gas(&input),
-        "subcall" => handle_subcall(&input),
-        "decode" => handle_decode(&input),
-        _ => Err("Unknown precompile".into()),
-    };
+    let result = run(binary_name, &input);
 
     match result {
         Ok(output) => {
@@ -517,4 +588,4 @@ fn main() {
             process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
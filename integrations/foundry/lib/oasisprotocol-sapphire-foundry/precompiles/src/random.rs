@@ -0,0 +1,62 @@
+//! Deterministic generator backing the `random_bytes` precompile.
+//!
+//! Each call derives a fresh HMAC-SHA512_256 key from the seed of the running test, the calling
+//! contract, the number of its previous calls and the personalization string, and expands it into
+//! the requested number of bytes. Like on Sapphire, different personalization strings give
+//! independent streams and repeated calls give different bytes.
+//!
+//! The seed and the per-caller counters are kept in the storage of the emulated precompile (see
+//! `RandomBytesPrecompile` in `BinaryContracts.sol`) and passed along with each call. Forge resets
+//! them for every test, so a test produces the same bytes with the same seed regardless of the
+//! tests run before or in parallel with it.
+use hmac::{Hmac, Mac};
+use sha2::Sha512_256;
+
+/// Domain separation context of the per-call keys.
+const CONTEXT: &[u8] = b"oasis-sapphire-foundry/random_bytes: v2";
+
+type HmacSha512_256 = Hmac<Sha512_256>;
+
+/// Derive `num_bytes` bytes for the given call of `caller` with the given personalization string.
+pub fn derive(seed: &[u8], caller: &[u8], counter: u64, pers: &[u8], num_bytes: usize) -> Vec<u8> {
+    let mut mac = HmacSha512_256::new_from_slice(seed).expect("HMAC accepts any key size");
+    mac.update(CONTEXT);
+    mac.update(caller);
+    mac.update(&counter.to_be_bytes());
+    mac.update(pers);
+    let key = mac.finalize().into_bytes();
+
+    let mut output = Vec::with_capacity(num_bytes);
+    let mut block = 0u64;
+    while output.len() < num_bytes {
+        let mut mac = HmacSha512_256::new_from_slice(&key).expect("HMAC accepts any key size");
+        mac.update(&block.to_be_bytes());
+        output.extend_from_slice(&mac.finalize().into_bytes());
+        block += 1;
+    }
+    output.truncate(num_bytes);
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_derive() {
+        let (seed, caller) = (b"seed", [1u8; 20]);
+        let bytes = derive(seed, &caller, 0, b"pers", 100);
+        assert_eq!(bytes.len(), 100);
+        assert_eq!(bytes, derive(seed, &caller, 0, b"pers", 100));
+        // Shorter outputs are prefixes of longer ones.
+        assert_eq!(bytes[..10], derive(seed, &caller, 0, b"pers", 10));
+        assert!(derive(seed, &caller, 0, b"pers", 0).is_empty());
+
+        // The seed, caller, counter and personalization string each select an independent stream.
+        assert_ne!(bytes, derive(b"other seed", &caller, 0, b"pers", 100));
+        assert_ne!(bytes, derive(seed, &[2; 20], 0, b"pers", 100));
+        assert_ne!(bytes, derive(seed, &caller, 1, b"pers", 100));
+        assert_ne!(bytes, derive(seed, &caller, 0, b"other pers", 100));
+        assert_ne!(bytes, derive(seed, &caller, 0, b"", 100));
+    }
+}
@@ -0,0 +1,369 @@
+//! JSON-RPC server answering emulated precompile calls over HTTP.
+//!
+//! The precompile contracts reach it through Forge's `vm.rpc` cheatcode, so no process is started
+//! per call. Each handler (e.g. `random_bytes`) is exposed as the method `sapphire_<handler>`,
+//! which takes the hex-encoded input as its only parameter and returns the hex-encoded output.
+//! Failed calls are answered with a JSON-RPC error carrying the error of the handler.
+//!
+//! Every connection is served by its own thread, so an idle or stalled client does not hold up
+//! the others, and is closed once it stays silent for [`READ_TIMEOUT`]. Calls themselves are run
+//! one at a time since handlers share state such as the emulated ledger.
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+/// Environment variable overriding the address the server listens on.
+pub const ADDR_ENV: &str = "SAPPHIRE_PRECOMPILES_ADDR";
+
+/// Default address the server listens on.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8555";
+
+/// Prefix of the JSON-RPC method of each handler.
+pub const METHOD_PREFIX: &str = "sapphire_";
+
+/// Time a connection may stay silent before it is closed.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum size of a request line or header.
+const MAX_LINE_SIZE: u64 = 8 * 1024;
+
+/// Maximum size of a request body.
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Runs a handler by name on the given input.
+pub type Handler = fn(&str, &[u8]) -> Result<Vec<u8>, String>;
+
+/// Serializes calls of the handler across connections.
+static CALL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Address the server listens on.
+pub fn addr() -> String {
+    env::var(ADDR_ENV).unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+}
+
+/// Serve calls of `handler` on `addr` until terminated.
+pub fn listen(addr: &str, handler: Handler) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("Serving precompiles on http://{}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Connection error: {}", e);
+                continue;
+            }
+        };
+        thread::spawn(move || {
+            if let Err(e) = serve_connection(stream, handler) {
+                eprintln!("Connection error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn serve_connection(stream: TcpStream, handler: Handler) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                write_response(&mut writer, "400 Bad Request", &e.to_string(), false)?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        if request.method != "POST" {
+            write_response(
+                &mut writer,
+                "405 Method Not Allowed",
+                "only POST is supported",
+                false,
+            )?;
+            return Ok(());
+        }
+        let body = handle_body(&request.body, handler);
+        write_response(&mut writer, "200 OK", &body.to_string(), request.keep_alive)?;
+        if !request.keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// An HTTP request.
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: String,
+    body: Vec<u8>,
+    keep_alive: bool,
+}
+
+/// Read an HTTP request, returning `None` if the stream ended before it.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let mut parts = line.split_whitespace();
+    let (method, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(_), Some(version), None) => (method.to_string(), version),
+        _ => return Err(invalid_data("malformed request line")),
+    };
+    let mut keep_alive = version == "HTTP/1.1";
+
+    let mut content_length = 0;
+    loop {
+        let header =
+            read_line(reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid_data("malformed header"))?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .parse()
+                .map_err(|_| invalid_data("malformed content length"))?;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(invalid_data("transfer encodings are not supported"));
+        } else if name.eq_ignore_ascii_case("connection") {
+            if value.eq_ignore_ascii_case("close") {
+                keep_alive = false;
+            } else if value.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(invalid_data("request too large"));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Request {
+        method,
+        body,
+        keep_alive,
+    }))
+}
+
+/// Read a CRLF-terminated line, returning `None` if the stream ended before it.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    reader.take(MAX_LINE_SIZE).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\n") {
+        return Err(invalid_data("line too long or truncated"));
+    }
+    let line = String::from_utf8(line).map_err(|_| invalid_data("malformed line"))?;
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn write_response(
+    writer: &mut impl Write,
+    status: &str,
+    body: &str,
+    keep_alive: bool,
+) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        if keep_alive { "keep-alive" } else { "close" },
+        body,
+    )?;
+    writer.flush()
+}
+
+/// Answer a JSON-RPC request.
+fn handle_body(body: &[u8], handler: Handler) -> Value {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(_) => return error(Value::Null, -32700, "parse error"),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let name = match request.get("method").and_then(Value::as_str) {
+        Some(method) => match method.strip_prefix(METHOD_PREFIX) {
+            Some(name) => name,
+            None => return error(id, -32601, "method not found"),
+        },
+        None => return error(id, -32600, "invalid request"),
+    };
+    let input = match request
+        .get("params")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+    {
+        Some([Value::String(input)]) => match hex::decode(input.trim_start_matches("0x")) {
+            Ok(input) => input,
+            Err(e) => return error(id, -32602, &format!("failed to decode hex input: {}", e)),
+        },
+        _ => {
+            return error(
+                id,
+                -32602,
+                "expected the hex-encoded input as the only parameter",
+            )
+        }
+    };
+
+    let result = {
+        let _guard = CALL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        handler(name, &input)
+    };
+    match result {
+        Ok(output) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": format!("0x{}", hex::encode(output)),
+        }),
+        Err(e) => error(id, -32000, &e),
+    }
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn echo(name: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+        match name {
+            "echo" => Ok(input.to_vec()),
+            _ => Err("Unknown precompile".into()),
+        }
+    }
+
+    fn call(body: &str) -> Value {
+        handle_body(body.as_bytes(), echo)
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n{}\
+                    POST / HTTP/1.1\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+        let mut reader = &raw[..];
+        assert_eq!(
+            read_request(&mut reader).unwrap(),
+            Some(Request {
+                method: "POST".into(),
+                body: b"{}".to_vec(),
+                keep_alive: true
+            })
+        );
+        assert_eq!(
+            read_request(&mut reader).unwrap(),
+            Some(Request {
+                method: "POST".into(),
+                body: vec![],
+                keep_alive: false
+            })
+        );
+        // The stream may only end between requests.
+        assert_eq!(read_request(&mut reader).unwrap(), None);
+        assert!(read_request(&mut &raw[..40]).is_err());
+        assert!(read_request(&mut &raw[..56]).is_err());
+
+        let raw = format!(
+            "POST / HTTP/1.0\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_SIZE + 1
+        );
+        assert!(read_request(&mut raw.as_bytes()).is_err());
+        let raw = format!(
+            "POST /{} HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_LINE_SIZE as usize)
+        );
+        assert!(read_request(&mut raw.as_bytes()).is_err());
+        assert!(read_request(&mut &b"POST\r\n\r\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_handle_body() {
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":7,"method":"sapphire_echo","params":["0x0102"]}"#),
+            json!({"jsonrpc": "2.0", "id": 7, "result": "0x0102"})
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":1,"method":"sapphire_sha384","params":["0x"]}"#)["error"],
+            json!({"code": -32000, "message": "Unknown precompile"})
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":1,"method":"eth_call","params":["0x"]}"#)["error"]
+                ["code"],
+            -32601
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":1,"method":"sapphire_echo","params":["0xzz"]}"#)["error"]
+                ["code"],
+            -32602
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc":"2.0","id":1,"method":"sapphire_echo","params":[]}"#)["error"]
+                ["code"],
+            -32602
+        );
+        assert_eq!(call("{")["error"]["code"], -32700);
+    }
+
+    #[test]
+    fn test_serve_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream, echo).unwrap();
+        });
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"sapphire_echo","params":["0xaa"]}"#;
+        let request = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        // Both requests are answered on the same connection.
+        for _ in 0..2 {
+            stream.write_all(request.as_bytes()).unwrap();
+            assert_eq!(read_line(&mut reader).unwrap().unwrap(), "HTTP/1.1 200 OK");
+            let mut content_length = 0;
+            while let Some(header) = read_line(&mut reader).unwrap().filter(|h| !h.is_empty()) {
+                if let Some(value) = header.strip_prefix("Content-Length: ") {
+                    content_length = value.parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let response: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(response["result"], "0xaa");
+        }
+        drop((reader, stream));
+        server.join().unwrap();
+    }
+}
@@ -5,6 +5,35 @@ import "forge-std/Test.sol";
 import {console} from "forge-std/console.sol";
 import "lib/oasisprotocol-sapphire-foundry/BaseSapphireTest.sol";
 
+contract GasPadding is Precompiles {
+    bytes32 public tmp;
+
+    function gasUsed(bool work) external returns (uint256) {
+        if (work) {
+            tmp = keccak256(abi.encodePacked(tmp));
+        }
+        (bool success, bytes memory result) = GAS_USED.staticcall("");
+        require(success, "gas_used failed");
+        return abi.decode(result, (uint256));
+    }
+
+    function padGas(bool work, uint128 amount) external {
+        if (work) {
+            for (uint256 i = 0; i < 100; i++) {
+                tmp = keccak256(abi.encodePacked(tmp, i));
+            }
+        }
+        (bool success, ) = PAD_GAS.staticcall(abi.encode(amount));
+        require(success, "pad_gas failed");
+    }
+}
+
+contract GasRelay {
+    function gasUsed(GasPadding padding, bool work) external returns (uint256) {
+        return padding.gasUsed(work);
+    }
+}
+
 contract PrecompileTest is SapphireTest {
     
     function setUp() public override {
@@ -39,6 +68,24 @@ contract PrecompileTest is SapphireTest {
         assertNotEq(keccak256(result_static), keccak256(result), "Results should be different");
     }
 
+    function testRandomBytesReplay() public {
+        bytes memory inputData = abi.encode(uint(32), bytes("test"));
+        (, bytes memory first) = RANDOM_BYTES.staticcall(inputData);
+        (, bytes memory second) = RANDOM_BYTES.staticcall(inputData);
+        assertNotEq(keccak256(first), keccak256(second), "Results should be different");
+
+        // Each caller has its own stream.
+        GasPadding other = new GasPadding();
+        vm.prank(address(other));
+        (, bytes memory otherFirst) = RANDOM_BYTES.staticcall(inputData);
+        assertNotEq(keccak256(otherFirst), keccak256(first), "Callers should not share a stream");
+
+        // Resetting the call counter of this contract replays its stream.
+        vm.store(RANDOM_BYTES, bytes32(uint256(uint160(address(this)))), bytes32(0));
+        (, bytes memory replayed) = RANDOM_BYTES.staticcall(inputData);
+        assertEq(replayed, first, "Stream should be replayed");
+    }
+
     function testX25519Derive() public {
         // Test vectors from Oasis core 
         bytes32 publicKey = bytes32(hex"3046db3fa70ce605457dc47c48837ebd8bd0a26abfde5994d033e1ced68e2576");
@@ -152,6 +199,130 @@ contract PrecompileTest is SapphireTest {
         assertEq(symmetricKey1, symmetricKey2, "Symmetric keys should be equal");
     }
     
+    // Messages of the SHA-2 examples from NIST (https://csrc.nist.gov/projects/cryptographic-standards-and-guidelines/example-values).
+    bytes constant NIST_ONE_BLOCK = "abc";
+    bytes constant NIST_TWO_BLOCK_SHORT = "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    bytes constant NIST_TWO_BLOCK_LONG =
+        "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    function assertDigest(address precompile, bytes memory message, bytes memory expected) internal view {
+        (bool success, bytes memory result) = precompile.staticcall(message);
+        assertTrue(success, "Hash call failed");
+        assertEq(result, expected, "Incorrect digest");
+    }
+
+    function testSha512_256() public view {
+        assertDigest(SHA512_256, "", hex"c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a");
+        assertDigest(SHA512_256, NIST_ONE_BLOCK, hex"53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23");
+        assertDigest(SHA512_256, NIST_TWO_BLOCK_SHORT, hex"bde8e1f9f19bb9fd3406c90ec6bc47bd36d8ada9f11880dbc8a22a7078b6a461");
+        assertDigest(SHA512_256, NIST_TWO_BLOCK_LONG, hex"3928e184fb8690f840da3988121d31be65cb9d3ef83ee6146feac861e19b563a");
+    }
+
+    function testSha512() public view {
+        assertDigest(
+            SHA512,
+            "",
+            hex"cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"
+        );
+        assertDigest(
+            SHA512,
+            NIST_ONE_BLOCK,
+            hex"ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assertDigest(
+            SHA512,
+            NIST_TWO_BLOCK_SHORT,
+            hex"204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c33596fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445"
+        );
+        assertDigest(
+            SHA512,
+            NIST_TWO_BLOCK_LONG,
+            hex"8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+        );
+    }
+
+    function testSha384() public view {
+        assertDigest(
+            SHA384,
+            "",
+            hex"38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b"
+        );
+        assertDigest(
+            SHA384,
+            NIST_ONE_BLOCK,
+            hex"cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+        );
+        assertDigest(
+            SHA384,
+            NIST_TWO_BLOCK_SHORT,
+            hex"3391fdddfc8dc7393707a65b1b4709397cf8b1d162af05abfe8f450de5f36bc6b0455a8520bc4e6f5fe95b1fe3c8452b"
+        );
+        assertDigest(
+            SHA384,
+            NIST_TWO_BLOCK_LONG,
+            hex"09330c33f71147e83d192fc782cd1b4753111b173b3b05d22fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039"
+        );
+    }
+
+    function testGasUsed() public {
+        GasPadding padding = new GasPadding();
+
+        (bool success, bytes memory result) =
+            sapphireCall(address(padding), abi.encodeCall(GasPadding.gasUsed, (false)), 1_000_000);
+        assertTrue(success, "gas_used call failed");
+        uint256 idle = abi.decode(result, (uint256));
+        bytes memory data = abi.encodeCall(GasPadding.gasUsed, (false));
+        assertGt(idle, intrinsicGas(data), "Intrinsic gas not accounted for");
+
+        (success, result) = sapphireCall(address(padding), abi.encodeCall(GasPadding.gasUsed, (true)), 1_000_000);
+        assertTrue(success, "gas_used call failed");
+        // Storing to a fresh slot costs at least 20_000 gas.
+        assertGt(abi.decode(result, (uint256)), idle + 20_000, "Work not accounted for");
+
+        // Calls through another contract account for the gas retained at each call depth, up to
+        // 1/63 of the gas used by the contract in between.
+        GasRelay relay = new GasRelay();
+        (success, result) =
+            sapphireCall(address(relay), abi.encodeCall(GasRelay.gasUsed, (padding, false)), 1_000_000);
+        assertTrue(success, "nested gas_used call failed");
+        uint256 nested = abi.decode(result, (uint256));
+        assertGt(nested, idle, "Relay not accounted for");
+        assertLt(nested, idle + 10_000, "Retained gas accounted for");
+
+        // Outside of a transaction only the cost of the precompile is reported.
+        (success, result) = GAS_USED.staticcall("");
+        assertTrue(success, "gas_used should not fail outside of a transaction");
+        assertEq(abi.decode(result, (uint256)), 10, "Only the precompile cost should be used");
+    }
+
+    function measurePadding(GasPadding padding, bool work, uint128 amount) internal returns (uint256) {
+        uint256 start = gasleft();
+        (bool success, ) = sapphireCall(address(padding), abi.encodeCall(GasPadding.padGas, (work, amount)), 1_000_000);
+        uint256 used = start - gasleft();
+        assertTrue(success, "pad_gas call failed");
+        return used;
+    }
+
+    function testPadGas() public {
+        GasPadding padding = new GasPadding();
+        // Warm up the storage slot so that both paths see the same storage costs.
+        measurePadding(padding, true, 500_000);
+
+        uint256 idle = measurePadding(padding, false, 200_000);
+        uint256 busy = measurePadding(padding, true, 200_000);
+        // Burning gas is precise up to a few gas.
+        assertApproxEqAbs(idle, busy, 5, "Padding should equalize the gas of both paths");
+        assertApproxEqAbs(measurePadding(padding, false, 210_000), idle + 10_000, 5, "Padding should follow the amount");
+
+        // Padding fails when more gas was already used, like on Sapphire.
+        (bool success, ) = sapphireCall(address(padding), abi.encodeCall(GasPadding.padGas, (true, 1_000)), 1_000_000);
+        assertFalse(success, "pad_gas should fail below the used gas");
+
+        // Outside of a transaction the gas used is unknown, so padding fails.
+        (success, ) = PAD_GAS.staticcall(abi.encode(uint128(1_000_000)));
+        assertFalse(success, "pad_gas should fail outside of a transaction");
+    }
+
     function testSubcall() public {
         bool success;
         bytes memory result;
@@ -78,3 +78,99 @@ contract CalldataEncryptionTest is SapphireTest {
         assertEq(decrypted, in_data);
     }
 }
+
+contract SubcallLedgerTest is SapphireTest {
+    StakingAddress constant VALIDATOR = StakingAddress.wrap(bytes21(hex"00c18ec5ee3f1d7e0b7d9d4a3e6d0b4e8c2cbd1f10"));
+
+    uint64 constant DELEGATE_RECEIPT = 1 << 32;
+    uint64 constant UNDELEGATE_RECEIPT = (1 << 32) + 1;
+
+    function setUp() public override {
+        super.setUp();
+        vm.deal(address(this), 100 ether);
+    }
+
+    /*
+    Delegate, undelegate and take back the tokens once debonding ends, like a staking pool would.
+    */
+    function testDelegation() public {
+        Subcall.consensusDelegate(VALIDATOR, 10 ether, DELEGATE_RECEIPT);
+        assertEq(address(this).balance, 90 ether);
+        uint128 shares = Subcall.consensusTakeReceiptDelegate(DELEGATE_RECEIPT);
+        assertEq(shares, 10 ether);
+
+        Subcall.consensusUndelegate(VALIDATOR, shares, UNDELEGATE_RECEIPT);
+        (uint64 epoch, uint64 doneReceipt) = Subcall.consensusTakeReceiptUndelegateStart(UNDELEGATE_RECEIPT);
+        assertEq(epoch, block.number + 1);
+
+        // Tokens are debonding until the epoch (the block number) reaches the end of debonding.
+        vm.expectRevert(Subcall.InvalidReceiptId.selector);
+        this.takeUndelegateDone(doneReceipt);
+        assertEq(address(this).balance, 90 ether);
+
+        vm.roll(epoch);
+        assertEq(this.takeUndelegateDone(doneReceipt), 10 ether);
+        assertEq(address(this).balance, 100 ether);
+    }
+
+    function testDelegationInsufficientBalance() public {
+        vm.expectRevert(abi.encodeWithSelector(Subcall.ConsensusDelegateError.selector, 2, "accounts"));
+        this.delegate(101 ether);
+        assertEq(address(this).balance, 100 ether);
+    }
+
+    function testTransfers() public {
+        Subcall.accountsTransfer(StakingAddress.wrap(bytes21(hex"00")), 1 ether);
+        Subcall.consensusWithdraw(VALIDATOR, 2 ether);
+        assertEq(address(this).balance, 97 ether);
+    }
+
+    /*
+    A delegation in a reverted call is rolled back in the ledger as well as in the EVM.
+    */
+    function testDelegationReverted() public {
+        vm.expectRevert("reverted");
+        this.delegateAndRevert(10 ether);
+        assertEq(address(this).balance, 100 ether);
+
+        // The receipt of the reverted delegation is gone, a new delegation gets a fresh one.
+        vm.expectRevert(Subcall.InvalidReceiptId.selector);
+        this.takeDelegate(DELEGATE_RECEIPT);
+        Subcall.consensusDelegate(VALIDATOR, 5 ether, DELEGATE_RECEIPT);
+        assertEq(Subcall.consensusTakeReceiptDelegate(DELEGATE_RECEIPT), 5 ether);
+        assertEq(address(this).balance, 95 ether);
+    }
+
+    /*
+    Fuzz runs share the ledger of the test, each run must start from the empty ledger.
+    */
+    function testFuzzDelegation(uint128 amount) public {
+        amount = uint128(bound(amount, 1, 100 ether));
+        Subcall.consensusDelegate(VALIDATOR, amount, DELEGATE_RECEIPT);
+        assertEq(Subcall.consensusTakeReceiptDelegate(DELEGATE_RECEIPT), amount);
+        assertEq(address(this).balance, 100 ether - amount);
+
+        Subcall.consensusUndelegate(VALIDATOR, amount, UNDELEGATE_RECEIPT);
+        (uint64 epoch, uint64 doneReceipt) = Subcall.consensusTakeReceiptUndelegateStart(UNDELEGATE_RECEIPT);
+        vm.roll(epoch);
+        assertEq(this.takeUndelegateDone(doneReceipt), amount);
+        assertEq(address(this).balance, 100 ether);
+    }
+
+    function takeUndelegateDone(uint64 receiptId) external returns (uint128) {
+        return Subcall.consensusTakeReceiptUndelegateDone(receiptId);
+    }
+
+    function delegate(uint128 amount) external {
+        Subcall.consensusDelegate(VALIDATOR, amount, DELEGATE_RECEIPT);
+    }
+
+    function delegateAndRevert(uint128 amount) external {
+        Subcall.consensusDelegate(VALIDATOR, amount, DELEGATE_RECEIPT);
+        revert("reverted");
+    }
+
+    function takeDelegate(uint64 receiptId) external returns (uint128) {
+        return Subcall.consensusTakeReceiptDelegate(receiptId);
+    }
+}
@@ -0,0 +1,713 @@
+//! Calibration of the gas costs of ROFL and ROFL market methods.
+//!
+//! Each method is dispatched under the SDK mock environment with realistic payload sizes and the
+//! CPU time per unit of gas is compared with that of `evm.Call` executing a compute-bound
+//! contract. Methods that use more CPU time per unit of gas than `evm.Call` are flagged as
+//! underpriced together with the gas cost that would bring them on par.
+//!
+//! Methods that require an attested ROFL origin are called by a signer registered as an instance
+//! of the scheduler app in the mock state. The attestation of `rofl.Register` itself can only be
+//! verified with a real TEE quote, so it is measured up to the (failing) verification.
+//!
+//! Run with `cargo bench --features debug-mock-sgx --bench gas_calibration`.
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use module_rofl_market::{
+    types::{self as market, InstanceId, Offer, Payment, Resources, TeeType, Term},
+    Config as MarketConfig,
+};
+use oasis_runtime_sdk::{
+    core::consensus::registry::EndorsedCapabilityTEE,
+    modules::{
+        accounts::{self, API as _},
+        rofl::{
+            self,
+            app_id::AppId,
+            policy::{AllowedEndorsement, AppAuthPolicy, FeePolicy},
+            types::Registration,
+            Config as RoflConfig,
+        },
+    },
+    testing::{
+        keys,
+        mock::{CallOptions, Mock, Signer},
+    },
+    types::{
+        address::Address,
+        token::{BaseUnits, Denomination},
+        transaction::Fee,
+    },
+    Runtime as _,
+};
+use sapphire_paratime::{Config, Runtime};
+
+/// Number of samples taken of each method. The median is reported.
+const SAMPLES: usize = 21;
+
+/// Gas limit of each call.
+const GAS_LIMIT: u64 = 1_000_000;
+
+/// Gas price that satisfies the genesis minimum gas price.
+const GAS_PRICE: u128 = 100_000_000_000;
+
+/// Number of offers of benchmarked providers (the maximum is 64).
+const OFFERS: u64 = 16;
+
+/// Number of instances handled by benchmarked batch methods.
+const INSTANCES: u64 = 4;
+
+/// Number of commands of benchmarked instances (the maximum is 8).
+const COMMANDS: u64 = 8;
+
+/// Size of each benchmarked instance command (the maximum is 16 KiB).
+const COMMAND_SIZE: usize = 1024;
+
+/// Methods that only accept encrypted calls.
+const ENCRYPTED_METHODS: &[&str] = &["rofl.DeriveKey"];
+
+/// Methods measured up to the attestation verification, which fails without a real TEE quote.
+const UNATTESTED_METHODS: &[&str] = &["rofl.Register"];
+
+/// Number of metadata pairs of benchmarked apps and providers (the maximum is 64).
+const METADATA_PAIRS: usize = 16;
+
+/// Init code of a contract whose runtime code hashes a 32-byte word 1000 times.
+const KECCAK_LOOP_INIT_CODE: &str =
+    "6015600c60003960156000f36103e85b6020600020600052600190038060035700";
+
+/// Dispatch a call during setup, returning the result.
+type Call<'a> = dyn FnMut(&str, cbor::Value) -> cbor::Value + 'a;
+
+/// A benchmarked method.
+struct Bench {
+    /// Method being measured.
+    method: &'static str,
+    /// Gas cost constants charged by the method (e.g. a base cost and a per-item cost times the
+    /// number of items).
+    constants: &'static [(&'static str, u64, u64)],
+    /// Prepare the state, returning the body of the measured call.
+    setup: fn(&mut Call<'_>) -> cbor::Value,
+}
+
+/// Result of benchmarking a method.
+struct Measurement {
+    /// Gas used by the measured call.
+    gas: u64,
+    /// Median time of the measured call.
+    time: Duration,
+}
+
+impl Measurement {
+    /// CPU time in nanoseconds per unit of gas.
+    fn nanos_per_gas(&self) -> f64 {
+        self.time.as_nanos() as f64 / self.gas.max(1) as f64
+    }
+}
+
+fn main() {
+    let reference = measure(&Bench {
+        method: "evm.Call",
+        constants: &[],
+        setup: |call| {
+            let create = module_evm::types::Create {
+                value: 0.into(),
+                init_code: hex::decode(KECCAK_LOOP_INIT_CODE).unwrap(),
+            };
+            let address: Vec<u8> =
+                cbor::from_value(call("evm.Create", cbor::to_value(create))).unwrap();
+            cbor::to_value(module_evm::types::Call {
+                address: module_evm::types::H160::from_slice(&address),
+                value: 0.into(),
+                data: vec![],
+            })
+        },
+    });
+    println!(
+        "reference: evm.Call uses {} gas in {:?} ({:.2} ns/gas)\n",
+        reference.gas,
+        reference.time,
+        reference.nanos_per_gas(),
+    );
+
+    println!(
+        "{:<32} {:>10} {:>10} {:>12} {:>10}  constants",
+        "method", "gas", "time (us)", "ns/gas", "ratio",
+    );
+    let mut underpriced = Vec::new();
+    for bench in benches() {
+        let measurement = measure(&bench);
+        let ratio = measurement.nanos_per_gas() / reference.nanos_per_gas();
+        let constants = bench
+            .constants
+            .iter()
+            .map(|(name, value, count)| match count {
+                1 => format!("{name}={value}"),
+                _ => format!("{name}={value}x{count}"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "{:<32} {:>10} {:>10.1} {:>12.2} {:>10.2}  {constants}",
+            bench.method,
+            measurement.gas,
+            measurement.time.as_nanos() as f64 / 1_000.0,
+            measurement.nanos_per_gas(),
+            ratio,
+        );
+        if ratio > 1.0 {
+            underpriced.push((bench, measurement, ratio));
+        }
+    }
+
+    println!();
+    if underpriced.is_empty() {
+        println!("no underpriced methods");
+    }
+    for (bench, measurement, ratio) in underpriced {
+        // Gas needed for the call to be on par with evm.Call, attributed to the constants.
+        let missing = (measurement.gas as f64 * (ratio - 1.0)).ceil() as u64;
+        let charged: u64 = bench.constants.iter().map(|(_, v, n)| v * n).sum();
+        println!(
+            "UNDERPRICED {}: {ratio:.2}x the CPU time per gas of evm.Call, constants charge \
+             {charged} gas and would need about {} gas",
+            bench.method,
+            charged + missing,
+        );
+    }
+    println!(
+        "\nmeasured without attestation verification (lower bound): {}",
+        UNATTESTED_METHODS.join(", ")
+    );
+}
+
+/// Benchmarked ROFL and ROFL market methods.
+fn benches() -> Vec<Bench> {
+    vec![
+        Bench {
+            method: "rofl.Create",
+            constants: &[(
+                "GAS_COST_CALL_CREATE",
+                <Config as RoflConfig>::GAS_COST_CALL_CREATE,
+                1,
+            )],
+            setup: |_| cbor::to_value(app_create()),
+        },
+        Bench {
+            method: "rofl.Update",
+            constants: &[(
+                "GAS_COST_CALL_UPDATE",
+                <Config as RoflConfig>::GAS_COST_CALL_UPDATE,
+                1,
+            )],
+            setup: |call| {
+                let id = create_app(call);
+                cbor::to_value(rofl::types::Update {
+                    id,
+                    policy: app_policy(),
+                    admin: Some(keys::alice::address()),
+                    metadata: metadata("updated"),
+                    ..Default::default()
+                })
+            },
+        },
+        Bench {
+            method: "rofl.Remove",
+            constants: &[(
+                "GAS_COST_CALL_REMOVE",
+                <Config as RoflConfig>::GAS_COST_CALL_REMOVE,
+                1,
+            )],
+            setup: |call| {
+                cbor::to_value(rofl::types::Remove {
+                    id: create_app(call),
+                })
+            },
+        },
+        Bench {
+            method: "rofl.Register",
+            constants: &[(
+                "GAS_COST_CALL_REGISTER",
+                <Config as RoflConfig>::GAS_COST_CALL_REGISTER,
+                1,
+            )],
+            setup: |call| {
+                let mut ect = EndorsedCapabilityTEE::default();
+                ect.capability_tee.rak = keys::alice::pk_ed25519().into();
+                cbor::to_value(rofl::types::Register {
+                    app: create_app(call),
+                    ect,
+                    expiration: 2,
+                    metadata: metadata("registered"),
+                    ..Default::default()
+                })
+            },
+        },
+        Bench {
+            method: "rofl.DeriveKey",
+            constants: &[(
+                "GAS_COST_CALL_DERIVE_KEY",
+                <Config as RoflConfig>::GAS_COST_CALL_DERIVE_KEY,
+                1,
+            )],
+            setup: |call| {
+                let app = create_app(call);
+                attest(app);
+                cbor::to_value(rofl::types::DeriveKey {
+                    app,
+                    key_id: b"gas calibration".to_vec(),
+                    ..Default::default()
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.ProviderCreate",
+            constants: &[(
+                "GAS_COST_CALL_PROVIDER_CREATE",
+                <Config as MarketConfig>::GAS_COST_CALL_PROVIDER_CREATE,
+                1,
+            )],
+            setup: |call| {
+                let scheduler_app = create_app(call);
+                cbor::to_value(provider_create(scheduler_app))
+            },
+        },
+        Bench {
+            method: "roflmarket.ProviderUpdate",
+            constants: &[(
+                "GAS_COST_CALL_PROVIDER_UPDATE",
+                <Config as MarketConfig>::GAS_COST_CALL_PROVIDER_UPDATE,
+                1,
+            )],
+            setup: |call| {
+                let scheduler_app = create_provider(call);
+                cbor::to_value(market::ProviderUpdate {
+                    provider: keys::alice::address(),
+                    scheduler_app,
+                    metadata: metadata("updated"),
+                    ..Default::default()
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.ProviderUpdateOffers",
+            constants: &[
+                (
+                    "GAS_COST_CALL_PROVIDER_UPDATE_OFFERS_BASE",
+                    <Config as MarketConfig>::GAS_COST_CALL_PROVIDER_UPDATE_OFFERS_BASE,
+                    1,
+                ),
+                (
+                    "GAS_COST_CALL_PROVIDER_UPDATE_OFFERS_ADD",
+                    <Config as MarketConfig>::GAS_COST_CALL_PROVIDER_UPDATE_OFFERS_ADD,
+                    OFFERS,
+                ),
+            ],
+            setup: |call| {
+                create_provider(call);
+                cbor::to_value(market::ProviderUpdateOffers {
+                    provider: keys::alice::address(),
+                    add: (0..OFFERS).map(|_| offer()).collect(),
+                    ..Default::default()
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.ProviderRemove",
+            constants: &[(
+                "GAS_COST_CALL_PROVIDER_REMOVE",
+                <Config as MarketConfig>::GAS_COST_CALL_PROVIDER_REMOVE,
+                1,
+            )],
+            setup: |call| {
+                create_provider(call);
+                cbor::to_value(market::ProviderRemove {
+                    provider: keys::alice::address(),
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceCreate",
+            constants: &[(
+                "GAS_COST_CALL_INSTANCE_CREATE",
+                <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_CREATE,
+                1,
+            )],
+            setup: |call| {
+                create_provider(call);
+                cbor::to_value(instance_create())
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceTopUp",
+            constants: &[(
+                "GAS_COST_CALL_INSTANCE_TOPUP",
+                <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_TOPUP,
+                1,
+            )],
+            setup: |call| {
+                let id = create_instance(call);
+                cbor::to_value(market::InstanceTopUp {
+                    provider: keys::alice::address(),
+                    id,
+                    term: Term::Hour,
+                    term_count: 1,
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceChangeAdmin",
+            constants: &[(
+                "GAS_COST_CALL_INSTANCE_CHANGE_ADMIN",
+                <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_CHANGE_ADMIN,
+                1,
+            )],
+            setup: |call| {
+                let id = create_instance(call);
+                cbor::to_value(market::InstanceChangeAdmin {
+                    provider: keys::alice::address(),
+                    id,
+                    admin: keys::bob::address(),
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceCancel",
+            constants: &[(
+                "GAS_COST_CALL_INSTANCE_CANCEL",
+                <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_CANCEL,
+                1,
+            )],
+            setup: |call| {
+                let id = create_instance(call);
+                cbor::to_value(market::InstanceCancel {
+                    provider: keys::alice::address(),
+                    id,
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceAccept",
+            constants: &[
+                (
+                    "GAS_COST_CALL_INSTANCE_ACCEPT_BASE",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_ACCEPT_BASE,
+                    1,
+                ),
+                (
+                    "GAS_COST_CALL_INSTANCE_ACCEPT_INSTANCE",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_ACCEPT_INSTANCE,
+                    INSTANCES,
+                ),
+            ],
+            setup: |call| cbor::to_value(instance_accept(create_instances(call))),
+        },
+        Bench {
+            method: "roflmarket.InstanceUpdate",
+            constants: &[
+                (
+                    "GAS_COST_CALL_INSTANCE_UPDATE_BASE",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_UPDATE_BASE,
+                    1,
+                ),
+                (
+                    "GAS_COST_CALL_INSTANCE_UPDATE_INST",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_UPDATE_INST,
+                    INSTANCES,
+                ),
+            ],
+            setup: |call| {
+                let ids = accept_instances(call);
+                cbor::to_value(market::InstanceUpdate {
+                    provider: keys::alice::address(),
+                    updates: ids
+                        .into_iter()
+                        .map(|id| market::Update {
+                            id,
+                            metadata: Some(metadata("updated")),
+                            ..Default::default()
+                        })
+                        .collect(),
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceExecuteCmds",
+            constants: &[
+                (
+                    "GAS_COST_CALL_INSTANCE_EXECUTE_CMDS_BASE",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_EXECUTE_CMDS_BASE,
+                    1,
+                ),
+                (
+                    "GAS_COST_CALL_INSTANCE_EXECUTE_CMDS_CMD",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_EXECUTE_CMDS_CMD,
+                    COMMANDS,
+                ),
+            ],
+            setup: |call| {
+                let id = accept_instances(call).remove(0);
+                cbor::to_value(market::InstanceExecuteCmds {
+                    provider: keys::alice::address(),
+                    id,
+                    cmds: (0..COMMANDS).map(|_| vec![0xaa; COMMAND_SIZE]).collect(),
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceClaimPayment",
+            constants: &[
+                (
+                    "GAS_COST_CALL_INSTANCE_CLAIM_PAYMENT_BASE",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_CLAIM_PAYMENT_BASE,
+                    1,
+                ),
+                (
+                    "GAS_COST_CALL_INSTANCE_CLAIM_PAYMENT_INST",
+                    <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_CLAIM_PAYMENT_INST,
+                    INSTANCES,
+                ),
+            ],
+            setup: |call| {
+                let instances = accept_instances(call);
+                cbor::to_value(market::InstanceClaimPayment {
+                    provider: keys::alice::address(),
+                    instances,
+                })
+            },
+        },
+        Bench {
+            method: "roflmarket.InstanceRemove",
+            constants: &[(
+                "GAS_COST_CALL_INSTANCE_REMOVE",
+                <Config as MarketConfig>::GAS_COST_CALL_INSTANCE_REMOVE,
+                1,
+            )],
+            setup: |call| {
+                let id = accept_instances(call).remove(0);
+                cbor::to_value(market::InstanceRemove {
+                    provider: keys::alice::address(),
+                    id,
+                })
+            },
+        },
+    ]
+}
+
+/// Benchmark the given method, each sample in a freshly initialized mock environment.
+fn measure(bench: &Bench) -> Measurement {
+    let mut samples = Vec::with_capacity(SAMPLES);
+    let mut gas = 0;
+    for _ in 0..SAMPLES {
+        let mut mock = Mock::default();
+        let ctx = mock.create_ctx_for_runtime::<Runtime>(true);
+        Runtime::migrate(&ctx);
+        fund(keys::alice::address());
+
+        let mut signer = Signer::new(0, keys::alice::sigspec());
+        let body = (bench.setup)(&mut |method, body| {
+            let result = signer
+                .call_opts(&ctx, method, body, call_options(false))
+                .result;
+            assert!(result.is_success(), "{method} should succeed: {result:?}");
+            result.unwrap()
+        });
+
+        let encrypted = ENCRYPTED_METHODS.contains(&bench.method);
+        let start = Instant::now();
+        let result = signer.call_opts(&ctx, bench.method, body, call_options(encrypted));
+        samples.push(start.elapsed());
+
+        assert_eq!(
+            result.result.is_success(),
+            !UNATTESTED_METHODS.contains(&bench.method),
+            "unexpected result of {}: {:?}",
+            bench.method,
+            result.result
+        );
+        gas = gas_used(&result.tags);
+    }
+    samples.sort();
+
+    Measurement {
+        gas,
+        time: samples[SAMPLES / 2],
+    }
+}
+
+/// Gas used by a transaction, as reported by the core module.
+fn gas_used(tags: &[oasis_runtime_sdk::core::transaction::tags::Tag]) -> u64 {
+    #[derive(cbor::Decode)]
+    struct GasUsed {
+        amount: u64,
+    }
+
+    // Key of the core.GasUsed event.
+    let key = [b"core".as_slice(), &1u32.to_be_bytes()].concat();
+    tags.iter()
+        .filter(|tag| tag.key == key)
+        .filter_map(|tag| cbor::from_slice::<Vec<GasUsed>>(&tag.value).ok())
+        .flatten()
+        .map(|event| event.amount)
+        .sum()
+}
+
+fn fund(address: Address) {
+    accounts::Module::mint(
+        address,
+        &BaseUnits::new(1_000_000_000_000_000_000_000, Denomination::NATIVE),
+    )
+    .expect("minting should succeed");
+}
+
+fn call_options(encrypted: bool) -> CallOptions {
+    CallOptions {
+        fee: Fee {
+            amount: BaseUnits::new(u128::from(GAS_LIMIT) * GAS_PRICE, Denomination::NATIVE),
+            gas: GAS_LIMIT,
+            ..Default::default()
+        },
+        encrypted,
+    }
+}
+
+/// Metadata with the given value prefix.
+fn metadata(prefix: &str) -> BTreeMap<String, String> {
+    (0..METADATA_PAIRS)
+        .map(|i| {
+            (
+                format!("net.oasis.key{i}"),
+                format!("{prefix}-{}", "x".repeat(64)),
+            )
+        })
+        .collect()
+}
+
+fn app_policy() -> AppAuthPolicy {
+    AppAuthPolicy {
+        endorsements: vec![AllowedEndorsement::Any],
+        fees: FeePolicy::EndorsingNodePays,
+        max_expiration: 3,
+        ..Default::default()
+    }
+}
+
+fn app_create() -> rofl::types::Create {
+    rofl::types::Create {
+        policy: app_policy(),
+        metadata: metadata("created"),
+        ..Default::default()
+    }
+}
+
+fn create_app(call: &mut Call<'_>) -> AppId {
+    cbor::from_value(call("rofl.Create", cbor::to_value(app_create()))).unwrap()
+}
+
+/// Register the signer as an attested instance of the given app, making it an authorized ROFL
+/// origin of the app.
+fn attest(app: AppId) {
+    rofl::state::update_registration(Registration {
+        app,
+        rak: keys::alice::pk_ed25519().into(),
+        expiration: 3,
+        ..Default::default()
+    })
+    .expect("registration should succeed");
+}
+
+fn offer() -> Offer {
+    Offer {
+        resources: Resources {
+            tee: TeeType::TDX,
+            memory: 4096,
+            cpus: 2,
+            storage: 20_000,
+            ..Default::default()
+        },
+        payment: Payment::Native {
+            denomination: Denomination::NATIVE,
+            terms: [(Term::Hour, 1_000_000_000_000_000_000)].into(),
+        },
+        capacity: 10,
+        metadata: BTreeMap::from([("net.oasis.description".to_string(), "x".repeat(64))]),
+        ..Default::default()
+    }
+}
+
+fn provider_create(scheduler_app: AppId) -> market::ProviderCreate {
+    market::ProviderCreate {
+        scheduler_app,
+        offers: (0..OFFERS).map(|_| offer()).collect(),
+        metadata: metadata("created"),
+        ..Default::default()
+    }
+}
+
+/// Create a provider owned by the signer, returning its scheduler app.
+fn create_provider(call: &mut Call<'_>) -> AppId {
+    let scheduler_app = create_app(call);
+    call(
+        "roflmarket.ProviderCreate",
+        cbor::to_value(provider_create(scheduler_app)),
+    );
+    scheduler_app
+}
+
+fn instance_create() -> market::InstanceCreate {
+    let offer = module_rofl_market::state::get_offers(keys::alice::address())
+        .remove(0)
+        .id;
+    market::InstanceCreate {
+        provider: keys::alice::address(),
+        offer,
+        term: Term::Hour,
+        term_count: 1,
+        ..Default::default()
+    }
+}
+
+fn create_instance(call: &mut Call<'_>) -> InstanceId {
+    create_provider(call);
+    cbor::from_value(call(
+        "roflmarket.InstanceCreate",
+        cbor::to_value(instance_create()),
+    ))
+    .unwrap()
+}
+
+/// Create instances of a provider whose scheduler app is attested by the signer.
+fn create_instances(call: &mut Call<'_>) -> Vec<InstanceId> {
+    let scheduler_app = create_provider(call);
+    attest(scheduler_app);
+    (0..INSTANCES)
+        .map(|_| {
+            cbor::from_value(call(
+                "roflmarket.InstanceCreate",
+                cbor::to_value(instance_create()),
+            ))
+            .unwrap()
+        })
+        .collect()
+}
+
+fn instance_accept(ids: Vec<InstanceId>) -> market::InstanceAccept {
+    market::InstanceAccept {
+        provider: keys::alice::address(),
+        ids,
+        metadata: metadata("accepted"),
+        ..Default::default()
+    }
+}
+
+/// Create instances and accept them as the scheduler.
+fn accept_instances(call: &mut Call<'_>) -> Vec<InstanceId> {
+    let ids = create_instances(call);
+    call(
+        "roflmarket.InstanceAccept",
+        cbor::to_value(instance_accept(ids.clone())),
+    );
+    ids
+}
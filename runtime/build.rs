@@ -0,0 +1,850 @@
+//! Build script of the runtime.
+//!
+//! Each `genesis/<network>.toml` file is parsed and validated here and then turned into Rust code
+//! that constructs the module parameter types, so any mismatch with the SDK types is reported by
+//! the compiler. Reward curves are expanded into schedule steps using the [`rewards`] module
+//! shared with the runtime. The runtime IDs declared in the `package.metadata.orc` section of `Cargo.toml`
+//! are exposed to the runtime as environment variables.
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{de, Deserialize, Deserializer};
+
+#[path = "src/rewards.rs"]
+mod rewards;
+
+/// Networks that have a genesis file in the `genesis` directory.
+const NETWORKS: &[&str] = &["mainnet", "testnet", "localnet"];
+
+/// Environment variable that can point to the genesis file used by custom network builds.
+const GENESIS_FILE_ENV: &str = "OASIS_SAPPHIRE_GENESIS_FILE";
+
+/// Name of the native denomination in genesis files.
+const NATIVE_DENOMINATION: &str = "native";
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    emit_runtime_ids(&manifest_dir);
+    emit_genesis(&manifest_dir);
+}
+
+/// Expose the runtime IDs from the orc package metadata.
+fn emit_runtime_ids(manifest_dir: &Path) {
+    let path = manifest_dir.join("Cargo.toml");
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let raw = fs::read_to_string(&path).unwrap();
+    let manifest: toml::Table = toml::from_str(&raw).unwrap();
+    for (variant, env) in [
+        ("release", "SAPPHIRE_ORC_RELEASE_RUNTIME_ID"),
+        ("test", "SAPPHIRE_ORC_TEST_RUNTIME_ID"),
+    ] {
+        let runtime_id = manifest
+            .get("package")
+            .and_then(|v| v.get("metadata"))
+            .and_then(|v| v.get("orc"))
+            .and_then(|v| v.get(variant))
+            .and_then(|v| v.get("runtime-id"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_else(|| panic!("missing package.metadata.orc.{variant}.runtime-id"));
+        if runtime_id.len() != 64 || !runtime_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            panic!("package.metadata.orc.{variant}.runtime-id must be 32 hex-encoded bytes");
+        }
+        println!("cargo:rustc-env={env}={runtime_id}");
+    }
+}
+
+/// Generate the genesis state functions of all networks.
+fn emit_genesis(manifest_dir: &Path) {
+    let genesis_dir = manifest_dir.join("genesis");
+    println!("cargo:rerun-if-changed={}", genesis_dir.display());
+    println!("cargo:rerun-if-env-changed={GENESIS_FILE_ENV}");
+
+    let mut out = String::from("// Generated by build.rs from the genesis files. Do not edit.\n");
+    for network in NETWORKS {
+        let path = genesis_dir.join(format!("{network}.toml"));
+        out += &generate(network, &path);
+    }
+
+    // Custom networks default to the Localnet parameters unless a file is provided.
+    let custom = env::var(GENESIS_FILE_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| genesis_dir.join("localnet.toml"));
+    println!("cargo:rerun-if-changed={}", custom.display());
+    out += &generate("custom", &custom);
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("genesis.rs"), out).unwrap();
+}
+
+/// Parse and validate the given genesis file and generate the function returning its state.
+fn generate(network: &str, path: &Path) -> String {
+    let raw = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("failed to read genesis file {}: {err}", path.display()));
+    let genesis: Genesis = toml::from_str(&raw)
+        .unwrap_or_else(|err| panic!("malformed genesis file {}: {err}", path.display()));
+    if let Err(err) = genesis.validate() {
+        panic!("invalid genesis file {}: {err}", path.display());
+    }
+
+    let file = path.file_name().unwrap().to_string_lossy();
+    let mut out = String::new();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "/// Genesis state of the {network} network (generated from `{file}`)."
+    )
+    .unwrap();
+    writeln!(out, "pub(crate) fn {network}() -> Genesis {{").unwrap();
+    out += &genesis.emit();
+    writeln!(out, "}}").unwrap();
+
+    // Only methods with a configured floor are exempt from the core minimum gas price.
+    let mut discounted: Vec<&str> = genesis
+        .gas_policy
+        .methods
+        .keys()
+        .map(|m| m.as_str())
+        .collect();
+    if !genesis.gas_policy.contracts.is_empty() {
+        discounted.push("evm.Call");
+    }
+    writeln!(
+        out,
+        "/// Methods with a minimum gas price floor on the {network} network (from `{file}`)."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub(crate) const {}_DISCOUNTED_METHODS: &[&str] = &{discounted:?};",
+        network.to_uppercase(),
+    )
+    .unwrap();
+    out
+}
+
+/// Genesis parameters of all configurable modules.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Genesis {
+    core: Core,
+    accounts: Accounts,
+    consensus: Consensus,
+    consensus_accounts: ConsensusAccounts,
+    rewards: Rewards,
+    governance: Governance,
+    pause: Pause,
+    gas_policy: GasPolicy,
+    lanes: Lanes,
+    endorsement: Endorsement,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Core {
+    min_gas_price: BTreeMap<String, Amount>,
+    dynamic_min_gas_price: DynamicMinGasPrice,
+    max_batch_gas: u64,
+    max_tx_size: u32,
+    max_tx_signers: u32,
+    max_multisig_signers: u32,
+    gas_costs: CoreGasCosts,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DynamicMinGasPrice {
+    enabled: bool,
+    target_block_gas_usage_percentage: u8,
+    min_price_max_change_denominator: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CoreGasCosts {
+    tx_byte: u64,
+    storage_byte: u64,
+    auth_signature: u64,
+    auth_multisig_signer: u64,
+    callformat_x25519_deoxysii: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Accounts {
+    gas_costs: AccountsGasCosts,
+    denomination_infos: BTreeMap<String, DenominationInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountsGasCosts {
+    tx_transfer: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DenominationInfo {
+    decimals: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Consensus {
+    gas_costs: ConsensusGasCosts,
+    consensus_denomination: String,
+    consensus_scaling_factor: u64,
+    min_delegate_amount: Amount,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsensusGasCosts {
+    round_root: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsensusAccounts {
+    gas_costs: ConsensusAccountsGasCosts,
+    disable_delegate: bool,
+    disable_undelegate: bool,
+    disable_deposit: bool,
+    disable_withdraw: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsensusAccountsGasCosts {
+    tx_deposit: u64,
+    tx_withdraw: u64,
+    tx_delegate: u64,
+    tx_undelegate: u64,
+    store_receipt: u64,
+    take_receipt: u64,
+    delegation: u64,
+    shares_to_tokens: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rewards {
+    /// Explicit schedule steps, mutually exclusive with `curve`.
+    #[serde(default)]
+    schedule: Vec<RewardStep>,
+    /// Curve expanded into schedule steps, mutually exclusive with `schedule`.
+    curve: Option<RewardCurve>,
+    /// Epoch from which the schedule applies.
+    #[serde(default)]
+    start_epoch: u64,
+    /// Maximum total emission of the schedule.
+    max_emission: Amount,
+    participation_threshold_numerator: u64,
+    participation_threshold_denominator: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RewardStep {
+    until: u64,
+    amount: Amount,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum RewardCurve {
+    Halving {
+        amount: Amount,
+        period: u64,
+        steps: u32,
+    },
+    ExponentialDecay {
+        amount: Amount,
+        period: u64,
+        steps: u32,
+        decay_numerator: u64,
+        decay_denominator: u64,
+    },
+}
+
+impl Rewards {
+    /// Schedule steps, either given explicitly or expanded from the curve.
+    fn steps(&self) -> Result<Vec<rewards::Step>, String> {
+        let curve = match &self.curve {
+            None if self.schedule.is_empty() => {
+                return Err("rewards.schedule or rewards.curve must be set".to_string())
+            }
+            None => {
+                return Ok(self
+                    .schedule
+                    .iter()
+                    .map(|s| rewards::Step {
+                        until: s.until,
+                        amount: s.amount.0,
+                    })
+                    .collect())
+            }
+            Some(_) if !self.schedule.is_empty() => {
+                return Err("rewards.schedule and rewards.curve are mutually exclusive".to_string())
+            }
+            Some(RewardCurve::Halving {
+                amount,
+                period,
+                steps,
+            }) => rewards::Curve::Halving {
+                amount: amount.0,
+                period: *period,
+                steps: *steps,
+            },
+            Some(RewardCurve::ExponentialDecay {
+                amount,
+                period,
+                steps,
+                decay_numerator,
+                decay_denominator,
+            }) => rewards::Curve::ExponentialDecay {
+                amount: amount.0,
+                period: *period,
+                steps: *steps,
+                numerator: *decay_numerator,
+                denominator: *decay_denominator,
+            },
+        };
+        curve
+            .expand(self.start_epoch)
+            .map_err(|err| format!("rewards.curve: {err}"))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Governance {
+    signers: Vec<String>,
+    threshold: u16,
+    timelock: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Pause {
+    guardians: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GasPolicy {
+    methods: BTreeMap<String, u16>,
+    contracts: BTreeMap<String, u16>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Lanes {
+    lanes: Vec<Lane>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Lane {
+    methods: Vec<String>,
+    reserved: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Endorsement {
+    min_cpusvn: String,
+    max_instances_per_entity: u64,
+    revoked_nodes: Vec<String>,
+}
+
+/// An amount of base units.
+///
+/// TOML integers are limited to 64 bits so amounts may also be given as (optionally
+/// underscore-separated) strings.
+struct Amount(u128);
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Integer(u64),
+            String(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Integer(v) => Ok(Amount(v.into())),
+            Raw::String(v) => v
+                .replace('_', "")
+                .parse()
+                .map(Amount)
+                .map_err(|_| de::Error::custom(format!("invalid amount: {v}"))),
+        }
+    }
+}
+
+impl Genesis {
+    /// Check the parameters for consistency.
+    fn validate(&self) -> Result<(), String> {
+        let core = &self.core;
+        if core.max_batch_gas == 0 {
+            return Err("core.max_batch_gas must be non-zero".to_string());
+        }
+        if core.max_tx_size == 0 {
+            return Err("core.max_tx_size must be non-zero".to_string());
+        }
+        if core.max_tx_signers == 0 {
+            return Err("core.max_tx_signers must be non-zero".to_string());
+        }
+        if core.dynamic_min_gas_price.target_block_gas_usage_percentage > 100 {
+            return Err(
+                "core.dynamic_min_gas_price.target_block_gas_usage_percentage must be at most 100"
+                    .to_string(),
+            );
+        }
+        if core.dynamic_min_gas_price.min_price_max_change_denominator == 0 {
+            return Err(
+                "core.dynamic_min_gas_price.min_price_max_change_denominator must be non-zero"
+                    .to_string(),
+            );
+        }
+        for denomination in core.min_gas_price.keys() {
+            validate_denomination(denomination)?;
+        }
+        for denomination in self.accounts.denomination_infos.keys() {
+            validate_denomination(denomination)?;
+        }
+
+        let consensus = &self.consensus;
+        validate_denomination(&consensus.consensus_denomination)?;
+        let mut scaling_factor = consensus.consensus_scaling_factor;
+        while scaling_factor >= 10 && scaling_factor.is_multiple_of(10) {
+            scaling_factor /= 10;
+        }
+        if scaling_factor != 1 {
+            return Err("consensus.consensus_scaling_factor must be a power of 10".to_string());
+        }
+
+        let rewards = &self.rewards;
+        if rewards.participation_threshold_denominator == 0 {
+            return Err("rewards.participation_threshold_denominator must be non-zero".to_string());
+        }
+        if rewards.participation_threshold_numerator > rewards.participation_threshold_denominator {
+            return Err(
+                "rewards.participation_threshold_numerator must not exceed the denominator"
+                    .to_string(),
+            );
+        }
+        let steps = rewards.steps()?;
+        rewards::validate(rewards.start_epoch, &steps)
+            .map_err(|err| format!("rewards.schedule: {err}"))?;
+        let emission = rewards::emission(rewards.start_epoch, &steps)
+            .ok_or("rewards.schedule emission overflows")?;
+        if emission > rewards.max_emission.0 {
+            return Err(format!(
+                "rewards.schedule emits {emission} which exceeds rewards.max_emission ({})",
+                rewards.max_emission.0,
+            ));
+        }
+
+        let governance = &self.governance;
+        if usize::from(governance.threshold) > governance.signers.len() {
+            return Err("governance.threshold must not exceed the number of signers".to_string());
+        }
+        if governance.threshold == 0 && !governance.signers.is_empty() {
+            return Err("governance.signers must be empty when the threshold is zero".to_string());
+        }
+        validate_addresses("governance.signers", &governance.signers)?;
+        validate_addresses("pause.guardians", &self.pause.guardians)?;
+
+        let gas_policy = &self.gas_policy;
+        for (target, floor) in gas_policy.methods.iter().chain(&gas_policy.contracts) {
+            if *floor > 1_000 {
+                return Err(format!(
+                    "gas_policy: floor of {target} must not exceed 1000"
+                ));
+            }
+        }
+        for method in gas_policy.methods.keys() {
+            if !method.contains('.') || method == "evm.Call" {
+                return Err(format!("gas_policy.methods: invalid method {method:?}"));
+            }
+        }
+        for contract in gas_policy.contracts.keys() {
+            if !contract
+                .strip_prefix("0x")
+                .is_some_and(|hex| is_hex(hex, 20))
+            {
+                return Err(format!(
+                    "gas_policy.contracts: invalid address {contract:?}"
+                ));
+            }
+        }
+
+        let mut methods = Vec::new();
+        for lane in &self.lanes.lanes {
+            if lane.methods.is_empty() {
+                return Err("lanes: lane without methods".to_string());
+            }
+            for method in &lane.methods {
+                if !method.contains('.') || methods.contains(&method) {
+                    return Err(format!("lanes: invalid or duplicate method {method:?}"));
+                }
+                methods.push(method);
+            }
+        }
+        let reserved: u32 = self.lanes.lanes.iter().map(|l| u32::from(l.reserved)).sum();
+        if reserved > 1_000 {
+            return Err("lanes: reserved shares must not exceed 1000".to_string());
+        }
+
+        let endorsement = &self.endorsement;
+        if !endorsement.min_cpusvn.is_empty() && !is_hex(&endorsement.min_cpusvn, 16) {
+            return Err("endorsement.min_cpusvn must be empty or 16 hex-encoded bytes".to_string());
+        }
+        for (i, node) in endorsement.revoked_nodes.iter().enumerate() {
+            if !is_hex(node, 32) || endorsement.revoked_nodes[..i].contains(node) {
+                return Err(format!(
+                    "endorsement.revoked_nodes: invalid or duplicate node {node:?}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generate the Rust expression constructing the genesis state.
+    fn emit(&self) -> String {
+        let Self {
+            core,
+            accounts,
+            consensus,
+            consensus_accounts,
+            rewards,
+            governance,
+            pause,
+            gas_policy,
+            lanes,
+            endorsement,
+        } = self;
+
+        let mut out = String::new();
+        let min_gas_price = core
+            .min_gas_price
+            .iter()
+            .map(|(d, p)| format!("({}, {})", denomination(d), p.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let denomination_infos = accounts
+            .denomination_infos
+            .iter()
+            .map(|(d, i)| {
+                format!(
+                    "({}, modules::accounts::types::DenominationInfo {{ decimals: {} }})",
+                    denomination(d),
+                    i.decimals,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let steps = rewards
+            .steps()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                format!(
+                    "modules::rewards::types::RewardStep {{ until: {}, amount: BaseUnits::new({}, \
+                     Denomination::NATIVE) }}",
+                    s.until, s.amount,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let g = &core.gas_costs;
+        let d = &core.dynamic_min_gas_price;
+        writeln!(
+            out,
+            "(modules::core::Genesis {{ parameters: modules::core::Parameters {{
+                min_gas_price: BTreeMap::from([{min_gas_price}]),
+                dynamic_min_gas_price: modules::core::DynamicMinGasPrice {{
+                    enabled: {},
+                    target_block_gas_usage_percentage: {},
+                    min_price_max_change_denominator: {},
+                }},
+                max_batch_gas: {},
+                max_tx_size: {},
+                max_tx_signers: {},
+                max_multisig_signers: {},
+                gas_costs: modules::core::GasCosts {{
+                    tx_byte: {},
+                    storage_byte: {},
+                    auth_signature: {},
+                    auth_multisig_signer: {},
+                    callformat_x25519_deoxysii: {},
+                }},
+            }} }},",
+            d.enabled,
+            d.target_block_gas_usage_percentage,
+            d.min_price_max_change_denominator,
+            core.max_batch_gas,
+            core.max_tx_size,
+            core.max_tx_signers,
+            core.max_multisig_signers,
+            g.tx_byte,
+            g.storage_byte,
+            g.auth_signature,
+            g.auth_multisig_signer,
+            g.callformat_x25519_deoxysii,
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "modules::accounts::Genesis {{
+                parameters: modules::accounts::Parameters {{
+                    gas_costs: modules::accounts::GasCosts {{ tx_transfer: {} }},
+                    denomination_infos: BTreeMap::from([{denomination_infos}]),
+                    ..Default::default()
+                }},
+                ..Default::default()
+            }},",
+            accounts.gas_costs.tx_transfer,
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "modules::consensus::Genesis {{ parameters: modules::consensus::Parameters {{
+                gas_costs: modules::consensus::GasCosts {{ round_root: {} }},
+                consensus_denomination: {},
+                consensus_scaling_factor: {},
+                min_delegate_amount: {},
+            }} }},",
+            consensus.gas_costs.round_root,
+            denomination(&consensus.consensus_denomination),
+            consensus.consensus_scaling_factor,
+            consensus.min_delegate_amount.0,
+        )
+        .unwrap();
+        let g = &consensus_accounts.gas_costs;
+        writeln!(
+            out,
+            "modules::consensus_accounts::Genesis {{
+                parameters: modules::consensus_accounts::Parameters {{
+                    gas_costs: modules::consensus_accounts::GasCosts {{
+                        tx_deposit: {},
+                        tx_withdraw: {},
+                        tx_delegate: {},
+                        tx_undelegate: {},
+                        store_receipt: {},
+                        take_receipt: {},
+                        delegation: {},
+                        shares_to_tokens: {},
+                    }},
+                    disable_delegate: {},
+                    disable_undelegate: {},
+                    disable_deposit: {},
+                    disable_withdraw: {},
+                }},
+            }},",
+            g.tx_deposit,
+            g.tx_withdraw,
+            g.tx_delegate,
+            g.tx_undelegate,
+            g.store_receipt,
+            g.take_receipt,
+            g.delegation,
+            g.shares_to_tokens,
+            consensus_accounts.disable_delegate,
+            consensus_accounts.disable_undelegate,
+            consensus_accounts.disable_deposit,
+            consensus_accounts.disable_withdraw,
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "modules::rewards::Genesis {{ parameters: modules::rewards::Parameters {{
+                schedule: modules::rewards::types::RewardSchedule {{ steps: vec![{steps}] }},
+                participation_threshold_numerator: {},
+                participation_threshold_denominator: {},
+            }} }},",
+            rewards.participation_threshold_numerator, rewards.participation_threshold_denominator,
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "modules::rofl::Genesis {{ parameters: Default::default(), apps: vec![] }},
+            module_evm::Genesis {{
+                parameters: module_evm::Parameters {{ gas_costs: module_evm::GasCosts {{}} }},
+            }},
+            module_rofl_market::Genesis::default(),",
+        )
+        .unwrap();
+        let signers = governance
+            .signers
+            .iter()
+            .map(|s| address(s))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "governance::Genesis {{ parameters: governance::Parameters {{
+                signers: vec![{signers}],
+                threshold: {},
+                timelock: {},
+            }} }},",
+            governance.threshold, governance.timelock,
+        )
+        .unwrap();
+        let guardians = pause
+            .guardians
+            .iter()
+            .map(|s| address(s))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "pause::Genesis {{ parameters: pause::Parameters {{ guardians: vec![{guardians}] }} \
+             }},",
+        )
+        .unwrap();
+        let methods = gas_policy
+            .methods
+            .iter()
+            .map(|(method, floor)| format!("({method:?}.to_string(), {floor})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let contracts = gas_policy
+            .contracts
+            .iter()
+            .map(|(contract, floor)| format!("({}, {floor})", h160(contract)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "gas_policy::Genesis {{ parameters: gas_policy::Parameters {{
+                methods: BTreeMap::from([{methods}]),
+                contracts: BTreeMap::from([{contracts}]),
+            }} }},",
+        )
+        .unwrap();
+        let lanes = lanes
+            .lanes
+            .iter()
+            .map(|lane| {
+                format!(
+                    "lanes::Lane {{ methods: vec![{}], reserved: {} }}",
+                    lane.methods
+                        .iter()
+                        .map(|method| format!("{method:?}.to_string()"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    lane.reserved,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "lanes::Genesis {{ parameters: lanes::Parameters {{ lanes: vec![{lanes}] }} }},",
+        )
+        .unwrap();
+        let min_cpusvn = match endorsement.min_cpusvn.as_str() {
+            "" => "None".to_string(),
+            svn => format!("Some({})", byte_array(svn)),
+        };
+        let revoked_nodes = endorsement
+            .revoked_nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "sdk::core::common::crypto::signature::PublicKey({})",
+                    byte_array(node)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "endorsement::Genesis {{ parameters: endorsement::Parameters {{
+                min_cpusvn: {min_cpusvn},
+                max_instances_per_entity: {},
+                revoked_nodes: vec![{revoked_nodes}],
+            }} }})",
+            endorsement.max_instances_per_entity,
+        )
+        .unwrap();
+
+        out
+    }
+}
+
+/// Make sure the denomination name is valid.
+fn validate_denomination(name: &str) -> Result<(), String> {
+    if name == NATIVE_DENOMINATION {
+        return Ok(());
+    }
+    if name.is_empty() || name.len() > 32 || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid denomination: {name:?}"));
+    }
+    Ok(())
+}
+
+/// Make sure the given list contains distinct bech32-encoded addresses.
+fn validate_addresses(field: &str, addresses: &[String]) -> Result<(), String> {
+    for (i, address) in addresses.iter().enumerate() {
+        if !is_address(address) {
+            return Err(format!("{field}: invalid address {address:?}"));
+        }
+        if addresses[..i].contains(address) {
+            return Err(format!("{field}: duplicate address {address}"));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the given string is a bech32-encoded address, as accepted by `Address::from_bech32`.
+fn is_address(address: &str) -> bool {
+    match bech32::decode(address) {
+        Ok((hrp, data)) => hrp.as_str() == "oasis" && data.len() == 21,
+        Err(_) => false,
+    }
+}
+
+/// Generate the Rust expression for the given bech32-encoded address.
+fn address(address: &str) -> String {
+    format!("sdk::types::address::Address::from_bech32({address:?}).unwrap()")
+}
+
+/// Whether the given string is the hex encoding of the given number of bytes.
+fn is_hex(hex: &str, len: usize) -> bool {
+    hex.len() == 2 * len && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Generate the Rust expression for the given 0x-prefixed hex-encoded EVM address.
+fn h160(address: &str) -> String {
+    format!("module_evm::types::H160({})", byte_array(&address[2..]))
+}
+
+/// Generate the Rust byte array expression for the given hex-encoded bytes.
+fn byte_array(hex: &str) -> String {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| format!("0x{}", &hex[i..i + 2]))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("[{bytes}]")
+}
+
+/// Generate the Rust expression for the given denomination.
+fn denomination(name: &str) -> String {
+    if name == NATIVE_DENOMINATION {
+        return "Denomination::NATIVE".to_string();
+    }
+    format!("\"{name}\".parse::<Denomination>().unwrap()")
+}
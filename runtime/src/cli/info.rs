@@ -0,0 +1,255 @@
+//! Export of the build configuration and genesis state as JSON.
+use oasis_runtime_sdk::Runtime as _;
+use sapphire_paratime::{network::NETWORK, Runtime};
+use serde_json::{json, Map, Value};
+
+/// Run the `info` subcommand.
+pub(crate) fn run_info(args: &[String]) -> Result<(), String> {
+    expect_no_args(args)?;
+
+    let trust_root = NETWORK.trust_root().map(|root| {
+        json!({
+            "height": root.height,
+            "hash": hex::encode(root.hash.as_ref()),
+            "runtime_id": hex::encode(root.runtime_id.as_ref()),
+            "chain_context": root.chain_context,
+        })
+    });
+    let trusted_signers = Runtime::trusted_signers().map(|ts| {
+        json!({
+            "signers": ts.signers.iter().map(|pk| hex::encode(pk.as_ref())).collect::<Vec<_>>(),
+            "threshold": ts.threshold,
+        })
+    });
+    let schedule_control = Runtime::SCHEDULE_CONTROL;
+
+    let info = json!({
+        "network": NETWORK.name(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "chain_id": NETWORK.chain_id(),
+        "state_version": Runtime::STATE_VERSION,
+        "runtime_id": NETWORK.runtime_id(),
+        "trust_root": trust_root,
+        "trusted_signers": trusted_signers,
+        "schedule_control": {
+            "initial_batch_size": schedule_control.initial_batch_size,
+            "batch_size": schedule_control.batch_size,
+            "min_remaining_gas": schedule_control.min_remaining_gas,
+            "max_tx_count": schedule_control.max_tx_count,
+        },
+        "genesis": genesis(),
+    });
+    print_json(&info);
+
+    Ok(())
+}
+
+/// Run the `genesis` subcommand.
+pub(crate) fn run_genesis(args: &[String]) -> Result<(), String> {
+    expect_no_args(args)?;
+    print_json(&genesis());
+
+    Ok(())
+}
+
+/// Genesis state of all modules, keyed by module name.
+fn genesis() -> Value {
+    let genesis = Runtime::genesis_state();
+
+    json!({
+        "core": to_json(cbor::to_value(genesis.0)),
+        "accounts": to_json(cbor::to_value(genesis.1)),
+        "consensus": to_json(cbor::to_value(genesis.2)),
+        "consensus_accounts": to_json(cbor::to_value(genesis.3)),
+        "rewards": to_json(cbor::to_value(genesis.4)),
+        "rofl": to_json(cbor::to_value(genesis.5)),
+        "evm": to_json(cbor::to_value(genesis.6)),
+        "roflmarket": to_json(cbor::to_value(genesis.7)),
+        "governance": to_json(cbor::to_value(genesis.8)),
+        "pause": to_json(cbor::to_value(genesis.9)),
+        "gas_policy": to_json(cbor::to_value(genesis.10)),
+        "lanes": to_json(cbor::to_value(genesis.11)),
+        "endorsement": to_json(cbor::to_value(genesis.12)),
+    })
+}
+
+/// Fields holding an amount (a `u128` encoded as a big-endian byte string) or a `BaseUnits`.
+const AMOUNT_FIELDS: &[&str] = &["amount", "min_delegate_amount"];
+/// Fields holding a denomination.
+const DENOMINATION_FIELDS: &[&str] = &["consensus_denomination"];
+/// Fields holding maps keyed by denomination.
+const DENOMINATION_MAP_FIELDS: &[&str] = &["min_gas_price", "total_supplies", "denomination_infos"];
+/// Fields holding maps of addresses to maps keyed by denomination.
+const ACCOUNT_MAP_FIELDS: &[&str] = &["balances"];
+
+/// Kind of a field, determining how its byte strings are converted.
+#[derive(Clone, Copy)]
+enum Field {
+    Plain,
+    Amount,
+    Denomination,
+    DenominationMap,
+    AccountMap,
+}
+
+impl Field {
+    fn of(name: &str) -> Self {
+        if AMOUNT_FIELDS.contains(&name) {
+            Self::Amount
+        } else if DENOMINATION_FIELDS.contains(&name) {
+            Self::Denomination
+        } else if DENOMINATION_MAP_FIELDS.contains(&name) {
+            Self::DenominationMap
+        } else if ACCOUNT_MAP_FIELDS.contains(&name) {
+            Self::AccountMap
+        } else {
+            Self::Plain
+        }
+    }
+}
+
+/// Convert a CBOR value into JSON.
+///
+/// Amounts are converted into decimal strings and denominations into their names, with `native`
+/// standing for the native denomination like in the genesis files. Other byte strings (e.g.
+/// addresses and public keys) are hex-encoded and map keys that are not strings are converted to
+/// their JSON representation.
+fn to_json(value: cbor::Value) -> Value {
+    convert(value, Field::Plain)
+}
+
+fn convert(value: cbor::Value, field: Field) -> Value {
+    match (value, field) {
+        (cbor::Value::ByteString(v), Field::Amount) => amount(v),
+        (cbor::Value::ByteString(v), Field::Denomination) => denomination(v),
+        (cbor::Value::ByteString(v), _) => hex::encode(v).into(),
+        // Base units are encoded as an array of the amount and the denomination.
+        (cbor::Value::Array(v), Field::Amount) if v.len() == 2 => {
+            let mut v = v.into_iter();
+            let amount = convert(v.next().unwrap(), Field::Amount);
+            let denomination = convert(v.next().unwrap(), Field::Denomination);
+            json!([amount, denomination])
+        }
+        (cbor::Value::Array(v), _) => v.into_iter().map(to_json).collect(),
+        (cbor::Value::Map(v), Field::DenominationMap) => {
+            map(v, |_| (Field::Denomination, Field::Amount))
+        }
+        (cbor::Value::Map(v), Field::AccountMap) => {
+            map(v, |_| (Field::Plain, Field::DenominationMap))
+        }
+        (cbor::Value::Map(v), _) => map(v, |key| match key {
+            cbor::Value::TextString(name) => (Field::Plain, Field::of(name)),
+            _ => (Field::Plain, Field::Plain),
+        }),
+        (cbor::Value::Unsigned(v), _) => v.into(),
+        (cbor::Value::Negative(v), _) => v.into(),
+        (cbor::Value::TextString(v), _) => v.into(),
+        (cbor::Value::Tag(_, v), field) => convert(*v, field),
+        (cbor::Value::Simple(cbor::SimpleValue::FalseValue), _) => false.into(),
+        (cbor::Value::Simple(cbor::SimpleValue::TrueValue), _) => true.into(),
+        (cbor::Value::Simple(_), _) => Value::Null,
+    }
+}
+
+/// Convert a CBOR map into a JSON object, converting keys and values as given by `fields`.
+fn map(
+    entries: Vec<(cbor::Value, cbor::Value)>,
+    fields: impl Fn(&cbor::Value) -> (Field, Field),
+) -> Value {
+    entries
+        .into_iter()
+        .map(|(k, v)| {
+            let (key_field, value_field) = fields(&k);
+            let k = match convert(k, key_field) {
+                Value::String(k) => k,
+                k => k.to_string(),
+            };
+            (k, convert(v, value_field))
+        })
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+/// Decimal representation of an amount, hex-encoded in case it is not a valid `u128`.
+fn amount(v: Vec<u8>) -> Value {
+    if v.len() > 16 {
+        return hex::encode(v).into();
+    }
+    let mut bytes = [0u8; 16];
+    bytes[16 - v.len()..].copy_from_slice(&v);
+    u128::from_be_bytes(bytes).to_string().into()
+}
+
+/// Name of a denomination, hex-encoded in case it is not valid UTF-8.
+fn denomination(v: Vec<u8>) -> Value {
+    if v.is_empty() {
+        return "native".into();
+    }
+    match String::from_utf8(v) {
+        Ok(name) => name.into(),
+        Err(err) => hex::encode(err.into_bytes()).into(),
+    }
+}
+
+/// Pretty-print the given JSON value.
+fn print_json(value: &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+/// Make sure that no arguments were passed.
+fn expect_no_args(args: &[String]) -> Result<(), String> {
+    match args.first() {
+        Some(arg) => Err(format!("unexpected argument: {arg}")),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use oasis_runtime_sdk::types::{
+        address::Address,
+        token::{BaseUnits, Denomination},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let test: Denomination = "TEST".parse().unwrap();
+        let address = Address::from_bytes(&[1; 21]).unwrap();
+        let account = hex::encode(address.as_ref());
+        let min_gas_price = BTreeMap::from([
+            (Denomination::NATIVE, 100_000_000_000u128),
+            (test.clone(), 0),
+        ]);
+        let balances = BTreeMap::from([(address, BTreeMap::from([(test.clone(), 5u128)]))]);
+        let reward = BaseUnits::new(3_000_000_000_000_000_000, Denomination::NATIVE);
+        let value = cbor::Value::Map(
+            [
+                ("min_gas_price", cbor::to_value(min_gas_price)),
+                ("consensus_denomination", cbor::to_value(test)),
+                ("min_delegate_amount", cbor::to_value(u128::MAX)),
+                ("amount", cbor::to_value(reward)),
+                ("balances", cbor::to_value(balances)),
+                ("key", cbor::Value::ByteString(vec![0xab, 0xcd])),
+            ]
+            .into_iter()
+            .map(|(k, v)| (cbor::Value::TextString(k.to_string()), v))
+            .collect(),
+        );
+
+        assert_eq!(
+            to_json(value),
+            json!({
+                "min_gas_price": {"native": "100000000000", "TEST": "0"},
+                "consensus_denomination": "TEST",
+                "min_delegate_amount": u128::MAX.to_string(),
+                "amount": ["3000000000000000000", "native"],
+                "balances": {account: {"TEST": "5"}},
+                "key": "abcd",
+            })
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Dry-run of the state migration against a state dump.
+//!
+//! The state dump is a JSON object mapping hex-encoded raw runtime state keys to hex-encoded
+//! values. No node command exports this format, see the README for how to produce one. The dump
+//! is loaded into an in-memory mock context where the runtime is upgraded the same way a node
+//! upgrades it, after which the differences in module parameters are printed.
+use std::{collections::BTreeMap, fmt, fs};
+
+use oasis_runtime_sdk::{modules, state::CurrentState, testing::mock::Mock, Module, Runtime as _};
+use sapphire_paratime::{
+    endorsement, gas_policy, governance, lanes, migrations, network::NETWORK, pause, Config,
+    Runtime,
+};
+
+/// Modules whose parameters are compared, together with a function returning them.
+const MODULES: &[(&str, fn() -> String)] = &[
+    ("core", params::<modules::core::Module<Config>>),
+    ("accounts", params::<modules::accounts::Module>),
+    ("consensus", params::<modules::consensus::Module>),
+    (
+        "consensus_accounts",
+        params::<modules::consensus_accounts::Module<modules::consensus::Module>>,
+    ),
+    ("rewards", params::<modules::rewards::Module>),
+    ("rofl", params::<modules::rofl::Module<Config>>),
+    ("evm", params::<module_evm::Module<Config>>),
+    ("roflmarket", params::<module_rofl_market::Module<Config>>),
+    ("governance", params::<governance::Module>),
+    ("pause", params::<pause::Module>),
+    ("gas_policy", params::<gas_policy::Module>),
+    ("lanes", params::<lanes::Module>),
+    ("endorsement", params::<endorsement::Module>),
+];
+
+/// Run the `migrate` subcommand.
+pub(crate) fn run(args: &[String]) -> Result<(), String> {
+    let mut dry_run = false;
+    let mut from_state = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--from-state" => {
+                from_state = Some(args.next().ok_or("missing value for --from-state")?);
+            }
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+    if !dry_run {
+        return Err("only --dry-run migrations are supported".to_string());
+    }
+    let from_state = from_state.ok_or("missing --from-state")?;
+
+    let report = dry_run(&load_dump(from_state)?)?;
+
+    println!("Network: {}", NETWORK.name());
+    println!("State version: {} -> {}", report.from, report.to);
+    if report.from == report.to {
+        println!("State is up to date, nothing to migrate.");
+        return Ok(());
+    }
+    for step in &report.steps {
+        println!("  {} -> {}: {}", step.from, step.from + 1, step.description);
+    }
+    if report.from + 1 != report.to {
+        println!(
+            "Warning: nodes only migrate state from version {}, the releases in between must be \
+             deployed first.",
+            report.to - 1
+        );
+    }
+
+    for (name, before, after) in &report.changes {
+        println!();
+        println!("--- {name} (current)");
+        println!("+++ {name} (migrated)");
+        print!("{}", Diff::new(before, after));
+    }
+    if report.changes.is_empty() {
+        println!();
+        println!("No module parameters would change.");
+    }
+
+    Ok(())
+}
+
+/// Outcome of a migration dry-run.
+struct Report {
+    /// State version of the dump.
+    from: u32,
+    /// State version of this build.
+    to: u32,
+    /// Migration steps that were executed.
+    steps: Vec<migrations::Migration>,
+    /// Names of modules whose parameters changed, with the pretty-printed parameters before and
+    /// after the migration.
+    changes: Vec<(&'static str, String, String)>,
+}
+
+/// Run the migration against the given state dump in an in-memory mock context.
+fn dry_run(dump: &BTreeMap<Vec<u8>, Vec<u8>>) -> Result<Report, String> {
+    let mut mock = Mock::default();
+    let ctx = mock.create_ctx_for_runtime::<Runtime>(false);
+    CurrentState::with_store(|store| {
+        for (key, value) in dump {
+            store.insert(key, value);
+        }
+    });
+
+    // Without the core metadata the dump cannot be a runtime state dump (e.g. it is empty or
+    // its keys are not hex-encoded raw state keys). Migrating it would report no changes.
+    let from = migrations::try_recorded_state_version().ok_or(
+        "state dump has no recorded state version, make sure it is a full runtime state dump with \
+         hex-encoded raw keys",
+    )?;
+    let to = Runtime::STATE_VERSION;
+    if from > to {
+        return Err(format!("state version {from} is newer than {to}"));
+    }
+    let steps =
+        migrations::plan(migrations::registry(NETWORK), from, to).map_err(|err| err.to_string())?;
+
+    let before: Vec<String> = MODULES.iter().map(|(_, params)| params()).collect();
+    // Nodes must have deployed the releases in between, which ran the earlier steps.
+    if from + 1 < to {
+        migrations::migrate(from, to - 1).map_err(|err| err.to_string())?;
+        migrations::set_recorded_state_version(to - 1);
+    }
+    // Upgrade through the SDK like a node would, which also initializes new modules from genesis
+    // and runs the last step.
+    Runtime::migrate(&ctx);
+    let after: Vec<String> = MODULES.iter().map(|(_, params)| params()).collect();
+
+    let changes = MODULES
+        .iter()
+        .zip(before.into_iter().zip(after))
+        .filter(|(_, (before, after))| before != after)
+        .map(|((name, _), (before, after))| (*name, before, after))
+        .collect();
+
+    Ok(Report {
+        from,
+        to,
+        steps,
+        changes,
+    })
+}
+
+/// Load a state dump from the given file.
+fn load_dump(path: &str) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, String> {
+    let raw = fs::read(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+    let dump: BTreeMap<String, String> =
+        serde_json::from_slice(&raw).map_err(|err| format!("malformed state dump: {err}"))?;
+
+    dump.into_iter()
+        .map(|(key, value)| {
+            let key = hex::decode(&key).map_err(|err| format!("malformed key {key}: {err}"))?;
+            let value =
+                hex::decode(&value).map_err(|err| format!("malformed value {value}: {err}"))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Pretty-printed parameters of the given module.
+fn params<M: Module>() -> String
+where
+    M::Parameters: fmt::Debug,
+{
+    format!("{:#?}", M::params())
+}
+
+/// Line-based diff of two texts.
+struct Diff<'a> {
+    lines: Vec<(char, &'a str)>,
+}
+
+impl<'a> Diff<'a> {
+    /// Compute the diff using the longest common subsequence of lines.
+    fn new(before: &'a str, after: &'a str) -> Self {
+        let a: Vec<&str> = before.lines().collect();
+        let b: Vec<&str> = after.lines().collect();
+
+        // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+        let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        let mut lines = Vec::new();
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                lines.push((' ', a[i]));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                lines.push(('-', a[i]));
+                i += 1;
+            } else {
+                lines.push(('+', b[j]));
+                j += 1;
+            }
+        }
+
+        Self { lines }
+    }
+}
+
+impl fmt::Display for Diff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (tag, line) in &self.lines {
+            writeln!(f, "{tag}{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+
+    /// State dump containing only the core module metadata with the given state version.
+    fn dump_at_version(version: u32) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        let key = [
+            modules::core::MODULE_NAME.as_bytes(),
+            modules::core::state::METADATA,
+        ]
+        .concat();
+        let meta = modules::core::types::Metadata {
+            versions: [(
+                modules::core::types::VERSION_GLOBAL_KEY.to_string(),
+                version,
+            )]
+            .into(),
+        };
+        [(key, cbor::to_vec(meta))].into()
+    }
+
+    #[test]
+    fn test_load_dump() {
+        let path = env::temp_dir().join(format!("sapphire-dump-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        fs::write(path, r#"{"00ff": "", "636f7265": "0102"}"#).unwrap();
+        let dump = load_dump(path).unwrap();
+        assert_eq!(
+            dump,
+            [
+                (vec![0x00, 0xff], vec![]),
+                (b"core".to_vec(), vec![0x01, 0x02])
+            ]
+            .into()
+        );
+
+        fs::write(path, r#"{"core": "00"}"#).unwrap();
+        assert!(load_dump(path)
+            .unwrap_err()
+            .starts_with("malformed key core"));
+        fs::write(path, r#"["00"]"#).unwrap();
+        assert!(load_dump(path)
+            .unwrap_err()
+            .starts_with("malformed state dump"));
+
+        fs::remove_file(path).unwrap();
+        assert!(load_dump(path).unwrap_err().starts_with("failed to read"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let diff = Diff::new("a\nb\nc\n", "a\nx\nc\nd\n");
+        assert_eq!(diff.to_string(), " a\n-b\n+x\n c\n+d\n");
+        assert_eq!(Diff::new("a\n", "a\n").to_string(), " a\n");
+        assert_eq!(Diff::new("", "a\n").to_string(), "+a\n");
+    }
+
+    #[test]
+    fn test_dry_run_empty_dump() {
+        let err = dry_run(&BTreeMap::new()).err().unwrap();
+        assert!(err.contains("no recorded state version"), "{err}");
+    }
+
+    #[test]
+    fn test_dry_run_unsupported_version() {
+        let err = dry_run(&dump_at_version(1)).err().unwrap();
+        assert_eq!(err, "missing state migration from version 1");
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let report = dry_run(&dump_at_version(Runtime::STATE_VERSION - 1)).unwrap();
+        assert_eq!(report.from, Runtime::STATE_VERSION - 1);
+        assert_eq!(report.to, Runtime::STATE_VERSION);
+        assert_eq!(report.steps.len(), 1);
+
+        // The dump has no parameters, so migrating sets them.
+        let (name, before, after) = &report.changes[0];
+        assert_eq!(*name, "core");
+        assert_eq!(
+            before,
+            &format!("{:#?}", modules::core::Parameters::default())
+        );
+        assert_eq!(
+            after,
+            &format!("{:#?}", Runtime::genesis_state().0.parameters)
+        );
+    }
+}
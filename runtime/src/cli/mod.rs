@@ -0,0 +1,58 @@
+//! Operator tooling bundled with the runtime binary.
+mod info;
+mod migrate;
+
+/// Usage of the available subcommands.
+const USAGE: &str = "\
+Usage: sapphire-paratime [COMMAND]
+
+Without a command the runtime is started.
+
+Commands:
+  info
+      Print the network configuration and genesis state the binary was built with as JSON.
+  genesis
+      Print the genesis state the binary was built with as JSON.
+  migrate --dry-run --from-state <DUMP>
+      Run the state migration against the given state dump and print the parameter changes.";
+
+/// Run the subcommand given by the command-line arguments.
+///
+/// Returns the process exit code or `None` in case no arguments have been given. Unknown
+/// subcommands are reported as errors rather than starting the runtime.
+pub(crate) fn run(args: Vec<String>) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+    let result = match command.as_str() {
+        "info" => info::run_info(args),
+        "genesis" => info::run_genesis(args),
+        "migrate" => migrate::run(args),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(format!("unknown command: {command}")),
+    };
+
+    match result {
+        Ok(()) => Some(0),
+        Err(err) => {
+            eprintln!("Error: {err}");
+            eprintln!();
+            eprintln!("{USAGE}");
+            Some(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        assert_eq!(run(vec![]), None);
+        assert_eq!(run(vec!["help".to_string()]), Some(0));
+        assert_eq!(run(vec!["migrte".to_string()]), Some(1));
+        assert_eq!(run(vec!["info".to_string(), "extra".to_string()]), Some(1));
+    }
+}
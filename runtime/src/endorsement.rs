@@ -0,0 +1,218 @@
+//! Sapphire-specific ROFL endorsement policy.
+//!
+//! The evaluator is the first of the endorsement policy evaluators of the ROFL module and enforces
+//! requirements that the application policies provided by the SDK cannot express:
+//!
+//! * a minimum CPU security version (CPUSVN for SGX, TEE_TCB_SVN for TDX),
+//! * a maximum number of ROFL instances endorsed by nodes of the same entity,
+//! * a list of revoked nodes that may not endorse any instance.
+//!
+//! The evaluators are consulted in order until one of them decides, so the requirements apply to
+//! every application policy. The evaluator never allows an endorsement by itself, it rejects those
+//! that do not satisfy the requirements and passes all others on to the basic and provider
+//! evaluators.
+use std::collections::BTreeMap;
+
+use oasis_runtime_sdk::{
+    context::Context,
+    core::{
+        common::{crypto::signature::PublicKey, sgx},
+        consensus::{
+            registry::{CapabilityTEE, EndorsedCapabilityTEE},
+            state::registry::ImmutableState as RegistryImmutableState,
+        },
+    },
+    handler, migration,
+    module::{self, Module as _, Parameters as _},
+    modules::{
+        self,
+        rofl::{self, policy::AllowedEndorsement},
+    },
+    sdk_derive,
+};
+use thiserror::Error;
+
+/// Unique module name.
+pub const MODULE_NAME: &str = "endorsement";
+
+/// Size of the header preceding the report body in SGX (v3) and TDX (v4) quotes. The report body
+/// starts with the 16-byte CPUSVN or TEE_TCB_SVN respectively.
+const QUOTE_HEADER_SIZE: usize = 48;
+
+/// Errors emitted by the endorsement module.
+#[derive(Error, Debug, oasis_runtime_sdk::Error)]
+pub enum Error {
+    #[error("core: {0}")]
+    #[sdk_error(transparent)]
+    Core(#[from] modules::core::Error),
+}
+
+/// Parameters for the endorsement module.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Parameters {
+    /// Minimum CPU security version, compared component-wise. Endorsements of TEEs without a
+    /// PCS quote are rejected when set.
+    pub min_cpusvn: Option<[u8; 16]>,
+    /// Maximum number of registered instances endorsed by nodes of the same entity, zero for no
+    /// limit.
+    pub max_instances_per_entity: u64,
+    /// Nodes that may not endorse any instance.
+    pub revoked_nodes: Vec<PublicKey>,
+}
+
+/// Errors emitted during parameter validation.
+#[derive(Error, Debug)]
+pub enum ParameterValidationError {
+    #[error("duplicate revoked node")]
+    DuplicateRevokedNode,
+}
+
+impl module::Parameters for Parameters {
+    type Error = ParameterValidationError;
+
+    fn validate_basic(&self) -> Result<(), Self::Error> {
+        for (i, node) in self.revoked_nodes.iter().enumerate() {
+            if self.revoked_nodes[..i].contains(node) {
+                return Err(ParameterValidationError::DuplicateRevokedNode);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Genesis state for the endorsement module.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Genesis {
+    pub parameters: Parameters,
+}
+
+/// The endorsement module.
+pub struct Module;
+
+#[sdk_derive(Module)]
+impl Module {
+    const NAME: &'static str = MODULE_NAME;
+    const VERSION: u32 = 1;
+    type Error = Error;
+    type Event = ();
+    type Parameters = Parameters;
+    type Genesis = Genesis;
+
+    #[migration(init)]
+    fn init(genesis: Genesis) {
+        genesis
+            .parameters
+            .validate_basic()
+            .expect("invalid genesis parameters");
+        Self::set_params(genesis.parameters);
+    }
+
+    #[handler(query = "endorsement.Parameters")]
+    fn query_parameters<C: Context>(_ctx: &C, _args: ()) -> Result<Parameters, Error> {
+        Ok(Self::params())
+    }
+}
+
+impl module::TransactionHandler for Module {}
+
+impl module::BlockHandler for Module {}
+
+impl module::InvariantHandler for Module {}
+
+/// Endorsement policy evaluator enforcing the module parameters.
+pub struct PolicyEvaluator;
+
+impl rofl::policy::EndorsementPolicyEvaluator for PolicyEvaluator {
+    fn verify(
+        ctx: &impl Context,
+        _policy: &AllowedEndorsement,
+        ect: &EndorsedCapabilityTEE,
+        _metadata: &BTreeMap<String, String>,
+    ) -> Result<Option<bool>, rofl::Error> {
+        let params = Module::params();
+        let node_id = ect.node_endorsement.public_key;
+
+        if params.revoked_nodes.contains(&node_id) {
+            return Ok(Some(false));
+        }
+        if let Some(min_cpusvn) = &params.min_cpusvn {
+            match cpusvn(&ect.capability_tee) {
+                Some(cpusvn) if meets_cpusvn(&cpusvn, min_cpusvn) => {}
+                _ => return Ok(Some(false)),
+            }
+        }
+        if params.max_instances_per_entity > 0 {
+            let registry = RegistryImmutableState::new(ctx.consensus_state());
+            let Some(node) = registry.node(&node_id).ok().flatten() else {
+                return Ok(Some(false));
+            };
+            // Re-registrations of the same instance do not count towards the limit.
+            let instances = rofl::state::get_registrations_for_entity(&node.entity_id)
+                .iter()
+                .filter(|registration| registration.rak != ect.capability_tee.rak)
+                .count();
+            if instances as u64 >= params.max_instances_per_entity {
+                return Ok(Some(false));
+            }
+        }
+
+        // Leave the decision to the other evaluators.
+        Ok(None)
+    }
+}
+
+/// CPU security version of the TEE, if it has been attested using a PCS quote.
+fn cpusvn(capability: &CapabilityTEE) -> Option<[u8; 16]> {
+    let sgx::Quote::Pcs(bundle) = cbor::from_slice::<sgx::Quote>(&capability.attestation).ok()?
+    else {
+        return None;
+    };
+    bundle
+        .quote
+        .get(QUOTE_HEADER_SIZE..QUOTE_HEADER_SIZE + 16)?
+        .try_into()
+        .ok()
+}
+
+/// Whether each component of the CPU security version is at least the minimum.
+fn meets_cpusvn(cpusvn: &[u8; 16], min_cpusvn: &[u8; 16]) -> bool {
+    cpusvn.iter().zip(min_cpusvn).all(|(svn, min)| svn >= min)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_meets_cpusvn() {
+        let mut min = [0u8; 16];
+        min[0] = 4;
+        min[1] = 2;
+
+        let mut cpusvn = min;
+        assert!(meets_cpusvn(&cpusvn, &min));
+        cpusvn[15] = 1;
+        assert!(meets_cpusvn(&cpusvn, &min));
+        // Components are not compared lexicographically.
+        cpusvn[0] = 5;
+        cpusvn[1] = 1;
+        assert!(!meets_cpusvn(&cpusvn, &min));
+        assert!(meets_cpusvn(&cpusvn, &[0; 16]));
+    }
+
+    #[test]
+    fn test_validate_basic() {
+        let node = PublicKey([1; 32]);
+        let params = Parameters {
+            revoked_nodes: vec![node, PublicKey([2; 32])],
+            ..Default::default()
+        };
+        assert!(params.validate_basic().is_ok());
+
+        let params = Parameters {
+            revoked_nodes: vec![node, node],
+            ..Default::default()
+        };
+        assert!(params.validate_basic().is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Minimum gas price policy module.
+//!
+//! The core module enforces the minimum gas price for all methods except those listed in
+//! [`MIN_GAS_PRICE_EXEMPT_METHODS`]. For the exempt methods, except those in [`FREE_METHODS`],
+//! this module enforces a floor relative to the current minimum gas price instead. This allows
+//! selected methods (e.g. `rofl.Register`) and calls of allowlisted EVM contracts to pay a
+//! discounted gas price so that they are not priced out when the dynamic minimum gas price rises
+//! during congestion.
+//!
+//! Only methods with a floor in the genesis state of the network are exempt, and `evm.Call` only
+//! in case floors for contracts are configured there. Parameter updates can thus only change the
+//! floors of these methods. Calls of exempt methods without a floor (e.g. of other contracts) pay
+//! the full minimum gas price.
+//!
+//! [`MIN_GAS_PRICE_EXEMPT_METHODS`]: oasis_runtime_sdk::modules::core::Config::MIN_GAS_PRICE_EXEMPT_METHODS
+use std::collections::BTreeMap;
+
+use module_evm::types::H160;
+use oasis_runtime_sdk::{
+    context::Context,
+    handler, migration,
+    module::{self, Module as _, Parameters as _},
+    modules::{
+        self,
+        core::{Config as _, API as _},
+    },
+    sdk_derive,
+    state::CurrentState,
+    types::transaction::Call,
+};
+use thiserror::Error;
+
+use crate::Config;
+
+/// Unique module name.
+pub const MODULE_NAME: &str = "gas_policy";
+
+/// Methods which do not need to pay any minimum gas price.
+pub const FREE_METHODS: &[&str] = &["consensus.Deposit"];
+
+/// Denominator of the minimum gas price floors.
+pub const FLOOR_DENOMINATOR: u16 = 1_000;
+
+/// Errors emitted by the gas policy module.
+#[derive(Error, Debug, oasis_runtime_sdk::Error)]
+pub enum Error {
+    #[error("core: {0}")]
+    #[sdk_error(transparent)]
+    Core(#[from] modules::core::Error),
+}
+
+/// Parameters for the gas policy module.
+///
+/// Floors are given in thousandths of the current minimum gas price.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Parameters {
+    /// Minimum gas price floors of methods exempt from the core minimum gas price.
+    pub methods: BTreeMap<String, u16>,
+    /// Minimum gas price floors of `evm.Call` calls of allowlisted contracts.
+    pub contracts: BTreeMap<H160, u16>,
+}
+
+/// Errors emitted during parameter validation.
+#[derive(Error, Debug)]
+pub enum ParameterValidationError {
+    #[error("method {0} is not exempt from the core minimum gas price")]
+    MethodNotExempt(String),
+    #[error("floor exceeds the minimum gas price")]
+    FloorTooHigh,
+}
+
+impl module::Parameters for Parameters {
+    type Error = ParameterValidationError;
+
+    fn validate_basic(&self) -> Result<(), Self::Error> {
+        for method in self.methods.keys() {
+            if !is_policy_method(method) || method == "evm.Call" {
+                return Err(ParameterValidationError::MethodNotExempt(method.clone()));
+            }
+        }
+        if !self.contracts.is_empty() && !is_policy_method("evm.Call") {
+            return Err(ParameterValidationError::MethodNotExempt(
+                "evm.Call".to_string(),
+            ));
+        }
+        if self
+            .methods
+            .values()
+            .chain(self.contracts.values())
+            .any(|floor| *floor > FLOOR_DENOMINATOR)
+        {
+            return Err(ParameterValidationError::FloorTooHigh);
+        }
+        Ok(())
+    }
+}
+
+impl Parameters {
+    /// Minimum gas price floor of the given call.
+    fn floor(&self, call: &Call) -> u16 {
+        if call.method == "evm.Call" {
+            return cbor::from_value::<module_evm::types::Call>(call.body.clone())
+                .ok()
+                .and_then(|body| self.contracts.get(&body.address).copied())
+                .unwrap_or(FLOOR_DENOMINATOR);
+        }
+        self.methods
+            .get(&call.method)
+            .copied()
+            .unwrap_or(FLOOR_DENOMINATOR)
+    }
+}
+
+/// Genesis state for the gas policy module.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Genesis {
+    pub parameters: Parameters,
+}
+
+/// The gas policy module.
+pub struct Module;
+
+#[sdk_derive(Module)]
+impl Module {
+    const NAME: &'static str = MODULE_NAME;
+    const VERSION: u32 = 1;
+    type Error = Error;
+    type Event = ();
+    type Parameters = Parameters;
+    type Genesis = Genesis;
+
+    #[migration(init)]
+    fn init(genesis: Genesis) {
+        genesis
+            .parameters
+            .validate_basic()
+            .expect("invalid genesis parameters");
+        Self::set_params(genesis.parameters);
+    }
+
+    #[handler(query = "gas_policy.Parameters")]
+    fn query_parameters<C: Context>(_ctx: &C, _args: ()) -> Result<Parameters, Error> {
+        Ok(Self::params())
+    }
+}
+
+impl module::TransactionHandler for Module {
+    fn before_handle_call<C: Context>(ctx: &C, call: &Call) -> Result<(), modules::core::Error> {
+        // Internal calls (e.g. EVM subcalls) are covered by the fee of the transaction.
+        if CurrentState::with_env(|env| env.is_internal()) {
+            return Ok(());
+        }
+        if !is_policy_method(&call.method) || FREE_METHODS.contains(&call.method.as_str()) {
+            return Ok(());
+        }
+
+        let fee = CurrentState::with_env(|env| env.tx_auth_info().fee.clone());
+        let denomination = fee.amount.denomination();
+        let mut min_gas_price = modules::core::Module::<Config>::min_gas_price(denomination)
+            .ok_or(modules::core::Error::GasPriceTooLow)?;
+        if CurrentState::with_env(|env| env.is_check_only()) {
+            // Honor the node-local minimum gas price in checks, consistent with the core module.
+            #[allow(clippy::borrow_interior_mutable_const)]
+            let local_min_gas_price = ctx
+                .local_config::<modules::core::LocalConfig>(modules::core::MODULE_NAME)
+                .as_ref()
+                .map(|cfg| &cfg.min_gas_price)
+                .unwrap_or(&Config::DEFAULT_LOCAL_MIN_GAS_PRICE)
+                .get(denomination)
+                .copied()
+                .unwrap_or_default();
+            min_gas_price = min_gas_price.max(local_min_gas_price);
+        }
+
+        let floor = Self::params().floor(call);
+        if fee.gas_price() < discounted(min_gas_price, floor) {
+            return Err(modules::core::Error::GasPriceTooLow);
+        }
+        Ok(())
+    }
+}
+
+impl module::BlockHandler for Module {}
+
+impl module::InvariantHandler for Module {}
+
+/// Whether the gas price of the given method is governed by this module.
+pub fn is_policy_method(method: &str) -> bool {
+    #[allow(clippy::borrow_interior_mutable_const)]
+    Config::MIN_GAS_PRICE_EXEMPT_METHODS.contains(method)
+}
+
+/// Minimum gas price discounted to the given floor.
+fn discounted(min_gas_price: u128, floor: u16) -> u128 {
+    min_gas_price.saturating_mul(floor.into()) / u128::from(FLOOR_DENOMINATOR)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::network::NETWORK;
+
+    #[test]
+    fn test_discounted() {
+        // Floors follow the minimum gas price as it changes.
+        for min_gas_price in [0, 1, 100_000_000_000, 112_500_000_000, 87_500_000_000] {
+            assert_eq!(discounted(min_gas_price, FLOOR_DENOMINATOR), min_gas_price);
+            assert_eq!(discounted(min_gas_price, 500), min_gas_price / 2);
+            assert_eq!(discounted(min_gas_price, 0), 0);
+        }
+        assert_eq!(discounted(u128::MAX, 500), u128::MAX / 1_000);
+    }
+
+    #[test]
+    fn test_validate_basic() {
+        let genesis = NETWORK.genesis_state().10.parameters;
+        assert!(genesis.validate_basic().is_ok());
+        assert_eq!(
+            Parameters {
+                contracts: [(H160::repeat_byte(1), 0)].into(),
+                ..Default::default()
+            }
+            .validate_basic()
+            .is_ok(),
+            !genesis.contracts.is_empty(),
+            "contract floors should only be valid if configured in genesis"
+        );
+
+        let mut invalid = vec![
+            Parameters {
+                methods: [("accounts.Transfer".to_string(), 500)].into(),
+                ..Default::default()
+            },
+            Parameters {
+                methods: [("evm.Call".to_string(), 500)].into(),
+                ..Default::default()
+            },
+            Parameters {
+                contracts: [(H160::repeat_byte(1), 1_001)].into(),
+                ..Default::default()
+            },
+        ];
+        if let Some(method) = genesis.methods.keys().next() {
+            invalid.push(Parameters {
+                methods: [(method.clone(), 1_001)].into(),
+                ..Default::default()
+            });
+        }
+        for params in invalid {
+            assert!(
+                params.validate_basic().is_err(),
+                "{params:?} should be rejected"
+            );
+        }
+    }
+}
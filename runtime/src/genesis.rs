@@ -0,0 +1,17 @@
+//! Genesis state of the networks the runtime can be built for.
+//!
+//! The parameters are defined in the `genesis/<network>.toml` files which are validated and
+//! embedded into the runtime by the build script.
+use std::collections::BTreeMap;
+
+use oasis_runtime_sdk::{
+    self as sdk, modules,
+    types::token::{BaseUnits, Denomination},
+};
+
+use crate::{endorsement, gas_policy, governance, lanes, pause, Runtime};
+
+/// Genesis state of all runtime modules.
+pub type Genesis = <<Runtime as sdk::Runtime>::Modules as sdk::module::MigrationHandler>::Genesis;
+
+include!(concat!(env!("OUT_DIR"), "/genesis.rs"));
@@ -0,0 +1,496 @@
+//! Governance module.
+//!
+//! Allows a threshold multisig of signers defined in genesis to update the parameters of the
+//! runtime modules without deploying a new runtime binary. Any signer can propose a parameter
+//! update which can be executed once it has been approved by the threshold of signers and the
+//! timelock has passed. Setting the threshold to zero disables governance.
+//!
+//! The fields changed by executed updates are recorded per module and reapplied whenever state
+//! migrations reset the parameters from genesis (see [`crate::migrations::update_parameters`]), so
+//! updates persist across runtime upgrades until they are superseded by another proposal. Fields
+//! that were never changed through governance take the genesis values of the new release.
+use oasis_runtime_sdk::{
+    context::Context,
+    handler, migration,
+    module::{self, Module as _, Parameters as _},
+    modules::{self, core::API as _},
+    sdk_derive,
+    state::CurrentState,
+    types::address::Address,
+    Runtime,
+};
+use thiserror::Error;
+
+use crate::{endorsement, gas_policy, lanes, pause, Config};
+
+pub mod state;
+pub mod types;
+
+/// Unique module name.
+pub const MODULE_NAME: &str = "governance";
+
+/// Gas cost of governance.Propose call.
+pub const GAS_COST_CALL_PROPOSE: u64 = 100_000;
+/// Gas cost of governance.Approve call.
+pub const GAS_COST_CALL_APPROVE: u64 = 10_000;
+/// Gas cost of governance.Execute call.
+pub const GAS_COST_CALL_EXECUTE: u64 = 100_000;
+/// Gas cost of governance.Cancel call.
+pub const GAS_COST_CALL_CANCEL: u64 = 10_000;
+
+/// Errors emitted by the governance module.
+#[derive(Error, Debug, oasis_runtime_sdk::Error)]
+pub enum Error {
+    #[error("invalid argument")]
+    #[sdk_error(code = 1)]
+    InvalidArgument,
+
+    #[error("forbidden")]
+    #[sdk_error(code = 2)]
+    Forbidden,
+
+    #[error("proposal not found")]
+    #[sdk_error(code = 3)]
+    ProposalNotFound,
+
+    #[error("proposal already executed")]
+    #[sdk_error(code = 4)]
+    ProposalExecuted,
+
+    #[error("proposal not approved")]
+    #[sdk_error(code = 5)]
+    ProposalNotApproved,
+
+    #[error("proposal timelocked until {0}")]
+    #[sdk_error(code = 6)]
+    ProposalTimelocked(u64),
+
+    #[error("invalid parameters: {0}")]
+    #[sdk_error(code = 7)]
+    InvalidParameters(String),
+
+    #[error("core: {0}")]
+    #[sdk_error(transparent)]
+    Core(#[from] modules::core::Error),
+}
+
+/// Events emitted by the governance module.
+#[derive(Debug, PartialEq, Eq, cbor::Encode, oasis_runtime_sdk::Event)]
+#[cbor(untagged)]
+pub enum Event {
+    #[sdk_event(code = 1)]
+    ProposalSubmitted { id: u64, submitter: Address },
+
+    #[sdk_event(code = 2)]
+    ProposalApproved { id: u64, signer: Address },
+
+    #[sdk_event(code = 3)]
+    ProposalExecuted { id: u64 },
+
+    #[sdk_event(code = 4)]
+    ProposalCancelled { id: u64 },
+}
+
+/// Parameters for the governance module.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Parameters {
+    /// Signers that can propose and approve parameter updates.
+    pub signers: Vec<Address>,
+    /// Number of signer approvals needed to execute a proposal. Zero disables governance.
+    pub threshold: u16,
+    /// Time (in seconds) between a proposal reaching the threshold and its execution.
+    pub timelock: u64,
+}
+
+/// Errors emitted during parameter validation.
+#[derive(Error, Debug)]
+pub enum ParameterValidationError {
+    #[error("threshold must be at most the number of signers")]
+    ThresholdTooHigh,
+    #[error("signers must be empty when governance is disabled")]
+    UnusedSigners,
+    #[error("duplicate signer")]
+    DuplicateSigner,
+}
+
+impl module::Parameters for Parameters {
+    type Error = ParameterValidationError;
+
+    fn validate_basic(&self) -> Result<(), Self::Error> {
+        if usize::from(self.threshold) > self.signers.len() {
+            return Err(ParameterValidationError::ThresholdTooHigh);
+        }
+        if self.threshold == 0 && !self.signers.is_empty() {
+            return Err(ParameterValidationError::UnusedSigners);
+        }
+        for (i, signer) in self.signers.iter().enumerate() {
+            if self.signers[..i].contains(signer) {
+                return Err(ParameterValidationError::DuplicateSigner);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Parameters {
+    /// Whether the given address is one of the signers.
+    fn is_signer(&self, address: &Address) -> bool {
+        self.threshold > 0 && self.signers.contains(address)
+    }
+
+    /// Whether the given approvals reach the threshold, counting only current signers.
+    fn is_approved(&self, approvals: &[Address]) -> bool {
+        let approvals = approvals.iter().filter(|a| self.is_signer(a)).count();
+        self.threshold > 0 && approvals >= usize::from(self.threshold)
+    }
+}
+
+/// Genesis state for the governance module.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Genesis {
+    pub parameters: Parameters,
+}
+
+/// The governance module.
+pub struct Module;
+
+#[sdk_derive(Module)]
+impl Module {
+    const NAME: &'static str = MODULE_NAME;
+    const VERSION: u32 = 1;
+    type Error = Error;
+    type Event = Event;
+    type Parameters = Parameters;
+    type Genesis = Genesis;
+
+    #[migration(init)]
+    fn init(genesis: Genesis) {
+        genesis
+            .parameters
+            .validate_basic()
+            .expect("invalid genesis parameters");
+        Self::set_params(genesis.parameters);
+    }
+
+    #[handler(call = "governance.Propose")]
+    fn tx_propose<C: Context>(ctx: &C, body: types::Propose) -> Result<u64, Error> {
+        <C::Runtime as Runtime>::Core::use_tx_gas(GAS_COST_CALL_PROPOSE)?;
+
+        let submitter = Self::ensure_signer()?;
+        validate_action(&body.action)?;
+
+        if CurrentState::with_env(|env| env.is_check_only()) {
+            return Ok(0);
+        }
+
+        let mut proposal = types::Proposal {
+            id: state::next_proposal_id(),
+            submitter,
+            action: body.action,
+            approvals: vec![submitter],
+            submitted_at: ctx.now(),
+            executable_at: 0,
+            executed: false,
+        };
+        Self::update_timelock(ctx, &mut proposal);
+        state::set_proposal(&proposal);
+
+        CurrentState::with(|state| {
+            state.emit_event(Event::ProposalSubmitted {
+                id: proposal.id,
+                submitter,
+            })
+        });
+
+        Ok(proposal.id)
+    }
+
+    #[handler(call = "governance.Approve")]
+    fn tx_approve<C: Context>(ctx: &C, body: types::Approve) -> Result<(), Error> {
+        <C::Runtime as Runtime>::Core::use_tx_gas(GAS_COST_CALL_APPROVE)?;
+
+        let signer = Self::ensure_signer()?;
+        let mut proposal = Self::get_pending(body.id)?;
+        if proposal.approvals.contains(&signer) {
+            return Err(Error::InvalidArgument);
+        }
+
+        if CurrentState::with_env(|env| env.is_check_only()) {
+            return Ok(());
+        }
+
+        proposal.approvals.push(signer);
+        Self::update_timelock(ctx, &mut proposal);
+        state::set_proposal(&proposal);
+
+        CurrentState::with(|state| {
+            state.emit_event(Event::ProposalApproved {
+                id: proposal.id,
+                signer,
+            })
+        });
+
+        Ok(())
+    }
+
+    #[handler(call = "governance.Execute")]
+    fn tx_execute<C: Context>(ctx: &C, body: types::Execute) -> Result<(), Error> {
+        <C::Runtime as Runtime>::Core::use_tx_gas(GAS_COST_CALL_EXECUTE)?;
+
+        Self::ensure_signer()?;
+        let mut proposal = Self::get_pending(body.id)?;
+        if !Self::params().is_approved(&proposal.approvals) || proposal.executable_at == 0 {
+            return Err(Error::ProposalNotApproved);
+        }
+        if ctx.now() < proposal.executable_at {
+            return Err(Error::ProposalTimelocked(proposal.executable_at));
+        }
+        // Parameters may have been changed since the proposal was submitted.
+        validate_action(&proposal.action)?;
+
+        if CurrentState::with_env(|env| env.is_check_only()) {
+            return Ok(());
+        }
+
+        let module = proposal.action.module();
+        let changes = current_params(module).and_then(|current| {
+            changed_fields(
+                cbor::to_value(current),
+                cbor::to_value(proposal.action.clone()),
+            )
+        });
+        apply_action(proposal.action.clone());
+        if let Some(changes) = changes {
+            state::add_override(module, changes);
+        }
+        proposal.executed = true;
+        state::set_proposal(&proposal);
+
+        CurrentState::with(|state| state.emit_event(Event::ProposalExecuted { id: proposal.id }));
+
+        Ok(())
+    }
+
+    #[handler(call = "governance.Cancel")]
+    fn tx_cancel<C: Context>(_ctx: &C, body: types::Cancel) -> Result<(), Error> {
+        <C::Runtime as Runtime>::Core::use_tx_gas(GAS_COST_CALL_CANCEL)?;
+
+        let signer = Self::ensure_signer()?;
+        let proposal = Self::get_pending(body.id)?;
+        if proposal.submitter != signer {
+            return Err(Error::Forbidden);
+        }
+
+        if CurrentState::with_env(|env| env.is_check_only()) {
+            return Ok(());
+        }
+
+        state::remove_proposal(proposal.id);
+
+        CurrentState::with(|state| state.emit_event(Event::ProposalCancelled { id: proposal.id }));
+
+        Ok(())
+    }
+
+    #[handler(query = "governance.Proposal")]
+    fn query_proposal<C: Context>(
+        _ctx: &C,
+        args: types::ProposalQuery,
+    ) -> Result<types::Proposal, Error> {
+        state::get_proposal(args.id).ok_or(Error::ProposalNotFound)
+    }
+
+    #[handler(query = "governance.Parameters")]
+    fn query_parameters<C: Context>(_ctx: &C, _args: ()) -> Result<Parameters, Error> {
+        Ok(Self::params())
+    }
+}
+
+impl Module {
+    /// Make sure the caller is one of the signers and return its address.
+    fn ensure_signer() -> Result<Address, Error> {
+        let caller = CurrentState::with_env(|env| env.tx_caller_address());
+        if !Self::params().is_signer(&caller) {
+            return Err(Error::Forbidden);
+        }
+        Ok(caller)
+    }
+
+    /// Proposal with the given identifier that has not been executed yet.
+    fn get_pending(id: u64) -> Result<types::Proposal, Error> {
+        let proposal = state::get_proposal(id).ok_or(Error::ProposalNotFound)?;
+        if proposal.executed {
+            return Err(Error::ProposalExecuted);
+        }
+        Ok(proposal)
+    }
+
+    /// Start the timelock once the proposal reaches the threshold.
+    fn update_timelock<C: Context>(ctx: &C, proposal: &mut types::Proposal) {
+        if proposal.executable_at == 0 && Self::params().is_approved(&proposal.approvals) {
+            proposal.executable_at = ctx.now().saturating_add(Self::params().timelock).max(1);
+        }
+    }
+}
+
+impl module::TransactionHandler for Module {}
+
+impl module::BlockHandler for Module {}
+
+impl module::InvariantHandler for Module {}
+
+/// Make sure the parameters set by the given action are valid.
+fn validate_action(action: &types::Action) -> Result<(), Error> {
+    match action {
+        types::Action::Core(params) => validate_params(params),
+        types::Action::Accounts(params) => validate_params(params),
+        types::Action::Consensus(params) => validate_params(params),
+        types::Action::ConsensusAccounts(params) => validate_params(params),
+        types::Action::Rewards(params) => validate_params(params),
+        types::Action::Rofl(params) => validate_params(params),
+        types::Action::Evm(params) => validate_params(params),
+        types::Action::RoflMarket(params) => validate_params(params),
+        types::Action::Governance(params) => validate_params(params),
+        types::Action::Pause(params) => validate_params(params),
+        types::Action::GasPolicy(params) => validate_params(params),
+        types::Action::Lanes(params) => validate_params(params),
+        types::Action::Endorsement(params) => validate_params(params),
+    }
+}
+
+fn validate_params<P: module::Parameters>(params: &P) -> Result<(), Error>
+where
+    P::Error: std::fmt::Debug,
+{
+    params
+        .validate_basic()
+        .map_err(|err| Error::InvalidParameters(format!("{err:?}")))
+}
+
+/// Reapply the fields changed by executed parameter updates of each module.
+///
+/// Used by state migrations after resetting parameters from genesis. Changes that no longer
+/// decode into the module's parameters (e.g. because a field changed its type) are dropped.
+pub fn reapply_overrides() {
+    for (module, changes) in state::get_overrides() {
+        let Some(current) = current_params(&module) else {
+            continue;
+        };
+        let params = remove_undefined(apply_changes(cbor::to_value(current), changes));
+        if let Ok(action) = cbor::from_value(params) {
+            apply_action(action);
+        }
+    }
+}
+
+/// Current parameters of the given module.
+fn current_params(module: &str) -> Option<types::Action> {
+    use types::Action;
+
+    let action = match module {
+        "core" => Action::Core(modules::core::Module::<Config>::params()),
+        "accounts" => Action::Accounts(modules::accounts::Module::params()),
+        "consensus" => Action::Consensus(modules::consensus::Module::params()),
+        "consensus_accounts" => Action::ConsensusAccounts(modules::consensus_accounts::Module::<
+            modules::consensus::Module,
+        >::params()),
+        "rewards" => Action::Rewards(modules::rewards::Module::params()),
+        "rofl" => Action::Rofl(modules::rofl::Module::<Config>::params()),
+        "evm" => Action::Evm(module_evm::Module::<Config>::params()),
+        "roflmarket" => Action::RoflMarket(module_rofl_market::Module::<Config>::params()),
+        MODULE_NAME => Action::Governance(Module::params()),
+        pause::MODULE_NAME => Action::Pause(pause::Module::params()),
+        gas_policy::MODULE_NAME => Action::GasPolicy(gas_policy::Module::params()),
+        lanes::MODULE_NAME => Action::Lanes(lanes::Module::params()),
+        endorsement::MODULE_NAME => Action::Endorsement(endorsement::Module::params()),
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Fields of `new` that differ from `old`, descending into nested maps.
+///
+/// Fields missing from `new` (e.g. optional fields reset to their default) are recorded as
+/// undefined. Returns `None` when nothing changed.
+fn changed_fields(old: cbor::Value, new: cbor::Value) -> Option<cbor::Value> {
+    let (cbor::Value::Map(old), cbor::Value::Map(new)) = (&old, &new) else {
+        return (old != new).then_some(new);
+    };
+
+    let mut changes = Vec::new();
+    for (key, value) in new {
+        match old.iter().find(|(k, _)| k == key) {
+            Some((_, old_value)) => {
+                if let Some(change) = changed_fields(old_value.clone(), value.clone()) {
+                    changes.push((key.clone(), change));
+                }
+            }
+            None => changes.push((key.clone(), value.clone())),
+        }
+    }
+    for (key, _) in old {
+        if !new.iter().any(|(k, _)| k == key) {
+            changes.push((key.clone(), undefined()));
+        }
+    }
+    (!changes.is_empty()).then_some(cbor::Value::Map(changes))
+}
+
+/// Apply the given changes on top of `base`, descending into nested maps.
+///
+/// Undefined fields are kept as such so that changes can be combined. Use [`remove_undefined`]
+/// before decoding the result.
+fn apply_changes(base: cbor::Value, changes: cbor::Value) -> cbor::Value {
+    match (base, changes) {
+        (cbor::Value::Map(mut base), cbor::Value::Map(changes)) => {
+            for (key, change) in changes {
+                match base.iter().position(|(k, _)| *k == key) {
+                    Some(i) => base[i].1 = apply_changes(base[i].1.clone(), change),
+                    None => base.push((key, change)),
+                }
+            }
+            cbor::Value::Map(base)
+        }
+        (_, changes) => changes,
+    }
+}
+
+/// Remove the fields marked as undefined from the given value.
+fn remove_undefined(value: cbor::Value) -> cbor::Value {
+    match value {
+        cbor::Value::Map(map) => cbor::Value::Map(
+            map.into_iter()
+                .filter(|(_, value)| *value != undefined())
+                .map(|(key, value)| (key, remove_undefined(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn undefined() -> cbor::Value {
+    cbor::Value::Simple(cbor::SimpleValue::Undefined)
+}
+
+/// Apply the parameter update performed by the given action.
+fn apply_action(action: types::Action) {
+    match action {
+        types::Action::Core(params) => modules::core::Module::<Config>::set_params(params),
+        types::Action::Accounts(params) => modules::accounts::Module::set_params(params),
+        types::Action::Consensus(params) => modules::consensus::Module::set_params(params),
+        types::Action::ConsensusAccounts(params) => {
+            modules::consensus_accounts::Module::<modules::consensus::Module>::set_params(params)
+        }
+        types::Action::Rewards(params) => modules::rewards::Module::set_params(params),
+        types::Action::Rofl(params) => modules::rofl::Module::<Config>::set_params(params),
+        types::Action::Evm(params) => module_evm::Module::<Config>::set_params(params),
+        types::Action::RoflMarket(params) => {
+            module_rofl_market::Module::<Config>::set_params(params)
+        }
+        types::Action::Governance(params) => Module::set_params(params),
+        types::Action::Pause(params) => pause::Module::set_params(params),
+        types::Action::GasPolicy(params) => gas_policy::Module::set_params(params),
+        types::Action::Lanes(params) => lanes::Module::set_params(params),
+        types::Action::Endorsement(params) => endorsement::Module::set_params(params),
+    }
+}
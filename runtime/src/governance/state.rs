@@ -0,0 +1,79 @@
+//! Governance module state.
+use std::collections::BTreeMap;
+
+use oasis_runtime_sdk::{state::CurrentState, storage};
+
+use super::{apply_changes, types::Proposal, MODULE_NAME};
+
+/// Identifier of the next proposal.
+const NEXT_PROPOSAL_ID: &[u8] = &[0x01];
+/// Map of proposal identifiers to proposals.
+const PROPOSALS: &[u8] = &[0x02];
+/// Map of module names to the parameter fields changed by executed updates of that module.
+const OVERRIDES: &[u8] = &[0x03];
+
+/// Allocate the identifier of a new proposal.
+pub fn next_proposal_id() -> u64 {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(store, &MODULE_NAME));
+        let id: u64 = store.get(NEXT_PROPOSAL_ID).unwrap_or_default();
+        store.insert(NEXT_PROPOSAL_ID, id + 1);
+        id
+    })
+}
+
+/// Proposal with the given identifier.
+pub fn get_proposal(id: u64) -> Option<Proposal> {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &PROPOSALS,
+        ));
+        store.get(id.to_be_bytes())
+    })
+}
+
+/// Insert or update the given proposal.
+pub fn set_proposal(proposal: &Proposal) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &PROPOSALS,
+        ));
+        store.insert(proposal.id.to_be_bytes(), proposal.clone());
+    })
+}
+
+/// Remove the proposal with the given identifier.
+pub fn remove_proposal(id: u64) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &PROPOSALS,
+        ));
+        store.remove(id.to_be_bytes());
+    })
+}
+
+/// Parameter fields changed by executed updates, keyed by module name.
+pub fn get_overrides() -> BTreeMap<String, cbor::Value> {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(store, &MODULE_NAME));
+        store.get(OVERRIDES).unwrap_or_default()
+    })
+}
+
+/// Record the given parameter fields changed by an executed update, on top of the fields changed
+/// by earlier updates of the same module.
+pub fn add_override(module: &str, changes: cbor::Value) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(store, &MODULE_NAME));
+        let mut overrides: BTreeMap<String, cbor::Value> = store.get(OVERRIDES).unwrap_or_default();
+        let changes = match overrides.remove(module) {
+            Some(earlier) => apply_changes(earlier, changes),
+            None => changes,
+        };
+        overrides.insert(module.to_string(), changes);
+        store.insert(OVERRIDES, overrides);
+    })
+}
@@ -0,0 +1,114 @@
+//! Governance module types.
+use oasis_runtime_sdk::{modules, types::address::Address};
+
+use super::Parameters;
+use crate::{endorsement, gas_policy, lanes, pause};
+
+/// Parameter update performed by a proposal.
+///
+/// Each update replaces all parameters of the given module.
+#[derive(Clone, Debug, cbor::Encode, cbor::Decode)]
+pub enum Action {
+    #[cbor(rename = "core")]
+    Core(modules::core::Parameters),
+    #[cbor(rename = "accounts")]
+    Accounts(modules::accounts::Parameters),
+    #[cbor(rename = "consensus")]
+    Consensus(modules::consensus::Parameters),
+    #[cbor(rename = "consensus_accounts")]
+    ConsensusAccounts(modules::consensus_accounts::Parameters),
+    #[cbor(rename = "rewards")]
+    Rewards(modules::rewards::Parameters),
+    #[cbor(rename = "rofl")]
+    Rofl(modules::rofl::Parameters),
+    #[cbor(rename = "evm")]
+    Evm(module_evm::Parameters),
+    #[cbor(rename = "roflmarket")]
+    RoflMarket(module_rofl_market::Parameters),
+    #[cbor(rename = "governance")]
+    Governance(Parameters),
+    #[cbor(rename = "pause")]
+    Pause(pause::Parameters),
+    #[cbor(rename = "gas_policy")]
+    GasPolicy(gas_policy::Parameters),
+    #[cbor(rename = "lanes")]
+    Lanes(lanes::Parameters),
+    #[cbor(rename = "endorsement")]
+    Endorsement(endorsement::Parameters),
+}
+
+impl Action {
+    /// Name of the module whose parameters are updated.
+    pub fn module(&self) -> &'static str {
+        match self {
+            Self::Core(_) => "core",
+            Self::Accounts(_) => "accounts",
+            Self::Consensus(_) => "consensus",
+            Self::ConsensusAccounts(_) => "consensus_accounts",
+            Self::Rewards(_) => "rewards",
+            Self::Rofl(_) => "rofl",
+            Self::Evm(_) => "evm",
+            Self::RoflMarket(_) => "roflmarket",
+            Self::Governance(_) => super::MODULE_NAME,
+            Self::Pause(_) => pause::MODULE_NAME,
+            Self::GasPolicy(_) => gas_policy::MODULE_NAME,
+            Self::Lanes(_) => lanes::MODULE_NAME,
+            Self::Endorsement(_) => endorsement::MODULE_NAME,
+        }
+    }
+}
+
+/// A parameter update proposal.
+#[derive(Clone, Debug, cbor::Encode, cbor::Decode)]
+pub struct Proposal {
+    /// Unique identifier of the proposal.
+    pub id: u64,
+    /// Signer that submitted the proposal.
+    pub submitter: Address,
+    /// Parameter update performed by the proposal.
+    pub action: Action,
+    /// Signers that approved the proposal, including the submitter.
+    pub approvals: Vec<Address>,
+    /// Time (in seconds) at which the proposal was submitted.
+    pub submitted_at: u64,
+    /// Time (in seconds) after which the proposal can be executed. Zero until the proposal has
+    /// been approved by the threshold of signers.
+    pub executable_at: u64,
+    /// Whether the proposal has been executed.
+    pub executed: bool,
+}
+
+/// Propose call.
+#[derive(Clone, Debug, cbor::Encode, cbor::Decode)]
+pub struct Propose {
+    /// Parameter update to perform.
+    pub action: Action,
+}
+
+/// Approve call.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Approve {
+    /// Identifier of the proposal to approve.
+    pub id: u64,
+}
+
+/// Execute call.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Execute {
+    /// Identifier of the proposal to execute.
+    pub id: u64,
+}
+
+/// Cancel call.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Cancel {
+    /// Identifier of the proposal to cancel.
+    pub id: u64,
+}
+
+/// Proposal query.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct ProposalQuery {
+    /// Identifier of the proposal.
+    pub id: u64,
+}
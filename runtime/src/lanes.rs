@@ -0,0 +1,311 @@
+//! Priority lanes module.
+//!
+//! All transactions compete for the same `max_batch_gas` of the core module, so during congestion
+//! (e.g. a burst of EVM traffic) operations that must be included in a timely manner, like ROFL
+//! instance re-registrations (`rofl.Register`) and `consensus.Deposit`, can get crowded out. Each
+//! lane reserves a share of `max_batch_gas` for transactions calling one of its methods.
+//!
+//! Transactions are admitted while a batch is being scheduled based on their gas limit. Lane
+//! transactions first use the reservation of their lane and then the shared capacity, all other
+//! transactions can only use the shared capacity. Transactions that do not fit are skipped by the
+//! scheduler and remain queued for a later batch. Executing an already proposed batch never fails
+//! because of the reservations, its transactions are only accounted for.
+use oasis_runtime_sdk::{
+    context::Context,
+    handler, migration,
+    module::{self, Module as _, Parameters as _},
+    modules::{self, core::API as _},
+    sdk_derive,
+    state::CurrentState,
+    types::transaction::Transaction,
+};
+use thiserror::Error;
+
+use crate::Config;
+
+/// Unique module name.
+pub const MODULE_NAME: &str = "lanes";
+
+/// Denominator of the reserved shares of `max_batch_gas`.
+pub const SHARE_DENOMINATOR: u16 = 1_000;
+
+/// Block-level key of the batch gas usage.
+const USAGE_KEY: &str = "lanes.Usage";
+
+/// Errors emitted by the lanes module.
+#[derive(Error, Debug, oasis_runtime_sdk::Error)]
+pub enum Error {
+    #[error("core: {0}")]
+    #[sdk_error(transparent)]
+    Core(#[from] modules::core::Error),
+}
+
+/// A priority lane.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Lane {
+    /// Methods whose transactions may use the reservation.
+    pub methods: Vec<String>,
+    /// Reserved share of `max_batch_gas`, in thousandths.
+    pub reserved: u16,
+}
+
+/// Parameters for the lanes module.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Parameters {
+    /// Priority lanes.
+    pub lanes: Vec<Lane>,
+}
+
+/// Errors emitted during parameter validation.
+#[derive(Error, Debug)]
+pub enum ParameterValidationError {
+    #[error("lane without methods")]
+    NoMethods,
+    #[error("method {0} is in multiple lanes")]
+    DuplicateMethod(String),
+    #[error("reserved shares exceed max_batch_gas")]
+    ReservedTooHigh,
+}
+
+impl module::Parameters for Parameters {
+    type Error = ParameterValidationError;
+
+    fn validate_basic(&self) -> Result<(), Self::Error> {
+        let mut methods = Vec::new();
+        for lane in &self.lanes {
+            if lane.methods.is_empty() {
+                return Err(ParameterValidationError::NoMethods);
+            }
+            for method in &lane.methods {
+                if methods.contains(&method) {
+                    return Err(ParameterValidationError::DuplicateMethod(method.clone()));
+                }
+                methods.push(method);
+            }
+        }
+        let reserved: u32 = self.lanes.iter().map(|lane| u32::from(lane.reserved)).sum();
+        if reserved > u32::from(SHARE_DENOMINATOR) {
+            return Err(ParameterValidationError::ReservedTooHigh);
+        }
+        Ok(())
+    }
+}
+
+impl Parameters {
+    /// Index of the lane of the given method.
+    fn lane(&self, method: &str) -> Option<usize> {
+        self.lanes
+            .iter()
+            .position(|lane| lane.methods.iter().any(|m| m == method))
+    }
+}
+
+/// Genesis state for the lanes module.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Genesis {
+    pub parameters: Parameters,
+}
+
+/// Batch gas admitted so far in the current block.
+#[derive(Clone, Debug, Default)]
+struct Usage {
+    /// Gas admitted against the reservation of each lane.
+    lanes: Vec<u64>,
+    /// Gas admitted against the shared capacity.
+    shared: u64,
+}
+
+impl Usage {
+    /// Admit a transaction with the given gas limit, returning whether it fits.
+    ///
+    /// The `reserved` slice holds the reserved gas of each lane, the rest of `max_batch_gas` is
+    /// shared by all transactions.
+    fn admit(
+        &mut self,
+        reserved: &[u64],
+        max_batch_gas: u64,
+        lane: Option<usize>,
+        gas: u64,
+    ) -> bool {
+        self.lanes.resize(reserved.len(), 0);
+        if let Some(lane) = lane {
+            let used = self.lanes[lane].saturating_add(gas);
+            if used <= reserved[lane] {
+                self.lanes[lane] = used;
+                return true;
+            }
+        }
+
+        let shared_capacity = max_batch_gas.saturating_sub(reserved.iter().sum());
+        let used = self.shared.saturating_add(gas);
+        if used > shared_capacity {
+            return false;
+        }
+        self.shared = used;
+        true
+    }
+
+    /// Account for a transaction included in the batch, using the shared capacity beyond the
+    /// limits if it does not fit.
+    fn record(&mut self, reserved: &[u64], max_batch_gas: u64, lane: Option<usize>, gas: u64) {
+        if !self.admit(reserved, max_batch_gas, lane, gas) {
+            self.shared = self.shared.saturating_add(gas);
+        }
+    }
+}
+
+/// Reserved gas of a lane with the given share of `max_batch_gas`.
+fn share(max_batch_gas: u64, reserved: u16) -> u64 {
+    let share = u128::from(max_batch_gas) * u128::from(reserved) / u128::from(SHARE_DENOMINATOR);
+    share.try_into().unwrap_or(u64::MAX)
+}
+
+/// The lanes module.
+pub struct Module;
+
+#[sdk_derive(Module)]
+impl Module {
+    const NAME: &'static str = MODULE_NAME;
+    const VERSION: u32 = 1;
+    type Error = Error;
+    type Event = ();
+    type Parameters = Parameters;
+    type Genesis = Genesis;
+
+    #[migration(init)]
+    fn init(genesis: Genesis) {
+        genesis
+            .parameters
+            .validate_basic()
+            .expect("invalid genesis parameters");
+        Self::set_params(genesis.parameters);
+    }
+
+    #[handler(query = "lanes.Parameters")]
+    fn query_parameters<C: Context>(_ctx: &C, _args: ()) -> Result<Parameters, Error> {
+        Ok(Self::params())
+    }
+}
+
+impl module::TransactionHandler for Module {
+    fn authenticate_tx<C: Context>(
+        _ctx: &C,
+        tx: &Transaction,
+    ) -> Result<module::AuthDecision, modules::core::Error> {
+        // Only batches are subject to the reservations, checks and simulations are not.
+        let (pre_schedule, execute) =
+            CurrentState::with_env(|env| (env.is_pre_schedule(), env.is_execute()));
+        if !pre_schedule && !execute {
+            return Ok(module::AuthDecision::Continue);
+        }
+        let params = Self::params();
+        if params.lanes.is_empty() {
+            return Ok(module::AuthDecision::Continue);
+        }
+
+        let max_batch_gas = modules::core::Module::<Config>::params().max_batch_gas;
+        let reserved: Vec<u64> = params
+            .lanes
+            .iter()
+            .map(|lane| share(max_batch_gas, lane.reserved))
+            .collect();
+        let lane = params.lane(&tx.call.method);
+        let gas = tx.auth_info.fee.gas;
+        CurrentState::with(|state| {
+            let usage = state.block_value::<Usage>(USAGE_KEY).or_default();
+            if execute {
+                // Transactions are accounted for when they are executed, both while scheduling
+                // and when executing a proposed batch, which must not fail.
+                usage.record(&reserved, max_batch_gas, lane, gas);
+                return Ok(());
+            }
+            if usage.clone().admit(&reserved, max_batch_gas, lane, gas) {
+                return Ok(());
+            }
+            // The scheduler skips transactions with future nonces without rejecting them, as it
+            // must for a sender's later transactions, so they remain queued and can be included
+            // once there is capacity again (see `test_lanes_defer_transactions`).
+            Err(modules::core::Error::FutureNonce)
+        })?;
+        Ok(module::AuthDecision::Continue)
+    }
+}
+
+impl module::BlockHandler for Module {}
+
+impl module::InvariantHandler for Module {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_admit() {
+        // A lane reserving 10% and another reserving 20% of 1_000 gas.
+        let reserved = [share(1_000, 100), share(1_000, 200)];
+        assert_eq!(reserved, [100, 200]);
+        let mut usage = Usage::default();
+
+        // Other transactions are limited to the shared capacity.
+        assert!(usage.admit(&reserved, 1_000, None, 600));
+        assert!(!usage.admit(&reserved, 1_000, None, 200));
+        assert!(usage.admit(&reserved, 1_000, None, 100));
+
+        // Lane transactions use their reservation and then the shared capacity.
+        assert!(usage.admit(&reserved, 1_000, Some(0), 100));
+        assert!(!usage.admit(&reserved, 1_000, Some(0), 1));
+        assert!(usage.admit(&reserved, 1_000, Some(1), 150));
+        assert!(usage.admit(&reserved, 1_000, Some(1), 50));
+        assert!(!usage.admit(&reserved, 1_000, Some(1), 1));
+        assert_eq!(usage.lanes, [100, 200]);
+        assert_eq!(usage.shared, 700);
+
+        let mut usage = Usage::default();
+        assert!(usage.admit(&reserved, 1_000, Some(0), 50));
+        assert!(usage.admit(&reserved, 1_000, Some(0), 80));
+        assert_eq!(usage.lanes, [50, 0]);
+        assert_eq!(usage.shared, 80);
+
+        // Executed transactions are always accounted for.
+        usage.record(&reserved, 1_000, None, 700);
+        usage.record(&reserved, 1_000, Some(0), 100);
+        assert_eq!(usage.lanes, [50, 0]);
+        assert_eq!(usage.shared, 880);
+        assert!(!usage.admit(&reserved, 1_000, None, 1));
+        assert!(usage.admit(&reserved, 1_000, Some(0), 50));
+    }
+
+    #[test]
+    fn test_validate_basic() {
+        let lane = |methods: &[&str], reserved| Lane {
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            reserved,
+        };
+        let valid = Parameters {
+            lanes: vec![
+                lane(&["rofl.Register"], 100),
+                lane(&["consensus.Deposit"], 900),
+            ],
+        };
+        assert!(valid.validate_basic().is_ok());
+        assert_eq!(valid.lane("consensus.Deposit"), Some(1));
+        assert_eq!(valid.lane("evm.Call"), None);
+
+        let invalid = [
+            vec![lane(&[], 100)],
+            vec![lane(&["rofl.Register", "rofl.Register"], 100)],
+            vec![lane(&["rofl.Register"], 100), lane(&["rofl.Register"], 100)],
+            vec![
+                lane(&["rofl.Register"], 600),
+                lane(&["consensus.Deposit"], 401),
+            ],
+        ];
+        for lanes in invalid {
+            let params = Parameters { lanes };
+            assert!(
+                params.validate_basic().is_err(),
+                "{params:?} should be rejected"
+            );
+        }
+    }
+}
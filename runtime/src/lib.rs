@@ -1,6 +1,20 @@
 //! The Sapphire ParaTime.
 #![deny(rust_2018_idioms, single_use_lifetimes, unreachable_pub)]
 
+pub mod endorsement;
+pub mod gas_policy;
+pub mod genesis;
+pub mod governance;
+pub mod lanes;
+pub mod migrations;
+pub mod network;
+pub mod pause;
+pub mod paymaster;
+pub mod precompiles;
+pub mod rewards;
+#[cfg(test)]
+mod test;
+
 use std::collections::{BTreeMap, BTreeSet};
 
 #[cfg(not(feature = "debug-mock-sgx"))]
@@ -12,55 +26,31 @@ use oasis_runtime_sdk::{
     keymanager::TrustedSigners,
     modules,
     types::token::{BaseUnits, Denomination},
-    Module, Version,
+    Version,
 };
 use once_cell::unsync::Lazy;
 
+use crate::network::NETWORK;
+
 /// Configuration of the various modules.
 pub struct Config;
 
-/// Determine whether the build is for Testnet.
-///
-/// If the crate version has a pre-release component (e.g. 3.0.0-alpha) then the build is classified
-/// as Testnet. If there is no such component (e.g. 5.0.0) then it is classified as Mainnet.
-const fn is_testnet() -> bool {
-    !env!("CARGO_PKG_VERSION_PRE").is_empty()
-}
-
-/// Determine EVM chain ID to use depending on whether the build is for Localnet, Testnet or Mainnet.
-const fn chain_id() -> u64 {
-    if option_env!("OASIS_UNSAFE_USE_LOCALNET_CHAINID").is_some() {
-        // Localnet.
-        0x5afd
-    } else if is_testnet() {
-        // Testnet.
-        0x5aff
-    } else {
-        // Mainnet.
-        0x5afe
-    }
-}
-
-/// Determine state version on weather the build is for Testnet or Mainnet.
-const fn state_version() -> u32 {
-    if is_testnet() {
-        // Testnet.
-        11
-    } else {
-        // Mainnet.
-        7
-    }
-}
-
 impl modules::core::Config for Config {
     /// Default local minimum gas price configuration that is used in case no overrides are set in
     /// local per-node configuration.
     const DEFAULT_LOCAL_MIN_GAS_PRICE: Lazy<BTreeMap<Denomination, u128>> =
         Lazy::new(|| [(Denomination::NATIVE, 100_000_000_000)].into());
 
-    /// Methods which are exempt from minimum gas price requirements.
-    const MIN_GAS_PRICE_EXEMPT_METHODS: Lazy<BTreeSet<&'static str>> =
-        Lazy::new(|| ["consensus.Deposit"].into());
+    /// Methods which are exempt from minimum gas price requirements. Apart from
+    /// [`gas_policy::FREE_METHODS`], these are the methods with a discounted minimum gas price in
+    /// genesis, which is enforced by [`gas_policy::Module`] instead.
+    const MIN_GAS_PRICE_EXEMPT_METHODS: Lazy<BTreeSet<&'static str>> = Lazy::new(|| {
+        gas_policy::FREE_METHODS
+            .iter()
+            .chain(NETWORK.discounted_methods())
+            .copied()
+            .collect()
+    });
 
     /// Estimated gas amount to be added to failed transaction simulations for selected methods.
     const ESTIMATE_GAS_EXTRA_FAIL: Lazy<BTreeMap<&'static str, u64>> =
@@ -68,13 +58,17 @@ impl modules::core::Config for Config {
 }
 
 impl module_evm::Config for Config {
-    type AdditionalPrecompileSet = ();
+    type AdditionalPrecompileSet = precompiles::Precompiles;
 
-    const CHAIN_ID: u64 = chain_id();
+    const CHAIN_ID: u64 = NETWORK.chain_id();
 
     const TOKEN_DENOMINATION: Denomination = Denomination::NATIVE;
 
     const CONFIDENTIAL: bool = true;
+
+    fn additional_precompiles() -> Option<Self::AdditionalPrecompileSet> {
+        Some(precompiles::Precompiles)
+    }
 }
 
 impl modules::rofl::Config for Config {
@@ -103,8 +97,10 @@ impl modules::rofl::Config for Config {
     const STAKE_APP_CREATE: BaseUnits =
         BaseUnits::new(100_000_000_000_000_000_000, Denomination::NATIVE);
 
-    /// Endorsement policy evaluator.
+    /// Endorsement policy evaluator. The Sapphire requirements come first so that they can reject
+    /// endorsements allowed by the application policy.
     type EndorsementPolicyEvaluator = (
+        endorsement::PolicyEvaluator,
         modules::rofl::policy::BasicEndorsementPolicyEvaluator,
         module_rofl_market::policy::ProviderEndorsementPolicyEvaluator,
     );
@@ -182,8 +178,8 @@ impl sdk::Runtime for Runtime {
     /// Version of the runtime.
     const VERSION: Version = sdk::version_from_cargo!();
     /// Current version of the global state (e.g. parameters). Any parameter updates should bump
-    /// this version in order for the migrations to be executed.
-    const STATE_VERSION: u32 = state_version();
+    /// this version and register a step in [`migrations`] in order for them to be executed.
+    const STATE_VERSION: u32 = NETWORK.state_version();
 
     /// Schedule control configuration.
     const SCHEDULE_CONTROL: config::ScheduleControl = config::ScheduleControl {
@@ -199,7 +195,7 @@ impl sdk::Runtime for Runtime {
 
     type Core = modules::core::Module<Config>;
     type Accounts = modules::accounts::Module;
-    type FeeProxy = modules::rofl::Module<Config>;
+    type FeeProxy = paymaster::FeeProxy;
 
     #[allow(clippy::type_complexity)]
     type Modules = (
@@ -219,6 +215,16 @@ impl sdk::Runtime for Runtime {
         module_evm::Module<Config>,
         // ROFL market.
         module_rofl_market::Module<Config>,
+        // Governance.
+        governance::Module,
+        // Emergency pause.
+        pause::Module,
+        // Minimum gas price policy.
+        gas_policy::Module,
+        // Priority lanes.
+        lanes::Module,
+        // ROFL endorsement policy.
+        endorsement::Module,
     );
 
     #[cfg(feature = "debug-mock-sgx")]
@@ -239,145 +245,17 @@ impl sdk::Runtime for Runtime {
 
     #[cfg(target_env = "sgx")]
     fn consensus_trust_root() -> Option<TrustRoot> {
-        if is_testnet() {
-            // Testnet.
-            Some(TrustRoot {
-                height: 30000000,
-                hash: "ac98ec3d9de10d890b011a70fda7e48d297f18809413cfec7b7ec89514a31977".into(),
-                runtime_id: "000000000000000000000000000000000000000000000000a6d1e3ebf60dff6c"
-                    .into(),
-                chain_context: "0b91b8e4e44b2003a7c5e23ddadb5e14ef5345c0ebcb3ddcae07fa2f244cab76"
-                    .to_string(),
-            })
-        } else {
-            // Mainnet.
-            Some(TrustRoot {
-                height: 28000000,
-                hash: "c54ec4bf4baf2094a5e06287b7157709da67e54b259ce378973a9a56b7e3eb76".into(),
-                runtime_id: "000000000000000000000000000000000000000000000000f80306c9858e7279"
-                    .into(),
-                chain_context: "bb3d748def55bdfb797a2ac53ee6ee141e54cd2ab2dc2375f4a0703a178e6e55"
-                    .to_string(),
-            })
-        }
+        NETWORK.trust_root()
     }
 
     fn genesis_state() -> <Self::Modules as sdk::module::MigrationHandler>::Genesis {
-        (
-            modules::core::Genesis {
-                parameters: modules::core::Parameters {
-                    min_gas_price: { BTreeMap::from([(Denomination::NATIVE, 100_000_000_000)]) },
-                    dynamic_min_gas_price: modules::core::DynamicMinGasPrice {
-                        enabled: true,
-                        target_block_gas_usage_percentage: 50,
-                        min_price_max_change_denominator: 8,
-                    },
-                    max_batch_gas: 15_000_000,
-                    max_tx_size: 128 * 1024,
-                    max_tx_signers: 3,
-                    max_multisig_signers: 8,
-                    gas_costs: modules::core::GasCosts {
-                        tx_byte: 1,
-                        storage_byte: 15,
-                        auth_signature: 1_000,
-                        auth_multisig_signer: 1_000,
-                        callformat_x25519_deoxysii: 10_000,
-                    },
-                },
-            },
-            modules::accounts::Genesis {
-                parameters: modules::accounts::Parameters {
-                    gas_costs: modules::accounts::GasCosts { tx_transfer: 1_000 },
-                    denomination_infos: {
-                        BTreeMap::from([(
-                            Denomination::NATIVE,
-                            modules::accounts::types::DenominationInfo {
-                                // Consistent with EVM ecosystem.
-                                decimals: 18,
-                            },
-                        )])
-                    },
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            modules::consensus::Genesis {
-                parameters: modules::consensus::Parameters {
-                    gas_costs: modules::consensus::GasCosts { round_root: 10_000 },
-                    // Consensus layer denomination is the native denomination of this runtime.
-                    consensus_denomination: Denomination::NATIVE,
-                    // Scale to 18 decimal places as this is what is expected in the EVM ecosystem.
-                    consensus_scaling_factor: 1_000_000_000,
-                    // Minimum delegation amount that matches the consensus layer.
-                    min_delegate_amount: 100_000_000_000,
-                },
-            },
-            modules::consensus_accounts::Genesis {
-                parameters: modules::consensus_accounts::Parameters {
-                    gas_costs: modules::consensus_accounts::GasCosts {
-                        tx_deposit: 60_000,
-                        tx_withdraw: 60_000,
-                        tx_delegate: 60_000,
-                        tx_undelegate: 120_000,
-
-                        store_receipt: 20_000,
-                        take_receipt: 15_000,
-
-                        delegation: 10_000,
-                        shares_to_tokens: 10_000,
-                    },
-                    disable_delegate: false,
-                    disable_undelegate: false,
-                    disable_deposit: false,
-                    disable_withdraw: false,
-                },
-            },
-            modules::rewards::Genesis {
-                parameters: modules::rewards::Parameters {
-                    schedule: modules::rewards::types::RewardSchedule {
-                        steps: vec![modules::rewards::types::RewardStep {
-                            until: 27_500,
-                            amount: BaseUnits::new(3_000_000_000_000_000_000, Denomination::NATIVE),
-                        }],
-                    },
-                    participation_threshold_numerator: 3,
-                    participation_threshold_denominator: 4,
-                },
-            },
-            modules::rofl::Genesis {
-                parameters: Default::default(),
-                apps: vec![],
-            },
-            module_evm::Genesis {
-                parameters: module_evm::Parameters {
-                    gas_costs: module_evm::GasCosts {},
-                },
-            },
-            module_rofl_market::Genesis::default(),
-        )
+        NETWORK.genesis_state()
     }
 
     fn migrate_state<C: sdk::Context>(_ctx: &C) {
-        // State migration from by copying over parameters from updated genesis state.
-        let genesis = Self::genesis_state();
-
-        // Core.
-        modules::core::Module::<Config>::set_params(genesis.0.parameters);
-        // Accounts.
-        modules::accounts::Module::set_params(genesis.1.parameters);
-        // Consensus layer interface.
-        modules::consensus::Module::set_params(genesis.2.parameters);
-        // Consensus layer accounts.
-        modules::consensus_accounts::Module::<modules::consensus::Module>::set_params(
-            genesis.3.parameters,
-        );
-        // Rewards.
-        modules::rewards::Module::set_params(genesis.4.parameters);
-        // ROFL.
-        modules::rofl::Module::<Config>::set_params(genesis.5.parameters);
-        // EVM.
-        module_evm::Module::<Config>::set_params(genesis.6.parameters);
-        // ROFL market.
-        module_rofl_market::Module::<Config>::set_params(genesis.7.parameters);
+        // The SDK only migrates from `STATE_VERSION - 1` and the step from that version is
+        // checked to exist at compile time, so this cannot fail.
+        migrations::migrate(migrations::recorded_state_version(), Self::STATE_VERSION)
+            .expect("state migration to the current version should be registered");
     }
 }
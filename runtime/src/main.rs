@@ -1,3 +1,13 @@
+#[cfg(not(target_env = "sgx"))]
+mod cli;
+
 fn main() {
+    // Tooling subcommands are only available outside of SGX. Without arguments the runtime is
+    // started.
+    #[cfg(not(target_env = "sgx"))]
+    if let Some(code) = cli::run(std::env::args().skip(1).collect()) {
+        std::process::exit(code);
+    }
+
     <sapphire_paratime::Runtime as oasis_runtime_sdk::Runtime>::start();
 }
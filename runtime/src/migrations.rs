@@ -0,0 +1,209 @@
+//! Runtime state migrations.
+//!
+//! Each change to the global state (e.g. parameter updates) is implemented as a migration step
+//! from one state version to the next.
+//!
+//! A node only ever executes a single step: the SDK only calls [`Runtime::migrate_state`] when
+//! the recorded state version is exactly `STATE_VERSION - 1` and aborts otherwise. A node cannot
+//! skip a release that bumps the state version, so each release must bump it by one (a single
+//! step can still combine several changes). Earlier steps stay registered so that the `migrate`
+//! dry-run can replay them in order on older state dumps. The step leading to the current state
+//! version of each public network is checked to exist at compile time.
+//!
+//! [`Runtime::STATE_VERSION`]: oasis_runtime_sdk::Runtime::STATE_VERSION
+//! [`Runtime::migrate_state`]: oasis_runtime_sdk::Runtime::migrate_state
+use oasis_runtime_sdk::{modules, state::CurrentState, storage, Module as _};
+use thiserror::Error;
+
+use crate::{
+    endorsement, gas_policy, governance, lanes,
+    network::{NetworkProfile, NETWORK},
+    pause, Config,
+};
+
+/// Errors emitted when planning a migration.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("missing state migration from version {0}")]
+    MissingStep(u32),
+    #[error("cannot migrate state from version {from} back to version {to}")]
+    Downgrade { from: u32, to: u32 },
+}
+
+/// A single step migrating the global state from one version to the next.
+///
+/// Steps should only change what they are meant to change (e.g. set a single new parameter)
+/// rather than reset whole modules, so that parameter updates executed through governance are
+/// kept.
+#[derive(Clone, Copy, Debug)]
+pub struct Migration {
+    /// State version that the step migrates from. After the step the state is at `from + 1`.
+    pub from: u32,
+    /// Short human-readable description of the step.
+    pub description: &'static str,
+    /// Function performing the migration.
+    pub migrate: fn(),
+}
+
+/// Migration steps of Mainnet.
+const MAINNET: &[Migration] = &[Migration {
+    from: 6,
+    description: "update module parameters from genesis",
+    migrate: update_parameters,
+}];
+
+/// Migration steps of Testnet.
+const TESTNET: &[Migration] = &[Migration {
+    from: 10,
+    description: "update module parameters from genesis",
+    migrate: update_parameters,
+}];
+
+/// Migration steps of the given network.
+///
+/// Localnet and custom networks share the steps of the public network whose state versions they
+/// follow (see [`NetworkProfile::state_lineage`]).
+pub fn registry(network: NetworkProfile) -> &'static [Migration] {
+    match network.state_lineage() {
+        NetworkProfile::Testnet => TESTNET,
+        _ => MAINNET,
+    }
+}
+
+// Fail compilation in case a public network cannot be migrated to its current state version.
+const _: () = {
+    assert!(
+        has_step(MAINNET, NetworkProfile::Mainnet.state_version() - 1),
+        "missing Mainnet state migration to the current state version"
+    );
+    assert!(
+        has_step(TESTNET, NetworkProfile::Testnet.state_version() - 1),
+        "missing Testnet state migration to the current state version"
+    );
+};
+
+/// Check whether there is a step from the given version in a const context.
+const fn has_step(steps: &[Migration], from: u32) -> bool {
+    let mut i = 0;
+    while i < steps.len() {
+        if steps[i].from == from {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Steps needed to migrate the global state from version `from` to version `to`, in order.
+pub fn plan(steps: &[Migration], from: u32, to: u32) -> Result<Vec<Migration>, Error> {
+    if from > to {
+        return Err(Error::Downgrade { from, to });
+    }
+    (from..to)
+        .map(|version| {
+            steps
+                .iter()
+                .find(|step| step.from == version)
+                .copied()
+                .ok_or(Error::MissingStep(version))
+        })
+        .collect()
+}
+
+/// Migrate the global state of this network from version `from` to version `to`.
+pub fn migrate(from: u32, to: u32) -> Result<(), Error> {
+    migrate_with(registry(NETWORK), from, to)
+}
+
+/// Migrate the global state from version `from` to version `to` using the given steps.
+///
+/// A `from` version of zero denotes state without a recorded version, whose parameters are all
+/// refreshed from genesis as the runtime did before migration steps were introduced. Otherwise
+/// nothing is changed unless all steps are available.
+pub fn migrate_with(steps: &[Migration], from: u32, to: u32) -> Result<(), Error> {
+    if from == 0 {
+        update_parameters();
+        return Ok(());
+    }
+    for step in plan(steps, from, to)? {
+        (step.migrate)();
+    }
+    Ok(())
+}
+
+/// Version of the global state as recorded in the core module metadata.
+///
+/// Returns zero in case no version is recorded.
+pub fn recorded_state_version() -> u32 {
+    try_recorded_state_version().unwrap_or_default()
+}
+
+/// Version of the global state as recorded in the core module metadata, if any.
+pub fn try_recorded_state_version() -> Option<u32> {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(
+            store,
+            &modules::core::MODULE_NAME,
+        ));
+        let meta: modules::core::types::Metadata = store.get(modules::core::state::METADATA)?;
+        meta.versions
+            .get(modules::core::types::VERSION_GLOBAL_KEY)
+            .copied()
+    })
+}
+
+/// Record the given global state version in the core module metadata.
+pub fn set_recorded_state_version(version: u32) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(
+            store,
+            &modules::core::MODULE_NAME,
+        ));
+        let mut meta: modules::core::types::Metadata = store
+            .get(modules::core::state::METADATA)
+            .unwrap_or_default();
+        meta.versions.insert(
+            modules::core::types::VERSION_GLOBAL_KEY.to_string(),
+            version,
+        );
+        store.insert(modules::core::state::METADATA, meta);
+    });
+}
+
+/// Update the parameters of all modules by copying them over from the genesis state.
+///
+/// Parameter fields changed through governance are reapplied afterwards.
+pub fn update_parameters() {
+    let genesis = NETWORK.genesis_state();
+
+    // Core.
+    modules::core::Module::<Config>::set_params(genesis.0.parameters);
+    // Accounts.
+    modules::accounts::Module::set_params(genesis.1.parameters);
+    // Consensus layer interface.
+    modules::consensus::Module::set_params(genesis.2.parameters);
+    // Consensus layer accounts.
+    modules::consensus_accounts::Module::<modules::consensus::Module>::set_params(
+        genesis.3.parameters,
+    );
+    // Rewards.
+    modules::rewards::Module::set_params(genesis.4.parameters);
+    // ROFL.
+    modules::rofl::Module::<Config>::set_params(genesis.5.parameters);
+    // EVM.
+    module_evm::Module::<Config>::set_params(genesis.6.parameters);
+    // ROFL market.
+    module_rofl_market::Module::<Config>::set_params(genesis.7.parameters);
+    // Governance.
+    governance::Module::set_params(genesis.8.parameters);
+    // Emergency pause.
+    pause::Module::set_params(genesis.9.parameters);
+    // Minimum gas price policy.
+    gas_policy::Module::set_params(genesis.10.parameters);
+    // Priority lanes.
+    lanes::Module::set_params(genesis.11.parameters);
+    // ROFL endorsement policy.
+    endorsement::Module::set_params(genesis.12.parameters);
+
+    governance::reapply_overrides();
+}
@@ -0,0 +1,531 @@
+//! Network profiles that the runtime can be built for.
+use oasis_runtime_sdk::core::consensus::verifier::TrustRoot;
+
+use crate::genesis::{self, Genesis};
+
+/// Network profile selected for this build.
+///
+/// The profile is resolved at compile time from the build environment (see
+/// [`NetworkProfile::from_build_env`]) and any inconsistency between the requested network and the
+/// crate version results in a compilation error.
+pub const NETWORK: NetworkProfile = NetworkProfile::from_build_env();
+
+/// EVM chain ID used on Mainnet.
+const MAINNET_CHAIN_ID: u64 = 0x5afe;
+/// EVM chain ID used on Testnet.
+const TESTNET_CHAIN_ID: u64 = 0x5aff;
+/// EVM chain ID used on Localnet.
+const LOCALNET_CHAIN_ID: u64 = 0x5afd;
+
+/// Current version of the global state on Mainnet.
+const MAINNET_STATE_VERSION: u32 = 7;
+/// Current version of the global state on Testnet.
+const TESTNET_STATE_VERSION: u32 = 11;
+
+/// Whether the crate version has a pre-release component (e.g. 3.0.0-alpha).
+const PRE_RELEASE: bool = !env!("CARGO_PKG_VERSION_PRE").is_empty();
+
+/// Build environment that the network profile is resolved from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BuildEnv<'a> {
+    /// Value of `OASIS_SAPPHIRE_NETWORK`, if set.
+    pub network: Option<&'a str>,
+    /// Value of `OASIS_SAPPHIRE_CHAIN_ID`, if set.
+    pub chain_id: Option<&'a str>,
+    /// Whether `OASIS_UNSAFE_USE_LOCALNET_CHAINID` is set.
+    pub localnet: bool,
+    /// Whether the crate version has a pre-release component.
+    pub pre_release: bool,
+}
+
+impl BuildEnv<'static> {
+    /// Environment of the current build.
+    pub const fn current() -> Self {
+        Self {
+            network: option_env!("OASIS_SAPPHIRE_NETWORK"),
+            chain_id: option_env!("OASIS_SAPPHIRE_CHAIN_ID"),
+            localnet: option_env!("OASIS_UNSAFE_USE_LOCALNET_CHAINID").is_some(),
+            pre_release: PRE_RELEASE,
+        }
+    }
+}
+
+/// A network that the runtime can be built for.
+///
+/// All network-dependent configuration (chain ID, state version, consensus trust root, genesis
+/// parameters, ...) is derived from the profile so that parameters of different networks can never
+/// be mixed in a single build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkProfile {
+    /// Oasis Mainnet.
+    Mainnet,
+    /// Oasis Testnet.
+    Testnet,
+    /// Local development network (e.g. sapphire-localnet).
+    Localnet,
+    /// Private network (e.g. a devnet) with its own EVM chain ID.
+    Custom {
+        /// EVM chain ID of the network.
+        chain_id: u64,
+    },
+}
+
+impl NetworkProfile {
+    /// Resolve the network profile from the build environment.
+    ///
+    /// The network can be explicitly requested by setting `OASIS_SAPPHIRE_NETWORK` to one of
+    /// `mainnet`, `testnet`, `localnet` or `custom`. Custom networks must also set
+    /// `OASIS_SAPPHIRE_CHAIN_ID` (decimal or `0x`-prefixed hexadecimal).
+    ///
+    /// When no network is requested, `OASIS_UNSAFE_USE_LOCALNET_CHAINID` selects Localnet.
+    /// Otherwise, if the crate version has a pre-release component (e.g. 3.0.0-alpha) then the
+    /// build is classified as Testnet. If there is no such component (e.g. 5.0.0) then it is
+    /// classified as Mainnet.
+    pub const fn from_build_env() -> Self {
+        match Self::resolve(BuildEnv::current()) {
+            Ok(network) => network,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Resolve the network profile from the given build environment (see
+    /// [`NetworkProfile::from_build_env`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chain ID of a custom network is not a valid integer.
+    pub const fn resolve(env: BuildEnv<'_>) -> Result<Self, &'static str> {
+        let Some(network) = env.network else {
+            return Ok(if env.localnet {
+                Self::Localnet
+            } else if env.pre_release {
+                Self::Testnet
+            } else {
+                Self::Mainnet
+            });
+        };
+
+        if str_eq(network, "mainnet") {
+            if env.pre_release {
+                return Err("Mainnet builds must not use a pre-release crate version");
+            }
+            if env.localnet {
+                return Err("OASIS_UNSAFE_USE_LOCALNET_CHAINID cannot be used with Mainnet builds");
+            }
+            Ok(Self::Mainnet)
+        } else if str_eq(network, "testnet") {
+            if !env.pre_release {
+                return Err("Testnet builds must use a pre-release crate version");
+            }
+            if env.localnet {
+                return Err("OASIS_UNSAFE_USE_LOCALNET_CHAINID cannot be used with Testnet builds");
+            }
+            Ok(Self::Testnet)
+        } else if str_eq(network, "localnet") {
+            Ok(Self::Localnet)
+        } else if str_eq(network, "custom") {
+            if env.localnet {
+                return Err(
+                    "OASIS_UNSAFE_USE_LOCALNET_CHAINID cannot be used with custom network builds",
+                );
+            }
+            let Some(chain_id) = env.chain_id else {
+                return Err("custom network builds must set OASIS_SAPPHIRE_CHAIN_ID");
+            };
+            let chain_id = parse_u64(chain_id);
+            if chain_id == MAINNET_CHAIN_ID
+                || chain_id == TESTNET_CHAIN_ID
+                || chain_id == LOCALNET_CHAIN_ID
+            {
+                return Err(
+                    "custom network chain ID must differ from the Mainnet, Testnet and Localnet \
+                     ones",
+                );
+            }
+            Ok(Self::Custom { chain_id })
+        } else {
+            Err("OASIS_SAPPHIRE_NETWORK must be one of mainnet, testnet, localnet or custom")
+        }
+    }
+
+    /// Human-readable name of the network.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+            Self::Localnet => "localnet",
+            Self::Custom { .. } => "custom",
+        }
+    }
+
+    /// EVM chain ID.
+    pub const fn chain_id(&self) -> u64 {
+        match self {
+            Self::Mainnet => MAINNET_CHAIN_ID,
+            Self::Testnet => TESTNET_CHAIN_ID,
+            Self::Localnet => LOCALNET_CHAIN_ID,
+            Self::Custom { chain_id } => *chain_id,
+        }
+    }
+
+    /// Hex-encoded runtime ID as declared in the orc package metadata.
+    ///
+    /// Mainnet uses the `release` and Testnet the `test` runtime ID, other networks are not
+    /// described by the package metadata.
+    pub const fn runtime_id(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some(env!("SAPPHIRE_ORC_RELEASE_RUNTIME_ID")),
+            Self::Testnet => Some(env!("SAPPHIRE_ORC_TEST_RUNTIME_ID")),
+            Self::Localnet | Self::Custom { .. } => None,
+        }
+    }
+
+    /// Public network whose state versions, and therefore migration steps, the network follows.
+    ///
+    /// Localnet and custom networks follow Testnet when built from a pre-release crate version
+    /// and Mainnet otherwise. This is how state versions were assigned before network profiles
+    /// were introduced, so existing Localnet states keep their version.
+    pub const fn state_lineage(&self) -> Self {
+        self.state_lineage_for(PRE_RELEASE)
+    }
+
+    /// Public network that the network follows when built from a crate version with or without
+    /// a pre-release component (see [`NetworkProfile::state_lineage`]).
+    pub const fn state_lineage_for(&self, pre_release: bool) -> Self {
+        match self {
+            Self::Mainnet | Self::Testnet => *self,
+            Self::Localnet | Self::Custom { .. } if pre_release => Self::Testnet,
+            Self::Localnet | Self::Custom { .. } => Self::Mainnet,
+        }
+    }
+
+    /// Current version of the global state.
+    pub const fn state_version(&self) -> u32 {
+        match self.state_lineage() {
+            Self::Testnet => TESTNET_STATE_VERSION,
+            _ => MAINNET_STATE_VERSION,
+        }
+    }
+
+    /// Genesis state of all runtime modules.
+    pub fn genesis_state(&self) -> Genesis {
+        match self {
+            Self::Mainnet => genesis::mainnet(),
+            Self::Testnet => genesis::testnet(),
+            Self::Localnet => genesis::localnet(),
+            Self::Custom { .. } => genesis::custom(),
+        }
+    }
+
+    /// Methods whose minimum gas price is discounted by the gas policy module according to the
+    /// genesis state, including `evm.Call` in case floors for contracts are configured.
+    pub const fn discounted_methods(&self) -> &'static [&'static str] {
+        match self {
+            Self::Mainnet => genesis::MAINNET_DISCOUNTED_METHODS,
+            Self::Testnet => genesis::TESTNET_DISCOUNTED_METHODS,
+            Self::Localnet => genesis::LOCALNET_DISCOUNTED_METHODS,
+            Self::Custom { .. } => genesis::CUSTOM_DISCOUNTED_METHODS,
+        }
+    }
+
+    /// Consensus layer trust root used for light client verification.
+    ///
+    /// Trust roots of the public networks are built in, while Localnet and custom networks use the
+    /// one supplied at build time (see [`TrustRootSpec::from_build_env`]), if any.
+    ///
+    /// Before network profiles were introduced, Localnet builds embedded the Mainnet or Testnet
+    /// trust root depending on the crate version. That root can never verify a Localnet consensus
+    /// layer, so Localnet SGX builds must now supply their own.
+    pub fn trust_root(&self) -> Option<TrustRoot> {
+        match self {
+            Self::Mainnet => Some(MAINNET_TRUST_ROOT.to_trust_root()),
+            Self::Testnet => Some(TESTNET_TRUST_ROOT.to_trust_root()),
+            Self::Localnet | Self::Custom { .. } => BUILD_ENV_TRUST_ROOT
+                .as_ref()
+                .map(TrustRootSpec::to_trust_root),
+        }
+    }
+}
+
+/// Consensus layer trust root in its hex-encoded form.
+pub struct TrustRootSpec {
+    /// Block height.
+    height: u64,
+    /// Hex-encoded block hash.
+    hash: &'static str,
+    /// Hex-encoded runtime ID.
+    runtime_id: &'static str,
+    /// Hex-encoded chain domain separation context.
+    chain_context: &'static str,
+}
+
+impl TrustRootSpec {
+    /// Resolve the trust root from the build environment.
+    ///
+    /// The trust root is given by setting all of `OASIS_SAPPHIRE_TRUST_ROOT_HEIGHT`,
+    /// `OASIS_SAPPHIRE_TRUST_ROOT_HASH`, `OASIS_SAPPHIRE_TRUST_ROOT_RUNTIME_ID` and
+    /// `OASIS_SAPPHIRE_TRUST_ROOT_CHAIN_CONTEXT`. It is only accepted for Localnet and custom
+    /// networks.
+    pub const fn from_build_env() -> Option<Self> {
+        match (
+            option_env!("OASIS_SAPPHIRE_TRUST_ROOT_HEIGHT"),
+            option_env!("OASIS_SAPPHIRE_TRUST_ROOT_HASH"),
+            option_env!("OASIS_SAPPHIRE_TRUST_ROOT_RUNTIME_ID"),
+            option_env!("OASIS_SAPPHIRE_TRUST_ROOT_CHAIN_CONTEXT"),
+        ) {
+            (None, None, None, None) => None,
+            (Some(height), Some(hash), Some(runtime_id), Some(chain_context)) => Some(Self {
+                height: parse_u64(height),
+                hash,
+                runtime_id,
+                chain_context,
+            }),
+            _ => panic!(
+                "trust roots must set all of OASIS_SAPPHIRE_TRUST_ROOT_HEIGHT, \
+                 OASIS_SAPPHIRE_TRUST_ROOT_HASH, OASIS_SAPPHIRE_TRUST_ROOT_RUNTIME_ID and \
+                 OASIS_SAPPHIRE_TRUST_ROOT_CHAIN_CONTEXT"
+            ),
+        }
+    }
+
+    /// Check that the trust root is well-formed and, if given, belongs to the runtime with the
+    /// given ID.
+    const fn validate(&self, runtime_id: Option<&str>) -> Result<(), &'static str> {
+        if self.height == 0 {
+            return Err("trust root height must be non-zero");
+        }
+        if !is_hex(self.hash, 32) {
+            return Err("trust root hash must be 32 hex-encoded bytes");
+        }
+        if !is_hex(self.runtime_id, 32) {
+            return Err("trust root runtime ID must be 32 hex-encoded bytes");
+        }
+        if !is_hex(self.chain_context, 32) {
+            return Err("trust root chain context must be 32 hex-encoded bytes");
+        }
+        if let Some(runtime_id) = runtime_id {
+            if !str_eq(self.runtime_id, runtime_id) {
+                return Err("trust root runtime ID does not match the orc package metadata");
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the trust root.
+    fn to_trust_root(&self) -> TrustRoot {
+        TrustRoot {
+            height: self.height,
+            hash: self.hash.into(),
+            runtime_id: self.runtime_id.into(),
+            chain_context: self.chain_context.to_string(),
+        }
+    }
+}
+
+/// Consensus layer trust root of Mainnet.
+const MAINNET_TRUST_ROOT: TrustRootSpec = TrustRootSpec {
+    height: 28000000,
+    hash: "c54ec4bf4baf2094a5e06287b7157709da67e54b259ce378973a9a56b7e3eb76",
+    runtime_id: "000000000000000000000000000000000000000000000000f80306c9858e7279",
+    chain_context: "bb3d748def55bdfb797a2ac53ee6ee141e54cd2ab2dc2375f4a0703a178e6e55",
+};
+const _: () = check_trust_root(
+    &MAINNET_TRUST_ROOT,
+    Some(env!("SAPPHIRE_ORC_RELEASE_RUNTIME_ID")),
+);
+
+/// Consensus layer trust root of Testnet.
+const TESTNET_TRUST_ROOT: TrustRootSpec = TrustRootSpec {
+    height: 30000000,
+    hash: "ac98ec3d9de10d890b011a70fda7e48d297f18809413cfec7b7ec89514a31977",
+    runtime_id: "000000000000000000000000000000000000000000000000a6d1e3ebf60dff6c",
+    chain_context: "0b91b8e4e44b2003a7c5e23ddadb5e14ef5345c0ebcb3ddcae07fa2f244cab76",
+};
+const _: () = check_trust_root(
+    &TESTNET_TRUST_ROOT,
+    Some(env!("SAPPHIRE_ORC_TEST_RUNTIME_ID")),
+);
+
+/// Consensus layer trust root supplied at build time.
+const BUILD_ENV_TRUST_ROOT: Option<TrustRootSpec> = TrustRootSpec::from_build_env();
+const _: () = {
+    if let Some(root) = &BUILD_ENV_TRUST_ROOT {
+        assert!(
+            matches!(
+                NETWORK,
+                NetworkProfile::Localnet | NetworkProfile::Custom { .. }
+            ),
+            "trust roots can only be supplied for Localnet and custom network builds"
+        );
+        check_trust_root(root, None);
+    } else if cfg!(target_env = "sgx") {
+        assert!(
+            matches!(NETWORK, NetworkProfile::Mainnet | NetworkProfile::Testnet),
+            "SGX builds for Localnet and custom networks must supply a trust root through the \
+             OASIS_SAPPHIRE_TRUST_ROOT_* environment variables"
+        );
+    }
+};
+
+/// Fail compilation in case the trust root is not valid for the runtime with the given ID.
+const fn check_trust_root(root: &TrustRootSpec, runtime_id: Option<&str>) {
+    if let Err(err) = root.validate(runtime_id) {
+        panic!("{}", err);
+    }
+}
+
+/// Check whether the string is a hex encoding of exactly `len` bytes in a const context.
+const fn is_hex(s: &str, len: usize) -> bool {
+    let s = s.as_bytes();
+    if s.len() != 2 * len {
+        return false;
+    }
+    let mut i = 0;
+    while i < s.len() {
+        if !s[i].is_ascii_hexdigit() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Compare two strings in a const context.
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal integer in a const context.
+const fn parse_u64(s: &str) -> u64 {
+    let s = s.as_bytes();
+    let (radix, start) = if s.len() > 2 && s[0] == b'0' && (s[1] == b'x' || s[1] == b'X') {
+        (16, 2)
+    } else {
+        (10, 0)
+    };
+    assert!(s.len() > start, "integer must not be empty");
+
+    let mut value: u64 = 0;
+    let mut i = start;
+    while i < s.len() {
+        let digit = match s[i] {
+            b'0'..=b'9' => (s[i] - b'0') as u64,
+            b'a'..=b'f' if radix == 16 => (s[i] - b'a' + 10) as u64,
+            b'A'..=b'F' if radix == 16 => (s[i] - b'A' + 10) as u64,
+            _ => panic!("integer must be decimal or 0x-prefixed hexadecimal"),
+        };
+        value = match value.checked_mul(radix) {
+            Some(v) => match v.checked_add(digit) {
+                Some(v) => v,
+                None => panic!("integer does not fit into 64 bits"),
+            },
+            None => panic!("integer does not fit into 64 bits"),
+        };
+        i += 1;
+    }
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve(
+        network: Option<&str>,
+        chain_id: Option<&str>,
+        localnet: bool,
+        pre_release: bool,
+    ) -> Result<NetworkProfile, &'static str> {
+        NetworkProfile::resolve(BuildEnv {
+            network,
+            chain_id,
+            localnet,
+            pre_release,
+        })
+    }
+
+    #[test]
+    fn test_resolve_implicit() {
+        use NetworkProfile::*;
+
+        assert_eq!(resolve(None, None, false, false), Ok(Mainnet));
+        assert_eq!(resolve(None, None, false, true), Ok(Testnet));
+        assert_eq!(resolve(None, None, true, false), Ok(Localnet));
+        assert_eq!(resolve(None, None, true, true), Ok(Localnet));
+        // The chain ID is only used by custom networks.
+        assert_eq!(resolve(None, Some("0x5b00"), false, false), Ok(Mainnet));
+    }
+
+    #[test]
+    fn test_resolve_explicit() {
+        use NetworkProfile::*;
+
+        for localnet in [false, true] {
+            for pre_release in [false, true] {
+                let mainnet = resolve(Some("mainnet"), None, localnet, pre_release);
+                assert_eq!(mainnet.is_ok(), !localnet && !pre_release);
+                let testnet = resolve(Some("testnet"), None, localnet, pre_release);
+                assert_eq!(testnet.is_ok(), !localnet && pre_release);
+                let local = resolve(Some("localnet"), None, localnet, pre_release);
+                assert_eq!(local, Ok(Localnet));
+                let custom = resolve(Some("custom"), Some("0x5b00"), localnet, pre_release);
+                assert_eq!(custom.is_ok(), !localnet);
+                assert!(resolve(Some("devnet"), None, localnet, pre_release).is_err());
+            }
+        }
+        assert_eq!(resolve(Some("mainnet"), None, false, false), Ok(Mainnet));
+        assert_eq!(resolve(Some("testnet"), None, false, true), Ok(Testnet));
+    }
+
+    #[test]
+    fn test_resolve_custom() {
+        assert_eq!(
+            resolve(Some("custom"), Some("0x5b00"), false, false),
+            Ok(NetworkProfile::Custom { chain_id: 0x5b00 })
+        );
+        assert_eq!(
+            resolve(Some("custom"), Some("1234"), false, true),
+            Ok(NetworkProfile::Custom { chain_id: 1234 })
+        );
+        assert!(resolve(Some("custom"), None, false, false).is_err());
+        for chain_id in ["0x5afe", "0x5aff", "0x5afd"] {
+            assert!(resolve(Some("custom"), Some(chain_id), false, false).is_err());
+        }
+    }
+
+    #[test]
+    fn test_state_lineage() {
+        use NetworkProfile::*;
+
+        for pre_release in [false, true] {
+            assert_eq!(Mainnet.state_lineage_for(pre_release), Mainnet);
+            assert_eq!(Testnet.state_lineage_for(pre_release), Testnet);
+        }
+        // Localnet and custom networks keep the state versions they had before network profiles.
+        let custom = Custom { chain_id: 0x5b00 };
+        assert_eq!(Localnet.state_lineage_for(false), Mainnet);
+        assert_eq!(Localnet.state_lineage_for(true), Testnet);
+        assert_eq!(custom.state_lineage_for(false), Mainnet);
+        assert_eq!(custom.state_lineage_for(true), Testnet);
+
+        assert_eq!(Mainnet.state_version(), MAINNET_STATE_VERSION);
+        assert_eq!(Testnet.state_version(), TESTNET_STATE_VERSION);
+        let expected = if PRE_RELEASE {
+            TESTNET_STATE_VERSION
+        } else {
+            MAINNET_STATE_VERSION
+        };
+        assert_eq!(Localnet.state_version(), expected);
+    }
+}
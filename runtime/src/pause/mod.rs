@@ -0,0 +1,291 @@
+//! Emergency pause (circuit breaker) module.
+//!
+//! Allows a designated set of guardians to reject calls of selected methods (e.g. `evm.Create` or
+//! all `roflmarket.*` methods) and of selected EVM contracts during incident response. Any single
+//! guardian can pause or unpause a target and each change emits an event. Only the methods listed
+//! in [`PAUSABLE_METHODS`] can be paused.
+//!
+//! Paused contracts are rejected on every call within the EVM, including calls made by other
+//! contracts or constructors and delegate calls (see [`crate::precompiles`]). Each call entering a
+//! contract is charged [`GAS_COST_CONTRACT_CHECK`] for looking up its pause state.
+use oasis_runtime_sdk::{
+    context::Context,
+    handler, migration,
+    module::{self, Module as _, Parameters as _},
+    modules::{self, core::API as _},
+    sdk_derive,
+    state::CurrentState,
+    types::{address::Address, transaction::Call},
+    Runtime,
+};
+use thiserror::Error;
+
+use crate::governance;
+
+pub mod state;
+pub mod types;
+
+/// Unique module name.
+pub const MODULE_NAME: &str = "pause";
+
+/// Methods and method patterns that guardians can pause.
+///
+/// Methods moving funds out of the runtime, transfers and EVM calls (individual contracts can be
+/// paused instead) are left out so that guardians cannot lock funds, as are the methods of this and
+/// the governance module so that the breaker can always be reset.
+pub const PAUSABLE_METHODS: &[&str] = &[
+    "evm.Create",
+    "consensus.Deposit",
+    "consensus.Delegate",
+    "rofl.*",
+    "roflmarket.*",
+];
+
+/// Gas cost of pause.Set call.
+pub const GAS_COST_CALL_SET: u64 = 10_000;
+/// Gas cost of checking whether the contract entered by an EVM call is paused, priced as a warm
+/// storage read as it is performed on every call.
+pub const GAS_COST_CONTRACT_CHECK: u64 = 100;
+
+/// Errors emitted by the pause module.
+#[derive(Error, Debug, oasis_runtime_sdk::Error)]
+pub enum Error {
+    #[error("invalid argument")]
+    #[sdk_error(code = 1)]
+    InvalidArgument,
+
+    #[error("forbidden")]
+    #[sdk_error(code = 2)]
+    Forbidden,
+
+    #[error("core: {0}")]
+    #[sdk_error(transparent)]
+    Core(#[from] modules::core::Error),
+}
+
+/// Events emitted by the pause module.
+#[derive(Debug, PartialEq, Eq, cbor::Encode, oasis_runtime_sdk::Event)]
+#[cbor(untagged)]
+pub enum Event {
+    #[sdk_event(code = 1)]
+    Paused {
+        target: types::Target,
+        guardian: Address,
+    },
+
+    #[sdk_event(code = 2)]
+    Unpaused {
+        target: types::Target,
+        guardian: Address,
+    },
+}
+
+/// Parameters for the pause module.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Parameters {
+    /// Guardians that can pause and unpause targets.
+    pub guardians: Vec<Address>,
+}
+
+/// Errors emitted during parameter validation.
+#[derive(Error, Debug)]
+pub enum ParameterValidationError {
+    #[error("duplicate guardian")]
+    DuplicateGuardian,
+}
+
+impl module::Parameters for Parameters {
+    type Error = ParameterValidationError;
+
+    fn validate_basic(&self) -> Result<(), Self::Error> {
+        for (i, guardian) in self.guardians.iter().enumerate() {
+            if self.guardians[..i].contains(guardian) {
+                return Err(ParameterValidationError::DuplicateGuardian);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Genesis state for the pause module.
+#[derive(Clone, Debug, Default, cbor::Encode, cbor::Decode)]
+pub struct Genesis {
+    pub parameters: Parameters,
+}
+
+/// The pause module.
+pub struct Module;
+
+#[sdk_derive(Module)]
+impl Module {
+    const NAME: &'static str = MODULE_NAME;
+    const VERSION: u32 = 1;
+    type Error = Error;
+    type Event = Event;
+    type Parameters = Parameters;
+    type Genesis = Genesis;
+
+    #[migration(init)]
+    fn init(genesis: Genesis) {
+        genesis
+            .parameters
+            .validate_basic()
+            .expect("invalid genesis parameters");
+        Self::set_params(genesis.parameters);
+    }
+
+    #[handler(call = "pause.Set")]
+    fn tx_set<C: Context>(_ctx: &C, body: types::Set) -> Result<(), Error> {
+        <C::Runtime as Runtime>::Core::use_tx_gas(GAS_COST_CALL_SET)?;
+
+        let guardian = CurrentState::with_env(|env| env.tx_caller_address());
+        if !Self::params().guardians.contains(&guardian) {
+            return Err(Error::Forbidden);
+        }
+        if let types::Target::Method(pattern) = &body.target {
+            validate_pattern(pattern)?;
+        }
+
+        if CurrentState::with_env(|env| env.is_check_only()) {
+            return Ok(());
+        }
+
+        match &body.target {
+            types::Target::Method(pattern) => {
+                let mut methods = state::get_methods();
+                if !update(&mut methods, pattern, body.paused) {
+                    return Ok(());
+                }
+                state::set_methods(methods);
+            }
+            types::Target::Contract(address) => {
+                if state::is_contract_paused(address) == body.paused {
+                    return Ok(());
+                }
+                state::set_contract_paused(address, body.paused);
+            }
+        }
+
+        let target = body.target;
+        CurrentState::with(|state| {
+            state.emit_event(if body.paused {
+                Event::Paused { target, guardian }
+            } else {
+                Event::Unpaused { target, guardian }
+            })
+        });
+
+        Ok(())
+    }
+
+    #[handler(query = "pause.Status")]
+    fn query_status<C: Context>(_ctx: &C, _args: ()) -> Result<types::Status, Error> {
+        Ok(state::get_status())
+    }
+
+    #[handler(query = "pause.Parameters")]
+    fn query_parameters<C: Context>(_ctx: &C, _args: ()) -> Result<Parameters, Error> {
+        Ok(Self::params())
+    }
+}
+
+impl module::TransactionHandler for Module {
+    fn before_handle_call<C: Context>(_ctx: &C, call: &Call) -> Result<(), modules::core::Error> {
+        if state::get_methods()
+            .iter()
+            .any(|pattern| matches_method(pattern, &call.method))
+        {
+            return Err(modules::core::Error::Forbidden);
+        }
+
+        // Direct calls are rejected early, all others when the contract is entered.
+        if call.method == "evm.Call" {
+            if let Ok(body) = cbor::from_value::<module_evm::types::Call>(call.body.clone()) {
+                if state::is_contract_paused(&body.address) {
+                    return Err(modules::core::Error::Forbidden);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl module::BlockHandler for Module {}
+
+impl module::InvariantHandler for Module {}
+
+/// Make sure the given method pattern is well-formed and covered by [`PAUSABLE_METHODS`].
+fn validate_pattern(pattern: &str) -> Result<(), Error> {
+    let Some((module, method)) = pattern.split_once('.') else {
+        return Err(Error::InvalidArgument);
+    };
+    if module.is_empty() || method.is_empty() {
+        return Err(Error::InvalidArgument);
+    }
+    if !PAUSABLE_METHODS
+        .iter()
+        .any(|pausable| *pausable == pattern || matches_method(pausable, pattern))
+    {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Whether the given method pattern matches the given method.
+fn matches_method(pattern: &str, method: &str) -> bool {
+    match pattern.strip_suffix(".*") {
+        Some(module) => method
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.starts_with('.')),
+        None => pattern == method,
+    }
+}
+
+/// Add the given item to or remove it from the list, returning whether the list changed.
+fn update<T: Clone + PartialEq>(list: &mut Vec<T>, item: &T, present: bool) -> bool {
+    match (list.contains(item), present) {
+        (false, true) => list.push(item.clone()),
+        (true, false) => list.retain(|i| i != item),
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches_method() {
+        assert!(matches_method("evm.Create", "evm.Create"));
+        assert!(!matches_method("evm.Create", "evm.Call"));
+        assert!(matches_method("roflmarket.*", "roflmarket.InstanceCreate"));
+        assert!(!matches_method("roflmarket.*", "rofl.Register"));
+        assert!(!matches_method("rofl.*", "roflmarket.InstanceCreate"));
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        assert!(validate_pattern("evm.Create").is_ok());
+        assert!(validate_pattern("roflmarket.*").is_ok());
+        assert!(validate_pattern("roflmarket.InstanceCreate").is_ok());
+        for pattern in [
+            "evm",
+            ".Create",
+            "evm.",
+            "evm.*",
+            "pause.Set",
+            "governance.*",
+            "accounts.Transfer",
+            "consensus.Withdraw",
+            "evm.Call",
+            "core.*",
+        ] {
+            assert!(
+                validate_pattern(pattern).is_err(),
+                "{pattern} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Pause module state.
+use module_evm::types::H160;
+use oasis_runtime_sdk::{state::CurrentState, storage};
+
+use super::{types::Status, MODULE_NAME};
+
+/// Paused methods and method patterns.
+const METHODS: &[u8] = &[0x01];
+/// Set of paused EVM contracts.
+const CONTRACTS: &[u8] = &[0x02];
+
+/// Current state of the circuit breaker.
+pub fn get_status() -> Status {
+    Status {
+        methods: get_methods(),
+        contracts: get_contracts(),
+    }
+}
+
+/// Paused methods and method patterns.
+pub fn get_methods() -> Vec<String> {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(store, &MODULE_NAME));
+        store.get(METHODS).unwrap_or_default()
+    })
+}
+
+/// Update the paused methods and method patterns.
+pub fn set_methods(methods: Vec<String>) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(store, &MODULE_NAME));
+        store.insert(METHODS, methods);
+    })
+}
+
+/// Paused EVM contracts.
+pub fn get_contracts() -> Vec<H160> {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &CONTRACTS,
+        ));
+        store
+            .iter::<Vec<u8>, bool>()
+            .map(|(address, _)| H160::from_slice(&address))
+            .collect()
+    })
+}
+
+/// Whether calls of the given EVM contract are paused.
+pub fn is_contract_paused(address: &H160) -> bool {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &CONTRACTS,
+        ));
+        store.get::<_, bool>(address.as_bytes()).unwrap_or_default()
+    })
+}
+
+/// Pause or unpause calls of the given EVM contract.
+pub fn set_contract_paused(address: &H160, paused: bool) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &CONTRACTS,
+        ));
+        if paused {
+            store.insert(address.as_bytes(), true);
+        } else {
+            store.remove(address.as_bytes());
+        }
+    })
+}
@@ -0,0 +1,31 @@
+//! Pause module types.
+use module_evm::types::H160;
+
+/// Target of the circuit breaker.
+#[derive(Clone, Debug, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub enum Target {
+    /// A method name (e.g. `evm.Create`) or all methods of a module (e.g. `roflmarket.*`).
+    #[cbor(rename = "method")]
+    Method(String),
+    /// Calls of an EVM contract.
+    #[cbor(rename = "contract")]
+    Contract(H160),
+}
+
+/// Current state of the circuit breaker.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Status {
+    /// Paused methods and method patterns.
+    pub methods: Vec<String>,
+    /// Paused EVM contracts.
+    pub contracts: Vec<H160>,
+}
+
+/// Set call.
+#[derive(Clone, Debug, cbor::Encode, cbor::Decode)]
+pub struct Set {
+    /// Target to pause or unpause.
+    pub target: Target,
+    /// Whether the target should be paused.
+    pub paused: bool,
+}
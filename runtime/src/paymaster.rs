@@ -0,0 +1,165 @@
+//! Contract-sponsored transaction fees.
+//!
+//! An EVM contract can register itself as a paymaster through the [`precompiles::paymaster`]
+//! precompile. Each paymaster has a deposit account which anyone can top up (e.g. using an
+//! `accounts.Transfer` to [`deposit_address`]) and only the contract can withdraw from.
+//!
+//! Transactions calling the paymaster contract can then set the fee proxy to [`MODULE_NAME`] with
+//! the contract address as the identifier in order to have their fees charged to the deposit of
+//! the paymaster instead of the signer. This makes it possible to onboard users that do not hold
+//! any tokens yet. A paymaster limits the fee it is willing to pay for each call and only sponsors
+//! the callers on its allowlist, so that its deposit cannot be drained by anyone sending failing
+//! transactions. Only contracts can register as paymasters.
+//!
+//! Only plain `evm.Call` transactions can be sponsored as the called contract of encrypted
+//! transactions is not visible when resolving the fee payer.
+//!
+//! [`precompiles::paymaster`]: crate::precompiles::paymaster
+use anyhow::anyhow;
+use oasis_runtime_sdk::{
+    context::Context,
+    module::FeeProxyHandler,
+    modules,
+    state::CurrentState,
+    storage,
+    types::{
+        address::Address,
+        token::Denomination,
+        transaction::{CallFormat, Transaction},
+    },
+};
+use primitive_types::H160;
+
+use crate::Config;
+
+/// Name of the fee proxy used to select paymaster sponsorship.
+pub const MODULE_NAME: &str = "paymaster";
+
+/// Storage key prefix of paymaster configurations.
+const PAYMASTERS: &[u8] = &[0x01];
+/// Storage key prefix of paymaster allowlists.
+const ALLOWED_CALLERS: &[u8] = &[0x02];
+
+/// Configuration of a paymaster contract.
+#[derive(Clone, Debug, Default, PartialEq, Eq, cbor::Encode, cbor::Decode)]
+pub struct Paymaster {
+    /// Maximum fee paid for a single call, in native base units.
+    pub max_fee_per_call: u128,
+}
+
+/// Fee proxy charging the fees of transactions to ROFL applications or paymaster contracts.
+pub struct FeeProxy;
+
+impl FeeProxyHandler for FeeProxy {
+    fn resolve_payer<C: Context>(
+        ctx: &C,
+        tx: &Transaction,
+    ) -> Result<Option<Address>, modules::core::Error> {
+        if let Some(payer) = modules::rofl::Module::<Config>::resolve_payer(ctx, tx)? {
+            return Ok(Some(payer));
+        }
+        resolve_payer(tx)
+    }
+}
+
+/// Resolve the deposit account of the paymaster sponsoring the given transaction.
+fn resolve_payer(tx: &Transaction) -> Result<Option<Address>, modules::core::Error> {
+    use modules::core::Error;
+
+    let Some(proxy) = &tx.auth_info.fee.proxy else {
+        return Ok(None);
+    };
+    if proxy.module != MODULE_NAME {
+        return Ok(None);
+    }
+    if proxy.id.len() != H160::len_bytes() {
+        return Err(Error::InvalidArgument(anyhow!(
+            "malformed paymaster contract address"
+        )));
+    }
+    let contract = H160::from_slice(&proxy.id);
+
+    // Only calls of the paymaster contract itself are sponsored.
+    if tx.call.format != CallFormat::Plain || tx.call.method != "evm.Call" {
+        return Err(Error::Forbidden);
+    }
+    let call: module_evm::types::Call =
+        cbor::from_value(tx.call.body.clone()).map_err(|_| Error::Forbidden)?;
+    if call.address.as_bytes() != contract.as_bytes() {
+        return Err(Error::Forbidden);
+    }
+
+    let paymaster = get_paymaster(contract).ok_or(Error::Forbidden)?;
+    let fee = &tx.auth_info.fee.amount;
+    if fee.denomination() != &Denomination::NATIVE || fee.amount() > paymaster.max_fee_per_call {
+        return Err(Error::Forbidden);
+    }
+    // Look only at the first signer, consistent with the ROFL fee proxy.
+    let caller = tx
+        .auth_info
+        .signer_info
+        .first()
+        .map(|si| si.address_spec.address())
+        .ok_or(Error::Forbidden)?;
+    if !is_allowed(contract, caller) {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(Some(deposit_address(contract)))
+}
+
+/// Address of the deposit account of the given paymaster contract.
+pub fn deposit_address(contract: H160) -> Address {
+    Address::from_module_raw(MODULE_NAME, contract.as_bytes())
+}
+
+/// Configuration of the given paymaster contract, if it is registered.
+pub fn get_paymaster(contract: H160) -> Option<Paymaster> {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &PAYMASTERS,
+        ));
+        store.get(contract.as_bytes())
+    })
+}
+
+/// Register or update the configuration of the given paymaster contract.
+pub fn set_paymaster(contract: H160, paymaster: Paymaster) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &PAYMASTERS,
+        ));
+        store.insert(contract.as_bytes(), paymaster);
+    })
+}
+
+/// Whether the given caller is on the allowlist of the given paymaster contract.
+pub fn is_allowed(contract: H160, caller: Address) -> bool {
+    CurrentState::with_store(|store| {
+        let store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &ALLOWED_CALLERS,
+        ));
+        store
+            .get::<_, bool>([contract.as_bytes(), caller.as_ref()].concat())
+            .unwrap_or_default()
+    })
+}
+
+/// Add the given caller to or remove it from the allowlist of the given paymaster contract.
+pub fn set_allowed(contract: H160, caller: Address, allowed: bool) {
+    CurrentState::with_store(|store| {
+        let mut store = storage::TypedStore::new(storage::PrefixStore::new(
+            storage::PrefixStore::new(store, &MODULE_NAME),
+            &ALLOWED_CALLERS,
+        ));
+        let key = [contract.as_bytes(), caller.as_ref()].concat();
+        if allowed {
+            store.insert(key, true);
+        } else {
+            store.remove(key);
+        }
+    })
+}
@@ -0,0 +1,165 @@
+//! Helpers for precompiles that are called using the Solidity ABI.
+//!
+//! Calls start with the selector of the invoked method, followed by its ABI-encoded arguments of
+//! which the first one is always the requested ABI version. This allows the output of a method to
+//! evolve without breaking contracts that were written against an earlier version.
+use ethabi::{ParamType, Token};
+use evm::{
+    executor::stack::{PrecompileFailure, PrecompileHandle},
+    ExitRevert,
+};
+use primitive_types::U256;
+
+/// Selector of the standard `Error(string)` revert reason.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Method of a precompile called using the Solidity ABI.
+pub(super) trait Method: Copy + Sized + 'static {
+    /// All methods of the precompile.
+    const ALL: &'static [Self];
+
+    /// Name of the method.
+    fn name(self) -> &'static str;
+
+    /// Parameters of the method following the ABI version.
+    fn params(self) -> Vec<ParamType>;
+
+    /// Selector of the method.
+    fn selector(self) -> [u8; 4] {
+        ethabi::short_signature(self.name(), &self.all_params())
+    }
+
+    /// All parameters of the method, including the ABI version.
+    fn all_params(self) -> Vec<ParamType> {
+        let mut params = vec![ParamType::Uint(16)];
+        params.extend(self.params());
+        params
+    }
+}
+
+/// Decode a call of one of the given methods into the method and its arguments.
+///
+/// Fails in case the method is unknown, the arguments are malformed or the requested ABI version
+/// is not the given one.
+pub(super) fn decode_call<M: Method>(
+    input: &[u8],
+    version: u16,
+) -> Result<(M, Vec<Token>), PrecompileFailure> {
+    if input.len() < 4 {
+        return Err(revert("missing method selector"));
+    }
+    let (selector, data) = input.split_at(4);
+    let method = M::ALL
+        .iter()
+        .copied()
+        .find(|method| method.selector() == selector)
+        .ok_or_else(|| revert("unknown method"))?;
+
+    let mut args = ethabi::decode_whole(&method.all_params(), data)
+        .map_err(|_| revert("malformed arguments"))?;
+    let requested = args.remove(0).into_uint().unwrap_or_default();
+    if requested != U256::from(version) {
+        return Err(revert("unsupported ABI version"));
+    }
+
+    Ok((method, args))
+}
+
+/// Charge the given amount of gas for each 32-byte word of the output.
+pub(super) fn record_output_cost(
+    handle: &mut impl PrecompileHandle,
+    output: &[u8],
+    gas_cost_per_word: u64,
+) -> Result<(), PrecompileFailure> {
+    let words = output.len().div_ceil(32) as u64;
+    handle.record_cost(words.saturating_mul(gas_cost_per_word))?;
+    Ok(())
+}
+
+/// Revert with the given reason.
+pub(super) fn revert(reason: &str) -> PrecompileFailure {
+    let mut output = ERROR_SELECTOR.to_vec();
+    output.extend(ethabi::encode(&[Token::String(reason.to_string())]));
+
+    PrecompileFailure::Revert {
+        exit_status: ExitRevert::Reverted,
+        output,
+    }
+}
+
+/// Convert an unsigned integer into a token.
+pub(super) fn uint(value: impl Into<U256>) -> Token {
+    Token::Uint(value.into())
+}
+
+/// Convert a map into a pair of key and value arrays.
+pub(super) fn string_map<'a>(
+    map: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> [Token; 2] {
+    let (keys, values) = map
+        .into_iter()
+        .map(|(key, value)| (Token::String(key.clone()), Token::String(value.clone())))
+        .unzip();
+    [Token::Array(keys), Token::Array(values)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum TestMethod {
+        Get,
+    }
+
+    impl Method for TestMethod {
+        const ALL: &'static [Self] = &[Self::Get];
+
+        fn name(self) -> &'static str {
+            "get"
+        }
+
+        fn params(self) -> Vec<ParamType> {
+            vec![ParamType::FixedBytes(21)]
+        }
+    }
+
+    fn call(version: u16) -> Vec<u8> {
+        let mut input = TestMethod::Get.selector().to_vec();
+        input.extend(ethabi::encode(&[
+            uint(version),
+            Token::FixedBytes(vec![1; 21]),
+        ]));
+        input
+    }
+
+    fn revert_reason(failure: PrecompileFailure) -> String {
+        let PrecompileFailure::Revert { output, .. } = failure else {
+            panic!("call should revert");
+        };
+        assert_eq!(output[..4], ERROR_SELECTOR);
+        ethabi::decode(&[ParamType::String], &output[4..])
+            .unwrap()
+            .remove(0)
+            .into_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decode_call() {
+        let (method, args) = decode_call::<TestMethod>(&call(1), 1).unwrap();
+        assert_eq!(method, TestMethod::Get);
+        assert_eq!(args, vec![Token::FixedBytes(vec![1; 21])]);
+
+        let cases = [
+            (call(2), "unsupported ABI version"),
+            (call(1)[..3].to_vec(), "missing method selector"),
+            ([&[0u8; 4][..], &call(1)[4..]].concat(), "unknown method"),
+            (call(1)[..call(1).len() - 1].to_vec(), "malformed arguments"),
+        ];
+        for (input, reason) in cases {
+            let err = decode_call::<TestMethod>(&input, 1).unwrap_err();
+            assert_eq!(revert_reason(err), reason);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Sapphire-specific EVM precompiles.
+//!
+//! These are exposed through [`module_evm::Config::AdditionalPrecompileSet`] and are available in
+//! addition to the precompiles provided by the EVM module.
+//!
+//! The set also enforces the emergency pause of EVM contracts (see [`crate::pause`]): the EVM
+//! consults the set on every call, so calls entering a paused contract revert instead of running
+//! its code.
+use evm::executor::stack::{
+    IsPrecompileResult, PrecompileFailure, PrecompileHandle, PrecompileResult, PrecompileSet,
+};
+use primitive_types::H160;
+
+use crate::pause;
+
+mod abi;
+pub mod bls12_381;
+pub mod p256;
+pub mod paymaster;
+pub mod rofl;
+pub mod rofl_market;
+
+/// Set of additional precompiles.
+pub struct Precompiles;
+
+impl PrecompileSet for Precompiles {
+    fn execute(&self, handle: &mut impl PrecompileHandle) -> Option<PrecompileResult> {
+        let address = handle.code_address();
+        if address == p256::VERIFY_ADDRESS {
+            return Some(p256::call_verify(handle));
+        }
+        if let Some(operation) = bls12_381::Operation::from_address(address) {
+            return Some(bls12_381::call(handle, operation));
+        }
+        if address == paymaster::PAYMASTER_ADDRESS {
+            return Some(paymaster::call_paymaster(handle));
+        }
+        if address == rofl::QUERY_ADDRESS {
+            return Some(rofl::call_query(handle));
+        }
+        if address == rofl_market::QUERY_ADDRESS {
+            return Some(rofl_market::call_query(handle));
+        }
+        // All other calls enter a contract (or an account without code).
+        check_pause(handle).err().map(Err)
+    }
+
+    fn is_precompile(&self, address: H160, _remaining_gas: u64) -> IsPrecompileResult {
+        IsPrecompileResult::Answer {
+            is_precompile: address == p256::VERIFY_ADDRESS
+                || bls12_381::Operation::from_address(address).is_some()
+                || address == paymaster::PAYMASTER_ADDRESS
+                || address == rofl::QUERY_ADDRESS
+                || address == rofl_market::QUERY_ADDRESS,
+            extra_cost: 0,
+        }
+    }
+}
+
+/// Reject the call in case the entered contract is paused.
+fn check_pause(handle: &mut impl PrecompileHandle) -> Result<(), PrecompileFailure> {
+    handle.record_cost(pause::GAS_COST_CONTRACT_CHECK)?;
+    let address = module_evm::types::H160::from_slice(handle.code_address().as_bytes());
+    if pause::state::is_contract_paused(&address) {
+        return Err(abi::revert("contract paused"));
+    }
+    Ok(())
+}
+
+/// Address of the precompile with the given index in the low bytes.
+const fn address(index: u16) -> H160 {
+    let mut address = [0u8; 20];
+    let index = index.to_be_bytes();
+    address[18] = index[0];
+    address[19] = index[1];
+    H160(address)
+}
+
+/// Address of the Sapphire-specific precompile with the given index in the low bytes.
+const fn sapphire_address(index: u16) -> H160 {
+    let mut address = address(index);
+    address.0[0] = 0x01;
+    address
+}
@@ -0,0 +1,142 @@
+//! Secp256r1 (P-256) signature verification precompile as specified in RIP-7212.
+//!
+//! The input is the 160-byte concatenation of the message hash and the `r`, `s`, `x` and `y`
+//! values, all encoded as 32-byte big-endian integers. A valid signature results in the 32-byte
+//! word 1, while invalid signatures or malformed inputs result in empty output. The call itself
+//! never fails apart from running out of gas.
+use evm::{
+    executor::stack::{PrecompileHandle, PrecompileOutput, PrecompileResult},
+    ExitSucceed,
+};
+use p256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use primitive_types::H160;
+
+/// Address of the verification precompile.
+pub const VERIFY_ADDRESS: H160 = super::address(0x100);
+
+/// Gas cost of a verification.
+pub const VERIFY_GAS_COST: u64 = 3450;
+
+/// Size of the precompile input.
+const INPUT_SIZE: usize = 160;
+
+/// Execute the verification precompile.
+pub(super) fn call_verify(handle: &mut impl PrecompileHandle) -> PrecompileResult {
+    handle.record_cost(VERIFY_GAS_COST)?;
+
+    let output = if verify(handle.input()) {
+        let mut word = vec![0u8; 32];
+        word[31] = 1;
+        word
+    } else {
+        vec![]
+    };
+
+    Ok(PrecompileOutput {
+        exit_status: ExitSucceed::Returned,
+        output,
+    })
+}
+
+/// Verify the signature encoded in the given precompile input.
+fn verify(input: &[u8]) -> bool {
+    if input.len() != INPUT_SIZE {
+        return false;
+    }
+    let (hash, rest) = input.split_at(32);
+    let (signature, key) = rest.split_at(64);
+
+    // Rejects zero scalars and scalars that are not reduced modulo the curve order.
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return false;
+    };
+    // Rejects the point at infinity and points which are not on the curve.
+    let mut point = [0u8; 65];
+    point[0] = 0x04; // Uncompressed SEC1 encoding.
+    point[1..].copy_from_slice(key);
+    let Ok(key) = VerifyingKey::from_sec1_bytes(&point) else {
+        return false;
+    };
+
+    key.verify_prehash(hash, &signature).is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Wycheproof `ecdsa_secp256r1_sha256_test.json` vectors in the precompile input format.
+    ///
+    /// The messages are hashed as the precompile takes a digest and the DER-encoded signatures are
+    /// converted into `r` and `s`. Vectors whose signature is not a valid DER encoding of two
+    /// 32-byte integers only test the signature encoding, which the precompile does not have, and
+    /// are left out like in the other RIP-7212 implementations.
+    const WYCHEPROOF: &str = include_str!("testdata/p256/ecdsa_secp256r1_sha256.json");
+
+    /// Reference vector from the RIP-7212 specification.
+    const RIP7212_VALID: &str = concat!(
+        "4cee90eb86eaa050036147a12d49004b6b9c72bd725d39d4785011fe190f0b4d",
+        "a73bd4903f0ce3b639bbbf6e8e80d16931ff4bcf5993d58468e8fb19086e8cac",
+        "36dbcd03009df8c59286b162af3bd7fcc0450c9aa81be5d10d312af6c66b1d60",
+        "4aebd3099c618202fcfe16ae7770b0c49ab5eadf74b754204a3bb6060e44eff3",
+        "7618b065f9832de4ca6ca971a7a1adc826d0f7c00181a5fb2ddf79ae00b4e10e",
+    );
+
+    fn field<'a>(vector: &'a Value, name: &str) -> &'a str {
+        vector[name].as_str().unwrap()
+    }
+
+    #[test]
+    fn test_wycheproof() {
+        let Value::Array(vectors) = serde_json::from_str(WYCHEPROOF).unwrap() else {
+            panic!("test vectors should be an array");
+        };
+        let valid = vectors
+            .iter()
+            .filter(|vector| !field(vector, "Expected").is_empty())
+            .count();
+        assert_eq!((vectors.len(), valid), (249, 174));
+
+        for vector in &vectors {
+            let input = hex::decode(field(vector, "Input")).unwrap();
+            let expected = !field(vector, "Expected").is_empty();
+            assert_eq!(
+                verify(&input),
+                expected,
+                "{} should {}verify",
+                field(vector, "Name"),
+                if expected { "" } else { "not " }
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_valid() {
+        assert!(verify(&hex::decode(RIP7212_VALID).unwrap()));
+    }
+
+    #[test]
+    fn test_verify_malformed() {
+        // Malformed keys and inputs, which the Wycheproof signature vectors do not cover.
+        let valid = hex::decode(RIP7212_VALID).unwrap();
+        let (digest_and_signature, key) = valid.split_at(96);
+        let (x, y) = key.split_at(32);
+
+        let cases = [
+            (
+                "point at infinity",
+                [digest_and_signature, &[0; 64]].concat(),
+            ),
+            ("point not on curve", [digest_and_signature, x, x].concat()),
+            ("swapped coordinates", [digest_and_signature, y, x].concat()),
+            ("truncated input", valid[..INPUT_SIZE - 1].to_vec()),
+            ("extended input", [&valid[..], &[0]].concat()),
+            ("empty input", vec![]),
+        ];
+        for (comment, input) in cases {
+            assert!(!verify(&input), "{comment} should not verify");
+        }
+    }
+}
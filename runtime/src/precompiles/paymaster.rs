@@ -0,0 +1,248 @@
+//! Paymaster management precompile.
+//!
+//! Allows contracts to sponsor the fees of calls made to them (see [`crate::paymaster`]),
+//! ABI-encoded according to the following interface (see [`super::abi`] for the calling
+//! convention):
+//!
+//! ```solidity
+//! interface IPaymasterV1 {
+//!     // Register the calling contract as a paymaster or update its configuration.
+//!     function configure(uint16 version, uint128 maxFeePerCall) external;
+//!     // Add a caller to or remove it from the allowlist of the calling contract.
+//!     function setAllowed(uint16 version, address caller, bool allowed) external;
+//!     // Withdraw from the deposit of the calling contract.
+//!     function withdraw(uint16 version, bytes21 to, uint128 amount) external;
+//!
+//!     function paymaster(uint16 version, address contract) external view returns (
+//!         bool registered, bytes21 depositAddress, uint128 balance, uint128 maxFeePerCall
+//!     );
+//!     function isAllowed(
+//!         uint16 version, address contract, address caller
+//!     ) external view returns (bool allowed);
+//! }
+//! ```
+//!
+//! Methods that modify the configuration apply to the calling contract and revert when invoked
+//! through a static or delegate call. Only accounts with deployed code can register, so calling
+//! `configure` from an externally owned account or a constructor reverts. Unregistered contracts have a zero maximum fee and thus
+//! do not sponsor any calls. As with subcalls, the changes are made in a nested state transaction
+//! of the calling frame and are discarded when that frame or any of its parents reverts.
+use ethabi::{ParamType, Token};
+use evm::{
+    executor::stack::{PrecompileFailure, PrecompileHandle, PrecompileOutput, PrecompileResult},
+    ExitSucceed,
+};
+use oasis_runtime_sdk::{
+    modules::accounts::{self, API as _},
+    state::{CurrentState, TransactionResult},
+    types::{
+        address::Address,
+        token::{BaseUnits, Denomination},
+    },
+};
+use primitive_types::H160;
+
+use super::abi::{self, uint};
+use crate::paymaster::{self, Paymaster};
+
+/// Address of the paymaster precompile.
+pub const PAYMASTER_ADDRESS: H160 = super::sapphire_address(0x0303);
+
+/// Current version of the ABI.
+pub const ABI_VERSION: u16 = 1;
+
+/// Base gas cost of a call.
+pub const GAS_COST_BASE: u64 = 1_000;
+/// Gas cost of each item read from state.
+pub const GAS_COST_READ: u64 = 2_000;
+/// Gas cost of each item written to state.
+pub const GAS_COST_WRITE: u64 = 10_000;
+/// Gas cost of each 32-byte word of output.
+pub const GAS_COST_OUTPUT_WORD: u64 = 10;
+
+/// Methods of the paymaster precompile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Method {
+    Configure,
+    SetAllowed,
+    Withdraw,
+    Paymaster,
+    IsAllowed,
+}
+
+impl abi::Method for Method {
+    const ALL: &'static [Self] = &[
+        Self::Configure,
+        Self::SetAllowed,
+        Self::Withdraw,
+        Self::Paymaster,
+        Self::IsAllowed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Configure => "configure",
+            Self::SetAllowed => "setAllowed",
+            Self::Withdraw => "withdraw",
+            Self::Paymaster => "paymaster",
+            Self::IsAllowed => "isAllowed",
+        }
+    }
+
+    fn params(self) -> Vec<ParamType> {
+        match self {
+            Self::Configure => vec![ParamType::Uint(128)],
+            Self::SetAllowed => vec![ParamType::Address, ParamType::Bool],
+            Self::Withdraw => vec![ParamType::FixedBytes(21), ParamType::Uint(128)],
+            Self::Paymaster => vec![ParamType::Address],
+            Self::IsAllowed => vec![ParamType::Address, ParamType::Address],
+        }
+    }
+}
+
+impl Method {
+    /// Whether the method modifies the configuration of the calling contract.
+    fn is_mutating(self) -> bool {
+        matches!(self, Self::Configure | Self::SetAllowed | Self::Withdraw)
+    }
+}
+
+/// Execute the paymaster precompile.
+pub(super) fn call_paymaster(handle: &mut impl PrecompileHandle) -> PrecompileResult {
+    handle.record_cost(GAS_COST_BASE)?;
+
+    let (method, args) = abi::decode_call::<Method>(handle.input(), ABI_VERSION)?;
+    if method.is_mutating() {
+        if handle.is_static() {
+            return Err(abi::revert("static call not allowed"));
+        }
+        if handle.context().address != handle.code_address() {
+            return Err(abi::revert("delegate call not allowed"));
+        }
+    }
+    let contract = handle.context().caller;
+
+    // The EVM backend runs each call frame in its own state transaction, which is only committed
+    // when the frame succeeds.
+    let output =
+        CurrentState::with_transaction(|| match dispatch(handle, method, contract, &args) {
+            Ok(output) => TransactionResult::Commit(Ok(output)),
+            Err(err) => TransactionResult::Rollback(Err(err)),
+        })?;
+    abi::record_output_cost(handle, &output, GAS_COST_OUTPUT_WORD)?;
+
+    Ok(PrecompileOutput {
+        exit_status: ExitSucceed::Returned,
+        output,
+    })
+}
+
+/// Execute the given method on behalf of the given calling contract.
+fn dispatch(
+    handle: &mut impl PrecompileHandle,
+    method: Method,
+    contract: H160,
+    args: &[Token],
+) -> Result<Vec<u8>, PrecompileFailure> {
+    let output = match method {
+        Method::Configure => {
+            handle.record_cost(GAS_COST_READ)?;
+            if !is_contract(contract) {
+                return Err(abi::revert("caller is not a contract"));
+            }
+            handle.record_cost(GAS_COST_WRITE)?;
+            paymaster::set_paymaster(
+                contract,
+                Paymaster {
+                    max_fee_per_call: decode_u128(&args[0])?,
+                },
+            );
+            vec![]
+        }
+        Method::SetAllowed => {
+            handle.record_cost(GAS_COST_WRITE)?;
+            let caller = decode_eth_address(&args[0]);
+            let allowed = args[1].clone().into_bool().unwrap_or_default();
+            paymaster::set_allowed(contract, caller, allowed);
+            vec![]
+        }
+        Method::Withdraw => {
+            handle.record_cost(GAS_COST_WRITE)?;
+            let to = decode_address(&args[0])?;
+            let amount = BaseUnits::new(decode_u128(&args[1])?, Denomination::NATIVE);
+            accounts::Module::transfer(paymaster::deposit_address(contract), to, &amount)
+                .map_err(|_| abi::revert("insufficient balance"))?;
+            vec![]
+        }
+        Method::Paymaster => {
+            handle.record_cost(GAS_COST_READ.saturating_mul(2))?;
+            let contract = args[0].clone().into_address().unwrap_or_default();
+            ethabi::encode(&encode_paymaster(contract))
+        }
+        Method::IsAllowed => {
+            handle.record_cost(GAS_COST_READ)?;
+            let contract = args[0].clone().into_address().unwrap_or_default();
+            let caller = decode_eth_address(&args[1]);
+            ethabi::encode(&[Token::Bool(paymaster::is_allowed(contract, caller))])
+        }
+    };
+    Ok(output)
+}
+
+/// Whether code is deployed at the given address.
+fn is_contract(address: H160) -> bool {
+    CurrentState::with_store(|store| {
+        module_evm::state::codes(store)
+            .get::<_, Vec<u8>>(address.as_bytes())
+            .is_some_and(|code| !code.is_empty())
+    })
+}
+
+fn decode_u128(token: &Token) -> Result<u128, PrecompileFailure> {
+    let value = token.clone().into_uint().unwrap_or_default();
+    u128::try_from(value).map_err(|_| abi::revert("amount out of range"))
+}
+
+fn decode_eth_address(token: &Token) -> Address {
+    let address = token.clone().into_address().unwrap_or_default();
+    Address::from_eth(address.as_bytes())
+}
+
+fn decode_address(token: &Token) -> Result<Address, PrecompileFailure> {
+    let raw = token.clone().into_fixed_bytes().unwrap_or_default();
+    Address::from_bytes(&raw).map_err(|_| abi::revert("malformed address"))
+}
+
+fn encode_paymaster(contract: H160) -> Vec<Token> {
+    let registered = paymaster::get_paymaster(contract);
+    let deposit_address = paymaster::deposit_address(contract);
+    let balance =
+        accounts::Module::get_balance(deposit_address, Denomination::NATIVE).unwrap_or_default();
+    let config = registered.clone().unwrap_or_default();
+
+    vec![
+        Token::Bool(registered.is_some()),
+        Token::FixedBytes(deposit_address.as_ref().to_vec()),
+        uint(balance),
+        uint(config.max_fee_per_call),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{abi::Method as _, *};
+
+    #[test]
+    fn test_selectors() {
+        let cases = [
+            (Method::Configure, "d95f6282"),
+            (Method::SetAllowed, "3d9891a6"),
+            (Method::Withdraw, "9beb1787"),
+            (Method::Paymaster, "8e9622e8"),
+            (Method::IsAllowed, "ecb5cdde"),
+        ];
+        for (method, selector) in cases {
+            assert_eq!(hex::encode(method.selector()), selector, "{method:?}");
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! ROFL application query precompile.
+//!
+//! Exposes the configuration of ROFL applications to contracts, ABI-encoded according to the
+//! following interface (see [`super::abi`] for the calling convention):
+//!
+//! ```solidity
+//! interface IRoflAppV1 {
+//!     // Endorsement policy tree node in pre-order, followed by its children.
+//!     struct Endorsement { uint8 kind; bytes value; uint32 children; }
+//!
+//!     function app(uint16 version, bytes21 appId) external view returns (
+//!         bytes21 admin, string memory stakeDenomination, uint128 stakeAmount,
+//!         string[] memory metadataKeys, string[] memory metadataValues
+//!     );
+//!     function policy(uint16 version, bytes21 appId) external view returns (
+//!         Endorsement[] memory endorsements, bytes32[] memory mrEnclaves,
+//!         bytes32[] memory mrSigners, uint8 feePolicy, uint64 maxExpiration
+//!     );
+//!     function instances(uint16 version, bytes21 appId) external view returns (
+//!         uint64 count, uint64 distinctProviders
+//!     );
+//! }
+//! ```
+//!
+//! Endorsement kinds are encoded as 0 (any), 1 (compute role), 2 (observer role), 3 (entity),
+//! 4 (node), 5 (provider), 6 (provider instance admin), 7 (and) and 8 (or). Entity and node
+//! endorsements carry the public key as their value, provider endorsements the provider address.
+//! Fee policies are encoded as 1 (instance pays) and 2 (endorsing node pays). Applications
+//! without an admin have a zero admin address. Querying an unknown application reverts.
+//!
+//! Instances are counted as registrations of the application, charging a state read for each.
+//! Distinct providers are the ROFL market providers named by the endorsement policy that list
+//! the endorsing node of at least one registered instance.
+use std::collections::{BTreeMap, BTreeSet};
+
+use ethabi::{ParamType, Token};
+use evm::{
+    executor::stack::{PrecompileFailure, PrecompileHandle, PrecompileOutput, PrecompileResult},
+    ExitSucceed,
+};
+use oasis_runtime_sdk::{
+    core::common::crypto::signature::PublicKey,
+    modules::rofl::{
+        self,
+        app_id::AppId,
+        policy::{AllowedEndorsement, FeePolicy},
+        state,
+        types::{AppConfig, Registration},
+    },
+    state::CurrentState,
+    storage,
+    types::address::Address,
+};
+use primitive_types::H160;
+
+use super::abi::{self, uint};
+
+/// Address of the query precompile.
+pub const QUERY_ADDRESS: H160 = super::sapphire_address(0x0302);
+
+/// Current version of the ABI.
+pub const ABI_VERSION: u16 = 1;
+
+/// Base gas cost of a query.
+pub const GAS_COST_BASE: u64 = 1_000;
+/// Gas cost of each item read from state.
+pub const GAS_COST_READ: u64 = 2_000;
+/// Gas cost of each 32-byte word of output.
+pub const GAS_COST_OUTPUT_WORD: u64 = 10;
+
+/// Methods of the query precompile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Method {
+    App,
+    Policy,
+    Instances,
+}
+
+impl abi::Method for Method {
+    const ALL: &'static [Self] = &[Self::App, Self::Policy, Self::Instances];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::App => "app",
+            Self::Policy => "policy",
+            Self::Instances => "instances",
+        }
+    }
+
+    fn params(self) -> Vec<ParamType> {
+        vec![ParamType::FixedBytes(21)]
+    }
+}
+
+/// Execute the query precompile.
+pub(super) fn call_query(handle: &mut impl PrecompileHandle) -> PrecompileResult {
+    handle.record_cost(GAS_COST_BASE)?;
+
+    let (method, args) = abi::decode_call::<Method>(handle.input(), ABI_VERSION)?;
+    let app_id = decode_app_id(&args[0])?;
+
+    handle.record_cost(GAS_COST_READ)?;
+    let app = state::get_app(app_id).ok_or_else(|| abi::revert("application not found"))?;
+
+    let output = match method {
+        Method::App => ethabi::encode(&encode_app(&app)),
+        Method::Policy => ethabi::encode(&encode_policy(&app)),
+        Method::Instances => {
+            let nodes = registered_nodes(handle, app_id)?;
+            let count = nodes.values().sum::<u64>();
+
+            let mut providers = BTreeSet::new();
+            for endorsement in &app.policy.endorsements {
+                named_providers(&mut providers, endorsement);
+            }
+            let mut distinct = 0u64;
+            for provider in providers {
+                handle.record_cost(GAS_COST_READ)?;
+                let Some(provider) = module_rofl_market::state::get_provider(provider) else {
+                    continue;
+                };
+                if provider.nodes.iter().any(|node| nodes.contains_key(node)) {
+                    distinct += 1;
+                }
+            }
+            ethabi::encode(&[uint(count), uint(distinct)])
+        }
+    };
+    abi::record_output_cost(handle, &output, GAS_COST_OUTPUT_WORD)?;
+
+    Ok(PrecompileOutput {
+        exit_status: ExitSucceed::Returned,
+        output,
+    })
+}
+
+/// Count the application's registrations per endorsing node.
+///
+/// Registrations are iterated directly from the ROFL module's state so that gas is charged for
+/// each one before the next is read.
+fn registered_nodes(
+    handle: &mut impl PrecompileHandle,
+    app_id: AppId,
+) -> Result<BTreeMap<PublicKey, u64>, PrecompileFailure> {
+    /// Prefix of the ROFL module's map of (application, RAK) to registrations.
+    const REGISTRATIONS: &[u8] = &[0x02];
+
+    CurrentState::with_store(|store| {
+        let store = storage::PrefixStore::new(store, &rofl::MODULE_NAME);
+        let registrations = storage::PrefixStore::new(store, &REGISTRATIONS);
+        let app = storage::TypedStore::new(storage::PrefixStore::new(registrations, app_id));
+
+        let mut nodes = BTreeMap::new();
+        for (_, registration) in app.iter::<Vec<u8>, Registration>() {
+            handle.record_cost(GAS_COST_READ)?;
+            *nodes.entry(registration.node_id).or_default() += 1;
+        }
+        Ok(nodes)
+    })
+}
+
+/// Collect the provider addresses named in the given endorsement policy tree.
+fn named_providers(output: &mut BTreeSet<Address>, endorsement: &AllowedEndorsement) {
+    match endorsement {
+        AllowedEndorsement::Provider(address)
+        | AllowedEndorsement::ProviderInstanceAdmin(address) => {
+            output.insert(*address);
+        }
+        AllowedEndorsement::And(children) | AllowedEndorsement::Or(children) => {
+            for child in children {
+                named_providers(output, child);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn decode_app_id(token: &Token) -> Result<AppId, PrecompileFailure> {
+    match token.clone().into_fixed_bytes() {
+        Some(raw) if raw.len() == 21 => Ok(AppId::from(&raw[..])),
+        _ => Err(abi::revert("malformed application identifier")),
+    }
+}
+
+fn encode_app(app: &AppConfig) -> Vec<Token> {
+    let admin = app
+        .admin
+        .map(|admin| admin.as_ref().to_vec())
+        .unwrap_or_else(|| vec![0; 21]);
+    let [keys, values] = abi::string_map(&app.metadata);
+
+    vec![
+        Token::FixedBytes(admin),
+        Token::String(app.stake.denomination().to_string()),
+        uint(app.stake.amount()),
+        keys,
+        values,
+    ]
+}
+
+fn encode_policy(app: &AppConfig) -> Vec<Token> {
+    let policy = &app.policy;
+
+    let mut endorsements = Vec::new();
+    for endorsement in &policy.endorsements {
+        encode_endorsement(&mut endorsements, endorsement);
+    }
+    let (mr_enclaves, mr_signers) = policy
+        .enclaves
+        .iter()
+        .map(|enclave| {
+            (
+                Token::FixedBytes(enclave.mr_enclave.as_ref().to_vec()),
+                Token::FixedBytes(enclave.mr_signer.as_ref().to_vec()),
+            )
+        })
+        .unzip();
+    let fee_policy: u8 = match policy.fees {
+        FeePolicy::InstancePays => 1,
+        FeePolicy::EndorsingNodePays => 2,
+    };
+
+    vec![
+        Token::Array(endorsements),
+        Token::Array(mr_enclaves),
+        Token::Array(mr_signers),
+        uint(fee_policy),
+        uint(policy.max_expiration),
+    ]
+}
+
+/// Append the given endorsement policy tree in pre-order.
+fn encode_endorsement(output: &mut Vec<Token>, endorsement: &AllowedEndorsement) {
+    let (kind, value, children): (u8, Vec<u8>, &[AllowedEndorsement]) = match endorsement {
+        AllowedEndorsement::Any => (0, vec![], &[]),
+        AllowedEndorsement::ComputeRole => (1, vec![], &[]),
+        AllowedEndorsement::ObserverRole => (2, vec![], &[]),
+        AllowedEndorsement::Entity(id) => (3, id.as_ref().to_vec(), &[]),
+        AllowedEndorsement::Node(id) => (4, id.as_ref().to_vec(), &[]),
+        AllowedEndorsement::Provider(address) => (5, address.as_ref().to_vec(), &[]),
+        AllowedEndorsement::ProviderInstanceAdmin(address) => (6, address.as_ref().to_vec(), &[]),
+        AllowedEndorsement::And(children) => (7, vec![], children),
+        AllowedEndorsement::Or(children) => (8, vec![], children),
+    };
+
+    output.push(Token::Tuple(vec![
+        uint(kind),
+        Token::Bytes(value),
+        uint(children.len() as u32),
+    ]));
+    for child in children {
+        encode_endorsement(output, child);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{abi::Method as _, *};
+
+    #[test]
+    fn test_selectors() {
+        let cases = [
+            (Method::App, "d8bb11f6"),
+            (Method::Policy, "0f080793"),
+            (Method::Instances, "57fbd6e2"),
+        ];
+        for (method, selector) in cases {
+            assert_eq!(hex::encode(method.selector()), selector, "{method:?}");
+        }
+    }
+}
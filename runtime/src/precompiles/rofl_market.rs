@@ -0,0 +1,272 @@
+//! ROFL market query precompile.
+//!
+//! Exposes the state of ROFL market instances to contracts, ABI-encoded according to the following
+//! interface (see [`super::abi`] for the calling convention):
+//!
+//! ```solidity
+//! interface IRoflMarketV1 {
+//!     struct Resources { uint8 tee; uint64 memoryMiB; uint16 cpus; uint64 storageMiB; }
+//!     // Native payments have a zero EVM contract address and no data, contract payments an empty
+//!     // denomination and no terms.
+//!     struct Payment {
+//!         address evmContract; bytes evmData; string denomination; uint8[] terms; uint128[] prices;
+//!     }
+//!     struct Offer { bytes8 id; uint64 capacity; Resources resources; Payment payment; }
+//!
+//!     function offers(uint16 version, bytes21 provider) external view returns (Offer[] memory);
+//!     function instance(uint16 version, bytes21 provider, bytes8 id) external view returns (
+//!         uint8 status, bytes8 offer, bytes21 creator, bytes21 admin, bytes32 nodeId,
+//!         uint64 createdAt, uint64 updatedAt
+//!     );
+//!     function deployment(uint16 version, bytes21 provider, bytes8 id) external view returns (
+//!         bool deployed, bytes21 appId, bytes32 manifestHash, string[] memory metadataKeys,
+//!         string[] memory metadataValues
+//!     );
+//!     function paymentState(uint16 version, bytes21 provider, bytes8 id) external view returns (
+//!         Payment memory payment, uint64 paidFrom, uint64 paidUntil
+//!     );
+//! }
+//! ```
+//!
+//! Instance statuses are encoded as 0 (created), 1 (accepted) and 2 (cancelled), TEE types as
+//! 1 (SGX) and 2 (TDX) and payment terms as 1 (hour), 2 (month) and 3 (year). The node ID of an
+//! instance that has not been accepted yet is zero. Querying an unknown instance reverts.
+use ethabi::{ParamType, Token};
+use evm::{
+    executor::stack::{PrecompileFailure, PrecompileHandle, PrecompileOutput, PrecompileResult},
+    ExitSucceed,
+};
+use module_rofl_market::{
+    state,
+    types::{Instance, InstanceId, InstanceStatus, Offer, Payment, Resources, TeeType, Term},
+};
+use oasis_runtime_sdk::types::address::Address;
+use primitive_types::H160;
+
+use super::abi::{self, uint};
+
+/// Address of the query precompile.
+pub const QUERY_ADDRESS: H160 = super::sapphire_address(0x0301);
+
+/// Current version of the ABI.
+pub const ABI_VERSION: u16 = 1;
+
+/// Base gas cost of a query.
+pub const GAS_COST_BASE: u64 = 1_000;
+/// Gas cost of each item read from state.
+pub const GAS_COST_READ: u64 = 2_000;
+/// Gas cost of each 32-byte word of output.
+pub const GAS_COST_OUTPUT_WORD: u64 = 10;
+
+/// Methods of the query precompile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Method {
+    Offers,
+    Instance,
+    Deployment,
+    PaymentState,
+}
+
+impl abi::Method for Method {
+    const ALL: &'static [Self] = &[
+        Self::Offers,
+        Self::Instance,
+        Self::Deployment,
+        Self::PaymentState,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Offers => "offers",
+            Self::Instance => "instance",
+            Self::Deployment => "deployment",
+            Self::PaymentState => "paymentState",
+        }
+    }
+
+    fn params(self) -> Vec<ParamType> {
+        match self {
+            Self::Offers => vec![ParamType::FixedBytes(21)],
+            Self::Instance | Self::Deployment | Self::PaymentState => {
+                vec![ParamType::FixedBytes(21), ParamType::FixedBytes(8)]
+            }
+        }
+    }
+}
+
+/// Execute the query precompile.
+pub(super) fn call_query(handle: &mut impl PrecompileHandle) -> PrecompileResult {
+    handle.record_cost(GAS_COST_BASE)?;
+
+    let (method, args) = abi::decode_call::<Method>(handle.input(), ABI_VERSION)?;
+    let provider = decode_address(&args[0])?;
+
+    handle.record_cost(GAS_COST_READ)?;
+    let output = match method {
+        Method::Offers => {
+            let offers = state::get_offers(provider);
+            handle.record_cost(GAS_COST_READ.saturating_mul(offers.len() as u64))?;
+
+            let offers = offers.iter().map(encode_offer).collect();
+            ethabi::encode(&[Token::Array(offers)])
+        }
+        Method::Instance => {
+            let instance = get_instance(provider, &args[1])?;
+            ethabi::encode(&encode_instance(&instance))
+        }
+        Method::Deployment => {
+            let instance = get_instance(provider, &args[1])?;
+            ethabi::encode(&encode_deployment(&instance))
+        }
+        Method::PaymentState => {
+            let instance = get_instance(provider, &args[1])?;
+            ethabi::encode(&[
+                encode_payment(&instance.payment),
+                uint(instance.paid_from),
+                uint(instance.paid_until),
+            ])
+        }
+    };
+    abi::record_output_cost(handle, &output, GAS_COST_OUTPUT_WORD)?;
+
+    Ok(PrecompileOutput {
+        exit_status: ExitSucceed::Returned,
+        output,
+    })
+}
+
+fn decode_address(token: &Token) -> Result<Address, PrecompileFailure> {
+    let raw = token.clone().into_fixed_bytes().unwrap_or_default();
+    Address::from_bytes(&raw).map_err(|_| abi::revert("malformed address"))
+}
+
+fn get_instance(provider: Address, id: &Token) -> Result<Instance, PrecompileFailure> {
+    let raw: [u8; 8] = id
+        .clone()
+        .into_fixed_bytes()
+        .and_then(|raw| raw.try_into().ok())
+        .ok_or_else(|| abi::revert("malformed instance identifier"))?;
+
+    state::get_instance(provider, InstanceId::from(raw))
+        .ok_or_else(|| abi::revert("instance not found"))
+}
+
+fn encode_offer(offer: &Offer) -> Token {
+    Token::Tuple(vec![
+        Token::FixedBytes(offer.id.as_ref().to_vec()),
+        uint(offer.capacity),
+        encode_resources(&offer.resources),
+        encode_payment(&offer.payment),
+    ])
+}
+
+fn encode_resources(resources: &Resources) -> Token {
+    let tee: u8 = match resources.tee {
+        TeeType::SGX => 1,
+        TeeType::TDX => 2,
+    };
+    Token::Tuple(vec![
+        uint(tee),
+        uint(resources.memory),
+        uint(resources.cpus),
+        uint(resources.storage),
+    ])
+}
+
+fn encode_payment(payment: &Payment) -> Token {
+    match payment {
+        Payment::Native {
+            denomination,
+            terms,
+        } => {
+            let (terms, prices) = terms
+                .iter()
+                .map(|(term, price)| (uint(encode_term(term)), uint(*price)))
+                .unzip();
+            Token::Tuple(vec![
+                Token::Address(H160::zero()),
+                Token::Bytes(vec![]),
+                Token::String(denomination.to_string()),
+                Token::Array(terms),
+                Token::Array(prices),
+            ])
+        }
+        Payment::EvmContract { address, data } => Token::Tuple(vec![
+            Token::Address(H160::from_slice(address.as_bytes())),
+            Token::Bytes(data.clone()),
+            Token::String(String::new()),
+            Token::Array(vec![]),
+            Token::Array(vec![]),
+        ]),
+    }
+}
+
+fn encode_term(term: &Term) -> u8 {
+    match term {
+        Term::Hour => 1,
+        Term::Month => 2,
+        Term::Year => 3,
+    }
+}
+
+fn encode_instance(instance: &Instance) -> Vec<Token> {
+    let status: u8 = match instance.status {
+        InstanceStatus::Created => 0,
+        InstanceStatus::Accepted => 1,
+        InstanceStatus::Cancelled => 2,
+    };
+    let node_id = instance
+        .node_id
+        .as_ref()
+        .map(|node_id| node_id.as_ref().to_vec())
+        .unwrap_or_else(|| vec![0; 32]);
+
+    vec![
+        uint(status),
+        Token::FixedBytes(instance.offer.as_ref().to_vec()),
+        Token::FixedBytes(instance.creator.as_ref().to_vec()),
+        Token::FixedBytes(instance.admin.as_ref().to_vec()),
+        Token::FixedBytes(node_id),
+        uint(instance.created_at),
+        uint(instance.updated_at),
+    ]
+}
+
+fn encode_deployment(instance: &Instance) -> Vec<Token> {
+    let Some(deployment) = &instance.deployment else {
+        return vec![
+            Token::Bool(false),
+            Token::FixedBytes(vec![0; 21]),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Array(vec![]),
+            Token::Array(vec![]),
+        ];
+    };
+
+    let [keys, values] = abi::string_map(&deployment.metadata);
+    vec![
+        Token::Bool(true),
+        Token::FixedBytes(deployment.app_id.as_ref().to_vec()),
+        Token::FixedBytes(deployment.manifest_hash.as_ref().to_vec()),
+        keys,
+        values,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{abi::Method as _, *};
+
+    #[test]
+    fn test_selectors() {
+        let cases = [
+            (Method::Offers, "b8760fb2"),
+            (Method::Instance, "da1d9128"),
+            (Method::Deployment, "36a524c3"),
+            (Method::PaymentState, "8426f8f3"),
+        ];
+        for (method, selector) in cases {
+            assert_eq!(hex::encode(method.selector()), selector, "{method:?}");
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Reward schedule curves.
+//!
+//! Genesis files can declare the reward schedule as a curve which the build script expands into
+//! reward schedule steps. This module is shared with the build script so it must not depend on
+//! any other crate.
+
+/// A step of a reward schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// Epoch until which (exclusive) the step applies.
+    pub until: u64,
+    /// Reward amount per epoch, in base units.
+    pub amount: u128,
+}
+
+/// Declarative reward curve starting at a given epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// Reward halving after every `period` epochs, for the given number of steps.
+    Halving {
+        amount: u128,
+        period: u64,
+        steps: u32,
+    },
+    /// Reward multiplied by `numerator / denominator` after every `period` epochs, for the given
+    /// number of steps.
+    ExponentialDecay {
+        amount: u128,
+        period: u64,
+        steps: u32,
+        numerator: u64,
+        denominator: u64,
+    },
+}
+
+impl Curve {
+    /// Expand the curve starting at the given epoch into schedule steps.
+    ///
+    /// Expansion stops early once the reward reaches zero.
+    pub fn expand(&self, start: u64) -> Result<Vec<Step>, String> {
+        let (amount, period, steps) = match *self {
+            Self::Halving {
+                amount,
+                period,
+                steps,
+            } => (amount, period, steps),
+            Self::ExponentialDecay {
+                amount,
+                period,
+                steps,
+                numerator,
+                denominator,
+            } => {
+                if denominator == 0 || numerator >= denominator {
+                    return Err("decay factor must be less than one".to_string());
+                }
+                (amount, period, steps)
+            }
+        };
+        if period == 0 {
+            return Err("period must be non-zero".to_string());
+        }
+        if steps == 0 {
+            return Err("number of steps must be non-zero".to_string());
+        }
+
+        let mut schedule = Vec::new();
+        let mut amount = amount;
+        let mut until = start;
+        for _ in 0..steps {
+            if amount == 0 {
+                break;
+            }
+            until = until
+                .checked_add(period)
+                .ok_or("schedule exceeds the maximum epoch")?;
+            schedule.push(Step { until, amount });
+
+            amount = match *self {
+                Self::Halving { .. } => amount / 2,
+                Self::ExponentialDecay {
+                    numerator,
+                    denominator,
+                    ..
+                } => {
+                    amount
+                        .checked_mul(numerator.into())
+                        .ok_or("reward amount overflow")?
+                        / u128::from(denominator)
+                }
+            };
+        }
+        Ok(schedule)
+    }
+}
+
+/// Make sure the steps of the schedule are strictly increasing and after the start epoch.
+pub fn validate(start: u64, steps: &[Step]) -> Result<(), String> {
+    let mut previous = start;
+    for step in steps {
+        if step.until <= previous {
+            return Err(format!(
+                "steps must be strictly increasing (epoch {} follows {previous})",
+                step.until,
+            ));
+        }
+        previous = step.until;
+    }
+    Ok(())
+}
+
+/// Total amount emitted by the schedule starting at the given epoch, i.e. the per-epoch reward
+/// amounts summed over all epochs.
+///
+/// Returns `None` in case the emission overflows.
+pub fn emission(start: u64, steps: &[Step]) -> Option<u128> {
+    let mut total = 0u128;
+    let mut previous = start;
+    for step in steps {
+        let epochs = step.until.saturating_sub(previous);
+        total = total.checked_add(step.amount.checked_mul(epochs.into())?)?;
+        previous = previous.max(step.until);
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_halving() {
+        let curve = Curve::Halving {
+            amount: 100,
+            period: 10,
+            steps: 10,
+        };
+        let steps = curve.expand(5).unwrap();
+        let step = |until, amount| Step { until, amount };
+        assert_eq!(
+            steps,
+            vec![
+                step(15, 100),
+                step(25, 50),
+                step(35, 25),
+                step(45, 12),
+                step(55, 6),
+                step(65, 3),
+                step(75, 1),
+            ],
+            "expansion should stop once the reward reaches zero"
+        );
+        assert_eq!(validate(5, &steps), Ok(()));
+        assert_eq!(emission(5, &steps), Some(1_970));
+    }
+
+    #[test]
+    fn test_exponential_decay() {
+        let curve = Curve::ExponentialDecay {
+            amount: 1_000,
+            period: 100,
+            steps: 3,
+            numerator: 9,
+            denominator: 10,
+        };
+        let steps = curve.expand(0).unwrap();
+        let amounts: Vec<_> = steps.iter().map(|step| step.amount).collect();
+        assert_eq!(amounts, vec![1_000, 900, 810]);
+        assert_eq!(steps.last().unwrap().until, 300);
+        assert_eq!(emission(0, &steps), Some(271_000));
+
+        let invalid = [
+            Curve::ExponentialDecay {
+                amount: 1,
+                period: 1,
+                steps: 1,
+                numerator: 1,
+                denominator: 1,
+            },
+            Curve::ExponentialDecay {
+                amount: 1,
+                period: 1,
+                steps: 1,
+                numerator: 0,
+                denominator: 0,
+            },
+            Curve::Halving {
+                amount: 1,
+                period: 0,
+                steps: 1,
+            },
+            Curve::Halving {
+                amount: 1,
+                period: 1,
+                steps: 0,
+            },
+            Curve::Halving {
+                amount: 1,
+                period: u64::MAX,
+                steps: 2,
+            },
+        ];
+        for curve in invalid {
+            assert!(curve.expand(1).is_err(), "{curve:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let step = |until| Step { until, amount: 1 };
+        assert_eq!(validate(0, &[]), Ok(()));
+        assert_eq!(validate(0, &[step(1), step(2)]), Ok(()));
+        assert!(validate(0, &[step(2), step(2)]).is_err());
+        assert!(validate(0, &[step(2), step(1)]).is_err());
+        assert!(validate(5, &[step(5)]).is_err());
+    }
+
+    #[test]
+    fn test_emission_overflow() {
+        let steps = [Step {
+            until: 2,
+            amount: u128::MAX,
+        }];
+        assert_eq!(emission(0, &steps), None);
+    }
+}
@@ -0,0 +1,1550 @@
+//! Tests of the runtime configuration using the SDK mock environment.
+use oasis_runtime_sdk::{
+    context::RuntimeBatchContext,
+    module::TransactionHandler as _,
+    modules::{
+        self,
+        accounts::{self, API as _},
+    },
+    state::{CurrentState, TransactionResult},
+    testing::{
+        keys,
+        mock::{CallOptions, Mock, Signer},
+    },
+    types::{
+        address::Address,
+        token::{BaseUnits, Denomination},
+        transaction::{Fee, Transaction},
+    },
+    Module as _, Runtime as _,
+};
+
+use crate::{
+    endorsement, gas_policy, governance, lanes, migrations,
+    network::{NetworkProfile, NETWORK},
+    pause, paymaster, precompiles, rewards, Config, Runtime,
+};
+
+/// Gas price that satisfies the genesis minimum gas price.
+const GAS_PRICE: u128 = 100_000_000_000;
+
+/// Init code of a contract whose runtime code returns the 32-byte word 42.
+const RETURN_42_INIT_CODE: &str = "600a600c600039600a6000f3602a60005260206000f3";
+
+/// Call options paying for the given amount of gas at [`GAS_PRICE`].
+fn call_options(gas: u64) -> CallOptions {
+    CallOptions {
+        fee: Fee {
+            amount: BaseUnits::new(u128::from(gas) * GAS_PRICE, Denomination::NATIVE),
+            gas,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Create a confidential context with the migrated genesis state and the given accounts funded.
+fn setup<'a>(mock: &'a mut Mock, funded: &[Address]) -> RuntimeBatchContext<'a, Runtime> {
+    let ctx = mock.create_ctx_for_runtime::<Runtime>(true);
+    Runtime::migrate(&ctx);
+    for &address in funded {
+        fund(address);
+    }
+    ctx
+}
+
+/// Fund the given account with 1000 native tokens.
+fn fund(address: Address) {
+    accounts::Module::mint(
+        address,
+        &BaseUnits::new(1_000_000_000_000_000_000_000, Denomination::NATIVE),
+    )
+    .expect("minting should succeed");
+}
+
+#[test]
+fn test_chain_id() {
+    let expected = match NETWORK {
+        NetworkProfile::Mainnet => 0x5afe,
+        NetworkProfile::Testnet => 0x5aff,
+        NetworkProfile::Localnet => 0x5afd,
+        NetworkProfile::Custom { chain_id } => chain_id,
+    };
+    assert_eq!(<Config as module_evm::Config>::CHAIN_ID, expected);
+}
+
+#[test]
+fn test_genesis() {
+    let mut mock = Mock::default();
+    let _ctx = setup(&mut mock, &[]);
+
+    let genesis = Runtime::genesis_state();
+    assert_eq!(
+        cbor::to_vec(modules::core::Module::<Config>::params()),
+        cbor::to_vec(genesis.0.parameters),
+        "core parameters should be initialized from genesis"
+    );
+    assert_eq!(
+        cbor::to_vec(modules::consensus_accounts::Module::<
+            modules::consensus::Module,
+        >::params()),
+        cbor::to_vec(genesis.3.parameters),
+        "consensus_accounts parameters should be initialized from genesis"
+    );
+    assert_eq!(
+        cbor::to_vec(modules::rewards::Module::params()),
+        cbor::to_vec(genesis.4.parameters),
+        "rewards parameters should be initialized from genesis"
+    );
+    assert_eq!(
+        migrations::recorded_state_version(),
+        Runtime::STATE_VERSION,
+        "state version should be recorded"
+    );
+}
+
+#[test]
+fn test_rewards_projected_emission() {
+    for network in [
+        NetworkProfile::Mainnet,
+        NetworkProfile::Testnet,
+        NetworkProfile::Localnet,
+    ] {
+        let steps: Vec<_> = network
+            .genesis_state()
+            .4
+            .parameters
+            .schedule
+            .steps
+            .iter()
+            .map(|step| rewards::Step {
+                until: step.until,
+                amount: step.amount.amount(),
+            })
+            .collect();
+        rewards::validate(0, &steps).expect("reward schedule should be valid");
+        let emission = rewards::emission(0, &steps).expect("emission should not overflow");
+
+        println!(
+            "{}: {} reward steps until epoch {}, projected emission of {} tokens",
+            network.name(),
+            steps.len(),
+            steps.last().map(|step| step.until).unwrap_or_default(),
+            emission / 1_000_000_000_000_000_000,
+        );
+    }
+}
+
+#[test]
+fn test_migration_registry() {
+    for network in [
+        NetworkProfile::Mainnet,
+        NetworkProfile::Testnet,
+        NetworkProfile::Localnet,
+        NetworkProfile::Custom { chain_id: 0x1234 },
+    ] {
+        let version = network.state_version();
+        let steps = migrations::plan(migrations::registry(network), version - 1, version)
+            .expect("migration to the current version should be registered");
+        assert_eq!(
+            steps.len(),
+            1,
+            "{} should migrate to the current version",
+            network.name()
+        );
+    }
+}
+
+#[test]
+fn test_migration_missing_step() {
+    let steps = migrations::registry(NETWORK);
+    assert_eq!(
+        migrations::plan(steps, 0, Runtime::STATE_VERSION).unwrap_err(),
+        migrations::Error::MissingStep(0)
+    );
+    assert_eq!(
+        migrations::plan(steps, Runtime::STATE_VERSION, Runtime::STATE_VERSION - 1).unwrap_err(),
+        migrations::Error::Downgrade {
+            from: Runtime::STATE_VERSION,
+            to: Runtime::STATE_VERSION - 1
+        }
+    );
+}
+
+#[test]
+fn test_migrate_state() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[]);
+
+    // Simulate state from the previous version with outdated parameters.
+    let mut params = modules::core::Module::<Config>::params();
+    params.max_batch_gas = 1_000;
+    modules::core::Module::<Config>::set_params(params);
+    migrations::set_recorded_state_version(Runtime::STATE_VERSION - 1);
+
+    // Upgrade through the SDK, which runs the registered step.
+    Runtime::migrate(&ctx);
+
+    assert_eq!(
+        modules::core::Module::<Config>::params().max_batch_gas,
+        Runtime::genesis_state().0.parameters.max_batch_gas,
+        "parameters should be updated from genesis"
+    );
+    assert_eq!(
+        migrations::recorded_state_version(),
+        Runtime::STATE_VERSION,
+        "state version should be bumped"
+    );
+}
+
+/// Test migration step raising the batch gas limit to 20M.
+fn raise_batch_gas() {
+    let mut params = modules::core::Module::<Config>::params();
+    params.max_batch_gas = 20_000_000;
+    modules::core::Module::<Config>::set_params(params);
+}
+
+/// Test migration step doubling the batch gas limit and the maximum transaction size.
+fn double_limits() {
+    let mut params = modules::core::Module::<Config>::params();
+    params.max_batch_gas *= 2;
+    params.max_tx_size *= 2;
+    modules::core::Module::<Config>::set_params(params);
+}
+
+#[test]
+fn test_migrate_steps() {
+    const STEPS: &[migrations::Migration] = &[
+        migrations::Migration {
+            from: 1,
+            description: "raise the batch gas limit",
+            migrate: raise_batch_gas,
+        },
+        migrations::Migration {
+            from: 2,
+            description: "double the limits",
+            migrate: double_limits,
+        },
+    ];
+
+    let mut mock = Mock::default();
+    let _ctx = setup(&mut mock, &[]);
+    let genesis = Runtime::genesis_state().0.parameters;
+
+    // Nothing changes if a step is missing.
+    assert_eq!(
+        migrations::migrate_with(STEPS, 1, 4),
+        Err(migrations::Error::MissingStep(3))
+    );
+    assert_eq!(
+        modules::core::Module::<Config>::params().max_batch_gas,
+        genesis.max_batch_gas
+    );
+
+    // Steps are applied in order.
+    migrations::migrate_with(STEPS, 1, 3).expect("migration should succeed");
+    let params = modules::core::Module::<Config>::params();
+    assert_eq!(params.max_batch_gas, 40_000_000);
+    assert_eq!(params.max_tx_size, 2 * genesis.max_tx_size);
+
+    // Only the remaining steps are applied to partially migrated state.
+    migrations::migrate_with(STEPS, 2, 3).expect("migration should succeed");
+    assert_eq!(
+        modules::core::Module::<Config>::params().max_batch_gas,
+        80_000_000
+    );
+
+    // State without a recorded version is refreshed from genesis.
+    migrations::migrate_with(STEPS, 0, 3).expect("migration should succeed");
+    assert_eq!(
+        cbor::to_vec(modules::core::Module::<Config>::params()),
+        cbor::to_vec(genesis)
+    );
+}
+
+#[test]
+fn test_accounts_transfer() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::alice::address()]);
+
+    let mut signer = Signer::new(0, keys::alice::sigspec());
+    let result = signer.call_opts(
+        &ctx,
+        "accounts.Transfer",
+        accounts::types::Transfer {
+            to: keys::bob::address(),
+            amount: BaseUnits::new(1_000, Denomination::NATIVE),
+        },
+        call_options(10_000),
+    );
+    assert!(result.result.is_success(), "transfer should succeed");
+
+    let balance = accounts::Module::get_balance(keys::bob::address(), Denomination::NATIVE)
+        .expect("balance query should succeed");
+    assert_eq!(balance, 1_000);
+}
+
+#[test]
+fn test_consensus_deposit() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[]);
+
+    // Deposits are exempt from the minimum gas price so no fee needs to be paid.
+    let mut signer = Signer::new(0, keys::alice::sigspec());
+    let result = signer.call_opts(
+        &ctx,
+        "consensus.Deposit",
+        modules::consensus_accounts::types::Deposit {
+            to: Some(keys::bob::address()),
+            amount: BaseUnits::new(1_000_000_000_000_000_000, Denomination::NATIVE),
+        },
+        CallOptions {
+            fee: Fee {
+                gas: 100_000,
+                consensus_messages: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+    assert!(result.result.is_success(), "deposit should succeed");
+}
+
+#[test]
+fn test_evm_create_call() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let result = signer.call_opts(
+        &ctx,
+        "evm.Create",
+        module_evm::types::Create {
+            value: 0.into(),
+            init_code: hex::decode(RETURN_42_INIT_CODE).unwrap(),
+        },
+        call_options(1_000_000),
+    );
+    assert!(result.result.is_success(), "evm.Create should succeed");
+    let address: Vec<u8> = cbor::from_value(result.result.unwrap()).unwrap();
+
+    let result = signer.call_opts(
+        &ctx,
+        "evm.Call",
+        module_evm::types::Call {
+            address: module_evm::types::H160::from_slice(&address),
+            value: 0.into(),
+            data: vec![],
+        },
+        call_options(1_000_000),
+    );
+    assert!(result.result.is_success(), "evm.Call should succeed");
+    let output: Vec<u8> = cbor::from_value(result.result.unwrap()).unwrap();
+
+    let mut expected = [0u8; 32];
+    expected[31] = 42;
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_p256_verify_precompile() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+
+    // Reference vector from the RIP-7212 specification.
+    let input = hex::decode(concat!(
+        "4cee90eb86eaa050036147a12d49004b6b9c72bd725d39d4785011fe190f0b4d",
+        "a73bd4903f0ce3b639bbbf6e8e80d16931ff4bcf5993d58468e8fb19086e8cac",
+        "36dbcd03009df8c59286b162af3bd7fcc0450c9aa81be5d10d312af6c66b1d60",
+        "4aebd3099c618202fcfe16ae7770b0c49ab5eadf74b754204a3bb6060e44eff3",
+        "7618b065f9832de4ca6ca971a7a1adc826d0f7c00181a5fb2ddf79ae00b4e10e",
+    ))
+    .unwrap();
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let mut expected = [0u8; 32];
+    expected[31] = 1;
+    for (data, expected) in [
+        (input.clone(), &expected[..]),
+        (input[1..].to_vec(), &[][..]),
+    ] {
+        let result = signer.call_opts(
+            &ctx,
+            "evm.Call",
+            module_evm::types::Call {
+                address: module_evm::types::H160::from_slice(
+                    precompiles::p256::VERIFY_ADDRESS.as_bytes(),
+                ),
+                value: 0.into(),
+                data,
+            },
+            call_options(100_000),
+        );
+        assert!(result.result.is_success(), "evm.Call should succeed");
+        let output: Vec<u8> = cbor::from_value(result.result.unwrap()).unwrap();
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn test_bls12_381_precompiles() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+
+    // Generator of G1 and the point at infinity.
+    let generator = hex::decode(concat!(
+        "0000000000000000000000000000000017f1d3a73197d7942695638c4fa9ac0f",
+        "c3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb",
+        "0000000000000000000000000000000008b3f481e3aaa0f1a09e30ed741d8ae4",
+        "fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1",
+    ))
+    .unwrap();
+    let infinity = vec![0u8; 128];
+    let address = module_evm::types::H160::from_slice(
+        precompiles::bls12_381::Operation::G1Add
+            .address()
+            .as_bytes(),
+    );
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let result = signer.call_opts(
+        &ctx,
+        "evm.Call",
+        module_evm::types::Call {
+            address,
+            value: 0.into(),
+            data: [generator.clone(), infinity].concat(),
+        },
+        call_options(100_000),
+    );
+    assert!(result.result.is_success(), "evm.Call should succeed");
+    let output: Vec<u8> = cbor::from_value(result.result.unwrap()).unwrap();
+    assert_eq!(output, generator);
+
+    // Malformed inputs make the call fail.
+    let result = signer.call_opts(
+        &ctx,
+        "evm.Call",
+        module_evm::types::Call {
+            address,
+            value: 0.into(),
+            data: generator,
+        },
+        call_options(100_000),
+    );
+    assert!(!result.result.is_success(), "evm.Call should fail");
+}
+
+#[test]
+fn test_rofl_market_query_precompile() {
+    use ethabi::{ParamType, Token};
+    use module_rofl_market::{
+        state,
+        types::{
+            Deployment, InstanceCreate, InstanceId, Offer, Payment, ProviderCreate, Resources,
+            TeeType, Term,
+        },
+    };
+    use oasis_runtime_sdk::{
+        core::common::crypto::hash::Hash,
+        modules::rofl::{
+            app_id::AppId,
+            policy::{AllowedEndorsement, AppAuthPolicy, FeePolicy},
+            types::Create,
+        },
+    };
+
+    let mut mock = Mock::default();
+    let now = mock.runtime_header.timestamp;
+    let ctx = setup(
+        &mut mock,
+        &[
+            keys::alice::address(),
+            keys::bob::address(),
+            keys::dave::address(),
+        ],
+    );
+
+    let address =
+        module_evm::types::H160::from_slice(precompiles::rofl_market::QUERY_ADDRESS.as_bytes());
+    let version = Token::Uint(precompiles::rofl_market::ABI_VERSION.into());
+    let provider = Token::FixedBytes(keys::alice::address().as_ref().to_vec());
+    let query = |method: &str, id: Option<InstanceId>| {
+        let mut params = vec![ParamType::Uint(16), ParamType::FixedBytes(21)];
+        let mut args = vec![version.clone(), provider.clone()];
+        if let Some(id) = id {
+            params.push(ParamType::FixedBytes(8));
+            args.push(Token::FixedBytes(id.as_ref().to_vec()));
+        }
+        [
+            ethabi::short_signature(method, &params).to_vec(),
+            ethabi::encode(&args),
+        ]
+        .concat()
+    };
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let mut call = |data: Vec<u8>| {
+        signer
+            .call_opts(
+                &ctx,
+                "evm.Call",
+                module_evm::types::Call {
+                    address,
+                    value: 0.into(),
+                    data,
+                },
+                call_options(100_000),
+            )
+            .result
+    };
+
+    // Providers without offers return an empty array.
+    let result = call(query("offers", None));
+    assert!(result.is_success(), "offers query should succeed");
+    let output: Vec<u8> = cbor::from_value(result.unwrap()).unwrap();
+    assert_eq!(output, ethabi::encode(&[Token::Array(vec![])]));
+
+    // Unknown instances revert.
+    let result = call(query("instance", Some(InstanceId::from([0; 8]))));
+    assert!(!result.is_success(), "instance query should revert");
+
+    // Register a provider with a native and a contract payment offer.
+    let mut alice = Signer::new(0, keys::alice::sigspec());
+    let result = alice.call_opts(
+        &ctx,
+        "rofl.Create",
+        Create {
+            policy: AppAuthPolicy {
+                endorsements: vec![AllowedEndorsement::Any],
+                fees: FeePolicy::EndorsingNodePays,
+                max_expiration: 3,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        call_options(1_000_000),
+    );
+    assert!(result.result.is_success(), "rofl.Create should succeed");
+    let app_id: AppId = cbor::from_value(result.result.unwrap()).unwrap();
+
+    let resources = Resources {
+        tee: TeeType::TDX,
+        memory: 4096,
+        cpus: 2,
+        storage: 20_000,
+        ..Default::default()
+    };
+    let result = alice.call_opts(
+        &ctx,
+        "roflmarket.ProviderCreate",
+        ProviderCreate {
+            scheduler_app: app_id,
+            offers: vec![
+                Offer {
+                    resources: resources.clone(),
+                    payment: Payment::Native {
+                        denomination: Denomination::NATIVE,
+                        terms: [(Term::Hour, 1_000)].into(),
+                    },
+                    capacity: 10,
+                    ..Default::default()
+                },
+                Offer {
+                    resources,
+                    payment: Payment::EvmContract {
+                        address: [0x42; 20].into(),
+                        data: vec![0xca, 0xfe],
+                    },
+                    capacity: 1,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        },
+        call_options(1_000_000),
+    );
+    assert!(
+        result.result.is_success(),
+        "roflmarket.ProviderCreate should succeed"
+    );
+    let offers: Vec<_> = state::get_offers(keys::alice::address())
+        .into_iter()
+        .map(|offer| offer.id)
+        .collect();
+
+    let mut bob = Signer::new(0, keys::bob::sigspec());
+    let manifest_hash = Hash::digest_bytes(b"manifest");
+    let result = bob.call_opts(
+        &ctx,
+        "roflmarket.InstanceCreate",
+        InstanceCreate {
+            provider: keys::alice::address(),
+            offer: offers[0],
+            deployment: Some(Deployment {
+                app_id,
+                manifest_hash,
+                metadata: [("net.oasis.deployment".to_string(), "test".to_string())].into(),
+            }),
+            term: Term::Hour,
+            term_count: 2,
+            ..Default::default()
+        },
+        call_options(1_000_000),
+    );
+    assert!(
+        result.result.is_success(),
+        "roflmarket.InstanceCreate should succeed"
+    );
+    let id: InstanceId = cbor::from_value(result.result.unwrap()).unwrap();
+
+    let resources = Token::Tuple(vec![
+        Token::Uint(2.into()),
+        Token::Uint(4096.into()),
+        Token::Uint(2.into()),
+        Token::Uint(20_000.into()),
+    ]);
+    let native = Token::Tuple(vec![
+        Token::Address(Default::default()),
+        Token::Bytes(vec![]),
+        Token::String(String::new()),
+        Token::Array(vec![Token::Uint(1.into())]),
+        Token::Array(vec![Token::Uint(1_000.into())]),
+    ]);
+    let mut output = |method: &str, id: Option<InstanceId>| {
+        let result = call(query(method, id));
+        assert!(result.is_success(), "{method} query should succeed");
+        cbor::from_value::<Vec<u8>>(result.unwrap()).unwrap()
+    };
+    assert_eq!(
+        output("offers", None),
+        ethabi::encode(&[Token::Array(vec![
+            Token::Tuple(vec![
+                Token::FixedBytes(offers[0].as_ref().to_vec()),
+                Token::Uint(10.into()),
+                resources.clone(),
+                native.clone(),
+            ]),
+            Token::Tuple(vec![
+                Token::FixedBytes(offers[1].as_ref().to_vec()),
+                Token::Uint(1.into()),
+                resources,
+                Token::Tuple(vec![
+                    Token::Address([0x42; 20].into()),
+                    Token::Bytes(vec![0xca, 0xfe]),
+                    Token::String(String::new()),
+                    Token::Array(vec![]),
+                    Token::Array(vec![]),
+                ]),
+            ]),
+        ])])
+    );
+    assert_eq!(
+        output("instance", Some(id)),
+        ethabi::encode(&[
+            Token::Uint(0.into()),
+            Token::FixedBytes(offers[0].as_ref().to_vec()),
+            Token::FixedBytes(keys::bob::address().as_ref().to_vec()),
+            Token::FixedBytes(keys::bob::address().as_ref().to_vec()),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Uint(now.into()),
+            Token::Uint(now.into()),
+        ])
+    );
+    assert_eq!(
+        output("deployment", Some(id)),
+        ethabi::encode(&[
+            Token::Bool(true),
+            Token::FixedBytes(app_id.as_ref().to_vec()),
+            Token::FixedBytes(manifest_hash.as_ref().to_vec()),
+            Token::Array(vec![Token::String("net.oasis.deployment".to_string())]),
+            Token::Array(vec![Token::String("test".to_string())]),
+        ])
+    );
+    assert_eq!(
+        output("paymentState", Some(id)),
+        ethabi::encode(&[
+            native,
+            Token::Uint(now.into()),
+            Token::Uint((now + 2 * 3_600).into()),
+        ])
+    );
+}
+
+#[test]
+fn test_rofl_query_precompile() {
+    use ethabi::{ParamType, Token};
+    use oasis_runtime_sdk::{
+        core::common::{
+            crypto::signature::PublicKey,
+            sgx::{EnclaveIdentity, MrEnclave, MrSigner},
+        },
+        modules::rofl::{
+            app_id::AppId,
+            policy::{AllowedEndorsement, AppAuthPolicy, FeePolicy},
+            state,
+            types::{AppConfig, Registration},
+        },
+    };
+
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+
+    let app_id = AppId::from(&[1; 21][..]);
+    let node = PublicKey([4; 32]);
+    let entity = PublicKey([5; 32]);
+    let version = Token::Uint(precompiles::rofl::ABI_VERSION.into());
+    let query = |method: &str, app_id: AppId| {
+        [
+            ethabi::short_signature(method, &[ParamType::Uint(16), ParamType::FixedBytes(21)])
+                .to_vec(),
+            ethabi::encode(&[version.clone(), Token::FixedBytes(app_id.as_ref().to_vec())]),
+        ]
+        .concat()
+    };
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let mut call = |data: Vec<u8>| {
+        signer
+            .call_opts(
+                &ctx,
+                "evm.Call",
+                module_evm::types::Call {
+                    address: module_evm::types::H160::from_slice(
+                        precompiles::rofl::QUERY_ADDRESS.as_bytes(),
+                    ),
+                    value: 0.into(),
+                    data,
+                },
+                call_options(100_000),
+            )
+            .result
+    };
+
+    // Unknown applications revert.
+    let result = call(query("app", app_id));
+    assert!(!result.is_success(), "app query should revert");
+
+    state::set_app(AppConfig {
+        id: app_id,
+        policy: AppAuthPolicy {
+            enclaves: vec![EnclaveIdentity {
+                mr_enclave: MrEnclave([2; 32]),
+                mr_signer: MrSigner([3; 32]),
+            }],
+            endorsements: vec![AllowedEndorsement::Or(vec![
+                AllowedEndorsement::Node(node),
+                AllowedEndorsement::Provider(keys::alice::address()),
+            ])],
+            fees: FeePolicy::EndorsingNodePays,
+            max_expiration: 3,
+            ..Default::default()
+        },
+        admin: Some(keys::alice::address()),
+        stake: BaseUnits::new(10_000, Denomination::NATIVE),
+        metadata: [("net.oasis.rofl.name".to_string(), "test".to_string())].into(),
+        ..Default::default()
+    });
+    state::update_registration(Registration {
+        app: app_id,
+        node_id: node,
+        entity_id: Some(entity),
+        expiration: 3,
+        ..Default::default()
+    })
+    .expect("registration should succeed");
+    // A second instance on a node that is not listed by any provider named in the policy.
+    state::update_registration(Registration {
+        app: app_id,
+        node_id: PublicKey([6; 32]),
+        entity_id: Some(entity),
+        rak: keys::bob::pk(),
+        expiration: 3,
+        ..Default::default()
+    })
+    .expect("registration should succeed");
+    assert_eq!(state::get_registrations_for_app(app_id).len(), 2);
+    module_rofl_market::state::set_provider(module_rofl_market::types::Provider {
+        address: keys::alice::address(),
+        nodes: vec![node],
+        ..Default::default()
+    });
+
+    let mut output = |method: &str| {
+        let result = call(query(method, app_id));
+        assert!(result.is_success(), "{method} query should succeed");
+        cbor::from_value::<Vec<u8>>(result.unwrap()).unwrap()
+    };
+    assert_eq!(
+        output("app"),
+        ethabi::encode(&[
+            Token::FixedBytes(keys::alice::address().as_ref().to_vec()),
+            Token::String(String::new()),
+            Token::Uint(10_000.into()),
+            Token::Array(vec![Token::String("net.oasis.rofl.name".to_string())]),
+            Token::Array(vec![Token::String("test".to_string())]),
+        ])
+    );
+    let endorsement = |kind: u8, value: &[u8], children: u32| {
+        Token::Tuple(vec![
+            Token::Uint(kind.into()),
+            Token::Bytes(value.to_vec()),
+            Token::Uint(children.into()),
+        ])
+    };
+    assert_eq!(
+        output("policy"),
+        ethabi::encode(&[
+            Token::Array(vec![
+                endorsement(8, &[], 2),
+                endorsement(4, node.as_ref(), 0),
+                endorsement(5, keys::alice::address().as_ref(), 0),
+            ]),
+            Token::Array(vec![Token::FixedBytes(vec![2; 32])]),
+            Token::Array(vec![Token::FixedBytes(vec![3; 32])]),
+            Token::Uint(2.into()),
+            Token::Uint(3.into()),
+        ])
+    );
+    assert_eq!(
+        hex::encode(output("instances")),
+        concat!(
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+    );
+}
+
+/// Authenticate the given transaction as the dispatcher does, discarding any state changes.
+fn authenticate(
+    ctx: &RuntimeBatchContext<'_, Runtime>,
+    tx: &Transaction,
+) -> Result<(), modules::core::Error> {
+    CurrentState::with_transaction(|| {
+        TransactionResult::Rollback(
+            <Runtime as oasis_runtime_sdk::Runtime>::Modules::authenticate_tx(ctx, tx).map(|_| ()),
+        )
+    })
+}
+
+#[test]
+fn test_paymaster_fee_proxy() {
+    use oasis_runtime_sdk::types::transaction::FeeProxy;
+
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+
+    let mut deployer = Signer::new(0, keys::dave::sigspec());
+    let result = deployer.call_opts(
+        &ctx,
+        "evm.Create",
+        module_evm::types::Create {
+            value: 0.into(),
+            init_code: hex::decode(RETURN_42_INIT_CODE).unwrap(),
+        },
+        call_options(1_000_000),
+    );
+    assert!(result.result.is_success(), "evm.Create should succeed");
+    let address: Vec<u8> = cbor::from_value(result.result.unwrap()).unwrap();
+    let contract = primitive_types::H160::from_slice(&address);
+
+    let max_fee_per_call = 100_000 * GAS_PRICE;
+    paymaster::set_paymaster(contract, paymaster::Paymaster { max_fee_per_call });
+    paymaster::set_allowed(contract, keys::bob::address(), true);
+    fund(paymaster::deposit_address(contract));
+
+    let sponsored = |gas: u64| {
+        let mut opts = call_options(gas);
+        opts.fee.proxy = Some(FeeProxy {
+            module: paymaster::MODULE_NAME.to_string(),
+            id: address.clone(),
+        });
+        opts
+    };
+    let call = module_evm::types::Call {
+        address: module_evm::types::H160::from_slice(&address),
+        value: 0.into(),
+        data: vec![],
+    };
+
+    // Calls of the paymaster contract are charged to its deposit.
+    let mut signer = Signer::new(0, keys::bob::sigspec());
+    let result = signer.call_opts(&ctx, "evm.Call", call.clone(), sponsored(100_000));
+    assert!(result.result.is_success(), "sponsored call should succeed");
+    let balance = accounts::Module::get_balance(keys::bob::address(), Denomination::NATIVE)
+        .expect("balance query should succeed");
+    assert_eq!(balance, 0, "caller should not pay the fee");
+    let deposit =
+        accounts::Module::get_balance(paymaster::deposit_address(contract), Denomination::NATIVE)
+            .expect("balance query should succeed");
+    assert!(
+        deposit < 1_000_000_000_000_000_000_000,
+        "paymaster should pay the fee"
+    );
+
+    // Fees above the per-call cap are not sponsored. Rejected transactions do not use a nonce.
+    let tx = signer.create_transaction_opts("evm.Call", call.clone(), sponsored(200_000));
+    assert!(
+        matches!(
+            authenticate(&ctx, &tx),
+            Err(modules::core::Error::Forbidden)
+        ),
+        "call above the cap should be rejected"
+    );
+
+    // Only allowlisted callers are sponsored.
+    let mut signer = Signer::new(0, keys::charlie::sigspec());
+    let tx = signer.create_transaction_opts("evm.Call", call, sponsored(100_000));
+    assert!(
+        matches!(
+            authenticate(&ctx, &tx),
+            Err(modules::core::Error::Forbidden)
+        ),
+        "call by unlisted caller should be rejected"
+    );
+}
+
+/// Init code of a contract calling the paymaster precompile with the given input.
+///
+/// Without calldata the contract fails if the precompile call fails. With one byte of calldata it
+/// reverts after the call and with two bytes it calls itself with one byte of calldata and
+/// succeeds regardless of the outcome of that call.
+fn paymaster_caller_init_code(input: &[u8]) -> Vec<u8> {
+    // The input is appended to the 75 bytes of runtime code.
+    let size = u8::try_from(input.len()).expect("input should be short");
+    let code_size = size.checked_add(75).expect("input should be short");
+    [
+        vec![0x60, code_size],
+        hex::decode("600c600039").unwrap(),
+        vec![0x60, code_size],
+        hex::decode("6000f3").unwrap(),
+        hex::decode("36600214603b5760").unwrap(),
+        vec![size],
+        hex::decode("604b6000396000600060").unwrap(),
+        vec![size],
+        hex::decode("6000600073").unwrap(),
+        precompiles::paymaster::PAYMASTER_ADDRESS
+            .as_bytes()
+            .to_vec(),
+        hex::decode("5af1153617603657005b600080fd5b60006000600160006000305af15000").unwrap(),
+        input.to_vec(),
+    ]
+    .concat()
+}
+
+#[test]
+fn test_paymaster_precompile_revert() {
+    use ethabi::{ParamType, Token};
+
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+
+    let max_fee_per_call = 100_000 * GAS_PRICE;
+    let input = [
+        ethabi::short_signature("configure", &[ParamType::Uint(16), ParamType::Uint(128)]).to_vec(),
+        ethabi::encode(&[
+            Token::Uint(precompiles::paymaster::ABI_VERSION.into()),
+            Token::Uint(max_fee_per_call.into()),
+        ]),
+    ]
+    .concat();
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+
+    // Externally owned accounts cannot register.
+    let result = signer.call_opts(
+        &ctx,
+        "evm.Call",
+        module_evm::types::Call {
+            address: module_evm::types::H160::from_slice(
+                precompiles::paymaster::PAYMASTER_ADDRESS.as_bytes(),
+            ),
+            value: 0.into(),
+            data: input.clone(),
+        },
+        call_options(1_000_000),
+    );
+    assert!(
+        !result.result.is_success(),
+        "configuring call by an account without code should fail"
+    );
+
+    let result = signer.call_opts(
+        &ctx,
+        "evm.Create",
+        module_evm::types::Create {
+            value: 0.into(),
+            init_code: paymaster_caller_init_code(&input),
+        },
+        call_options(1_000_000),
+    );
+    assert!(result.result.is_success(), "evm.Create should succeed");
+    let address: Vec<u8> = cbor::from_value(result.result.unwrap()).unwrap();
+    let contract = primitive_types::H160::from_slice(&address);
+
+    let mut call = |data: Vec<u8>| {
+        signer
+            .call_opts(
+                &ctx,
+                "evm.Call",
+                module_evm::types::Call {
+                    address: module_evm::types::H160::from_slice(&address),
+                    value: 0.into(),
+                    data,
+                },
+                call_options(1_000_000),
+            )
+            .result
+            .is_success()
+    };
+
+    // Changes are discarded when the calling frame reverts, even if the transaction succeeds.
+    assert!(call(vec![0, 0]), "outer call should succeed");
+    assert_eq!(paymaster::get_paymaster(contract), None);
+    assert!(!call(vec![0]), "reverting call should fail");
+    assert_eq!(paymaster::get_paymaster(contract), None);
+
+    assert!(call(vec![]), "configuring call should succeed");
+    assert_eq!(
+        paymaster::get_paymaster(contract),
+        Some(paymaster::Paymaster { max_fee_per_call })
+    );
+}
+
+#[test]
+fn test_governance_parameter_update() {
+    let mut mock = Mock::default();
+    let ctx = setup(
+        &mut mock,
+        &[
+            keys::alice::address(),
+            keys::bob::address(),
+            keys::charlie::address(),
+        ],
+    );
+    governance::Module::set_params(governance::Parameters {
+        signers: vec![keys::alice::address(), keys::bob::address()],
+        threshold: 2,
+        timelock: 3_600,
+    });
+
+    let mut params = modules::consensus_accounts::Module::<modules::consensus::Module>::params();
+    params.disable_withdraw = true;
+    let propose = governance::types::Propose {
+        action: governance::types::Action::ConsensusAccounts(params),
+    };
+
+    // Only signers can propose updates.
+    let mut charlie = Signer::new(0, keys::charlie::sigspec());
+    let result = charlie.call_opts(
+        &ctx,
+        "governance.Propose",
+        propose.clone(),
+        call_options(200_000),
+    );
+    assert!(!result.result.is_success(), "non-signer should not propose");
+
+    let mut alice = Signer::new(0, keys::alice::sigspec());
+    let result = alice.call_opts(&ctx, "governance.Propose", propose, call_options(200_000));
+    assert!(result.result.is_success(), "signer should propose");
+    let id: u64 = cbor::from_value(result.result.unwrap()).unwrap();
+
+    let execute = governance::types::Execute { id };
+    let result = alice.call_opts(
+        &ctx,
+        "governance.Execute",
+        execute.clone(),
+        call_options(200_000),
+    );
+    assert!(
+        !result.result.is_success(),
+        "unapproved proposal should not execute"
+    );
+
+    let mut bob = Signer::new(0, keys::bob::sigspec());
+    let result = bob.call_opts(
+        &ctx,
+        "governance.Approve",
+        governance::types::Approve { id },
+        call_options(200_000),
+    );
+    assert!(result.result.is_success(), "signer should approve");
+
+    let result = alice.call_opts(
+        &ctx,
+        "governance.Execute",
+        execute.clone(),
+        call_options(200_000),
+    );
+    assert!(
+        !result.result.is_success(),
+        "timelocked proposal should not execute"
+    );
+
+    mock.runtime_header.timestamp += 3_600;
+    let ctx = mock.create_ctx_for_runtime::<Runtime>(true);
+    let result = alice.call_opts(&ctx, "governance.Execute", execute, call_options(200_000));
+    assert!(
+        result.result.is_success(),
+        "approved proposal should execute"
+    );
+    assert!(
+        modules::consensus_accounts::Module::<modules::consensus::Module>::params()
+            .disable_withdraw,
+        "parameters should be updated"
+    );
+
+    // Fields changed by executed updates survive migrations resetting parameters from genesis,
+    // other fields of the same module take their genesis values.
+    let mut params = modules::consensus_accounts::Module::<modules::consensus::Module>::params();
+    params.disable_deposit = !params.disable_deposit;
+    modules::consensus_accounts::Module::<modules::consensus::Module>::set_params(params);
+    migrations::update_parameters();
+    let params = modules::consensus_accounts::Module::<modules::consensus::Module>::params();
+    assert!(
+        params.disable_withdraw,
+        "parameters should be kept by migrations"
+    );
+    assert_eq!(
+        params.disable_deposit,
+        Runtime::genesis_state().3.parameters.disable_deposit,
+        "unchanged fields should be reset"
+    );
+    assert_eq!(
+        cbor::to_vec(modules::core::Module::<Config>::params()),
+        cbor::to_vec(Runtime::genesis_state().0.parameters),
+        "other parameters should be reset"
+    );
+}
+
+/// Encoded events of the pause module with the given code, as emitted by a transaction.
+fn pause_events(
+    tags: &[oasis_runtime_sdk::core::transaction::tags::Tag],
+    code: u32,
+) -> Option<Vec<u8>> {
+    let key = [pause::MODULE_NAME.as_bytes(), &code.to_be_bytes()].concat();
+    tags.iter()
+        .find(|tag| tag.key == key)
+        .map(|tag| tag.value.clone())
+}
+
+#[test]
+fn test_pause_evm_create() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::alice::address(), keys::dave::address()]);
+    pause::Module::set_params(pause::Parameters {
+        guardians: vec![keys::alice::address()],
+    });
+
+    let mut guardian = Signer::new(0, keys::alice::sigspec());
+    let mut set = |target: pause::types::Target, paused: bool| {
+        let result = guardian.call_opts(
+            &ctx,
+            "pause.Set",
+            pause::types::Set { target, paused },
+            call_options(100_000),
+        );
+        result.result.is_success().then_some(result.tags)
+    };
+    let target = pause::types::Target::Method("evm.Create".to_string());
+    let tags = set(target.clone(), true).expect("guardian should pause evm.Create");
+    assert_eq!(
+        pause_events(&tags, 1),
+        Some(cbor::to_vec(vec![pause::Event::Paused {
+            target: target.clone(),
+            guardian: keys::alice::address(),
+        }])),
+        "pausing should emit an event"
+    );
+    let tags = set(target.clone(), true).expect("pausing again should succeed");
+    assert_eq!(pause_events(&tags, 1), None, "no-op should not emit events");
+    for method in ["pause.Set", "accounts.Transfer", "consensus.Withdraw"] {
+        assert!(
+            set(pause::types::Target::Method(method.to_string()), true).is_none(),
+            "{method} should not be pausable"
+        );
+    }
+
+    let create = module_evm::types::Create {
+        value: 0.into(),
+        init_code: hex::decode(RETURN_42_INIT_CODE).unwrap(),
+    };
+    let mut deployer = Signer::new(0, keys::dave::sigspec());
+    let result = deployer.call_opts(&ctx, "evm.Create", create.clone(), call_options(1_000_000));
+    assert!(
+        !result.result.is_success(),
+        "paused method should be rejected"
+    );
+
+    let tags = set(target.clone(), false).expect("guardian should unpause evm.Create");
+    assert_eq!(
+        pause_events(&tags, 2),
+        Some(cbor::to_vec(vec![pause::Event::Unpaused {
+            target,
+            guardian: keys::alice::address(),
+        }])),
+        "unpausing should emit an event"
+    );
+    let result = deployer.call_opts(&ctx, "evm.Create", create, call_options(1_000_000));
+    assert!(result.result.is_success(), "unpaused method should succeed");
+    assert_eq!(pause::state::get_status(), pause::types::Status::default());
+}
+
+/// Init code of a contract calling the contract whose address is given as the first calldata word
+/// and returning the success of that call as a 32-byte word.
+const PROXY_INIT_CODE: &str =
+    "6017600c60003960176000f3600060006000600060006000355af160005260206000f3";
+
+/// Init code whose constructor calls the given contract and reverts in case the call fails.
+fn constructor_call_init_code(address: &[u8]) -> Vec<u8> {
+    [
+        hex::decode("6000600060006000600073").unwrap(),
+        address.to_vec(),
+        hex::decode("5af1602857600080fd5b00").unwrap(),
+    ]
+    .concat()
+}
+
+#[test]
+fn test_pause_evm_contract() {
+    use evm::executor::stack::{IsPrecompileResult, PrecompileSet as _};
+
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::alice::address(), keys::dave::address()]);
+    pause::Module::set_params(pause::Parameters {
+        guardians: vec![keys::alice::address()],
+    });
+
+    let mut deployer = Signer::new(0, keys::dave::sigspec());
+    let mut create = |init_code: Vec<u8>| {
+        let result = deployer
+            .call_opts(
+                &ctx,
+                "evm.Create",
+                module_evm::types::Create {
+                    value: 0.into(),
+                    init_code,
+                },
+                call_options(1_000_000),
+            )
+            .result;
+        result
+            .is_success()
+            .then(|| cbor::from_value::<Vec<u8>>(result.unwrap()).unwrap())
+    };
+    let target = create(hex::decode(RETURN_42_INIT_CODE).unwrap()).expect("target should deploy");
+    let proxy = create(hex::decode(PROXY_INIT_CODE).unwrap()).expect("proxy should deploy");
+
+    let mut guardian = Signer::new(0, keys::alice::sigspec());
+    let mut set = |paused: bool| {
+        let target = pause::types::Target::Contract(module_evm::types::H160::from_slice(&target));
+        let result = guardian.call_opts(
+            &ctx,
+            "pause.Set",
+            pause::types::Set {
+                target: target.clone(),
+                paused,
+            },
+            call_options(100_000),
+        );
+        assert!(
+            result.result.is_success(),
+            "guardian should update the pause"
+        );
+        let guardian = keys::alice::address();
+        let (code, event) = if paused {
+            (1, pause::Event::Paused { target, guardian })
+        } else {
+            (2, pause::Event::Unpaused { target, guardian })
+        };
+        assert_eq!(
+            pause_events(&result.tags, code),
+            Some(cbor::to_vec(vec![event])),
+            "pause update should emit an event"
+        );
+    };
+    let mut caller = Signer::new(0, keys::bob::sigspec());
+    fund(keys::bob::address());
+    let mut call = |address: &[u8], data: Vec<u8>| {
+        let result = caller
+            .call_opts(
+                &ctx,
+                "evm.Call",
+                module_evm::types::Call {
+                    address: module_evm::types::H160::from_slice(address),
+                    value: 0.into(),
+                    data,
+                },
+                call_options(1_000_000),
+            )
+            .result;
+        result
+            .is_success()
+            .then(|| cbor::from_value::<Vec<u8>>(result.unwrap()).unwrap())
+    };
+    let word = |value: u8| {
+        let mut word = vec![0u8; 32];
+        word[31] = value;
+        word
+    };
+    let via_proxy = [vec![0u8; 12], target.clone()].concat();
+
+    set(true);
+    assert_eq!(
+        call(&target, vec![]),
+        None,
+        "direct call should be rejected"
+    );
+    // Paused contracts are rejected when entered and keep their access costs.
+    assert!(
+        matches!(
+            precompiles::Precompiles.is_precompile(primitive_types::H160::from_slice(&target), 0),
+            IsPrecompileResult::Answer {
+                is_precompile: false,
+                ..
+            }
+        ),
+        "paused contract should not be reported as a precompile"
+    );
+    assert_eq!(
+        call(&proxy, via_proxy.clone()),
+        Some(word(0)),
+        "call through another contract should fail"
+    );
+    assert_eq!(
+        create(constructor_call_init_code(&target)),
+        None,
+        "call from a constructor should fail"
+    );
+
+    set(false);
+    assert_eq!(call(&target, vec![]), Some(word(42)));
+    assert_eq!(call(&proxy, via_proxy), Some(word(1)));
+    assert!(create(constructor_call_init_code(&target)).is_some());
+}
+
+/// Whether the given call result is a rejection because of a too low gas price.
+fn is_gas_price_too_low(result: &oasis_runtime_sdk::module::CallResult) -> bool {
+    use oasis_runtime_sdk::{error::Error as _, module::CallResult};
+
+    let err = modules::core::Error::GasPriceTooLow;
+    matches!(
+        result,
+        CallResult::Failed { module, code, .. } if module == err.module_name() && *code == err.code()
+    )
+}
+
+#[test]
+fn test_gas_policy_floors() {
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+    gas_policy::Module::set_params(gas_policy::Parameters {
+        methods: [
+            ("rofl.Register".to_string(), 500),
+            ("roflmarket.InstanceAccept".to_string(), 500),
+        ]
+        .into(),
+        ..Default::default()
+    });
+
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let mut call = |method: &str, gas_price: u128| {
+        let gas = 100_000;
+        signer
+            .call_opts(
+                &ctx,
+                method,
+                (),
+                CallOptions {
+                    fee: Fee {
+                        amount: BaseUnits::new(u128::from(gas) * gas_price, Denomination::NATIVE),
+                        gas,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .result
+    };
+
+    // Only methods with a floor in genesis are exempt from the core minimum gas price.
+    for method in ["rofl.Register", "roflmarket.InstanceAccept"] {
+        assert_eq!(
+            gas_policy::is_policy_method(method),
+            NETWORK.discounted_methods().contains(&method),
+        );
+        if !gas_policy::is_policy_method(method) {
+            assert!(is_gas_price_too_low(&call(method, GAS_PRICE / 2)));
+            continue;
+        }
+
+        // The call itself fails as the body is empty, but the discounted gas price is accepted.
+        assert!(
+            is_gas_price_too_low(&call(method, GAS_PRICE / 2 - 1)),
+            "{method} below the floor should be rejected"
+        );
+        assert!(
+            !is_gas_price_too_low(&call(method, GAS_PRICE / 2)),
+            "{method} at the floor should be accepted"
+        );
+    }
+
+    // Calls of contracts pay the full gas price unless contract floors are configured in genesis.
+    assert!(!gas_policy::is_policy_method("evm.Call"));
+    assert!(is_gas_price_too_low(&call("evm.Call", GAS_PRICE / 2)));
+    assert!(!is_gas_price_too_low(&call("evm.Call", GAS_PRICE)));
+
+    // The floor follows changes of the minimum gas price.
+    if gas_policy::is_policy_method("rofl.Register") {
+        let mut params = modules::core::Module::<Config>::params();
+        params.dynamic_min_gas_price.enabled = false;
+        params
+            .min_gas_price
+            .insert(Denomination::NATIVE, 2 * GAS_PRICE);
+        modules::core::Module::<Config>::set_params(params);
+        assert!(is_gas_price_too_low(&call("rofl.Register", GAS_PRICE / 2)));
+        assert!(!is_gas_price_too_low(&call("rofl.Register", GAS_PRICE)));
+    }
+}
+
+#[test]
+fn test_lanes_defer_transactions() {
+    use oasis_runtime_sdk::state::{Mode, Options};
+
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[keys::dave::address()]);
+    let mut params = modules::core::Module::<Config>::params();
+    params.max_batch_gas = 1_000_000;
+    modules::core::Module::<Config>::set_params(params);
+    // Only 100_000 gas is left for transactions outside of the lane.
+    lanes::Module::set_params(lanes::Parameters {
+        lanes: vec![lanes::Lane {
+            methods: vec!["consensus.Deposit".to_string()],
+            reserved: 900,
+        }],
+    });
+
+    let pre_schedule = |tx: &Transaction| {
+        CurrentState::with_transaction_opts(Options::new().with_mode(Mode::PreSchedule), || {
+            TransactionResult::Rollback(authenticate(&ctx, tx))
+        })
+    };
+    let mut signer = Signer::new(0, keys::dave::sigspec());
+    let call = module_evm::types::Call {
+        address: module_evm::types::H160::from_slice(&[0x42; 20]),
+        value: 0.into(),
+        data: vec![],
+    };
+
+    // Transactions that do not fit are reported with the same error as transactions whose nonce
+    // is ahead of the account, which the scheduler keeps queued instead of rejecting them so that
+    // the transactions of a sender can be included in nonce order.
+    let tx = signer.create_transaction_opts("evm.Call", call.clone(), call_options(200_000));
+    assert!(
+        matches!(pre_schedule(&tx), Err(modules::core::Error::FutureNonce)),
+        "transaction not fitting the shared capacity should be deferred"
+    );
+    let mut ahead = Signer::new(1, keys::dave::sigspec());
+    let tx = ahead.create_transaction_opts("evm.Call", call.clone(), call_options(50_000));
+    assert!(
+        matches!(pre_schedule(&tx), Err(modules::core::Error::FutureNonce)),
+        "transaction with a future nonce should be deferred in the same way"
+    );
+
+    // Lane transactions can use the reservation.
+    let tx = signer.create_transaction_opts("consensus.Deposit", (), call_options(200_000));
+    assert!(pre_schedule(&tx).is_ok(), "lane transaction should fit");
+
+    // Executing a proposed batch never fails because of the reservations.
+    let tx = signer.create_transaction_opts("evm.Call", call, call_options(200_000));
+    assert!(authenticate(&ctx, &tx).is_ok());
+}
+
+#[test]
+fn test_endorsement_policy_evaluator() {
+    use std::collections::BTreeMap;
+
+    use oasis_runtime_sdk::{
+        core::{
+            common::crypto::signature::{PublicKey, SignatureBundle},
+            consensus::registry::EndorsedCapabilityTEE,
+        },
+        modules::rofl::{
+            self,
+            policy::{AllowedEndorsement, EndorsementPolicyEvaluator as _},
+        },
+    };
+
+    type Evaluator = <Config as rofl::Config>::EndorsementPolicyEvaluator;
+
+    let mut mock = Mock::default();
+    let ctx = setup(&mut mock, &[]);
+
+    let node = PublicKey([1; 32]);
+    let ect = EndorsedCapabilityTEE {
+        node_endorsement: SignatureBundle {
+            public_key: node,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let verify = |policy: AllowedEndorsement| {
+        Evaluator::verify(&ctx, &policy, &ect, &BTreeMap::new()).expect("verify should succeed")
+    };
+
+    // Without requirements the basic and provider evaluators decide.
+    assert_eq!(verify(AllowedEndorsement::Any), Some(true));
+    assert_eq!(verify(AllowedEndorsement::Node(node)), Some(true));
+    assert_eq!(
+        verify(AllowedEndorsement::Node(PublicKey([2; 32]))),
+        Some(false)
+    );
+    assert_eq!(
+        verify(AllowedEndorsement::Provider(keys::alice::address())),
+        Some(false),
+        "nodes without provider instances should be rejected"
+    );
+
+    // Each requirement rejects endorsements of every kind, including those allowed above.
+    let atoms = [
+        AllowedEndorsement::Any,
+        AllowedEndorsement::ComputeRole,
+        AllowedEndorsement::ObserverRole,
+        AllowedEndorsement::Entity(PublicKey([3; 32])),
+        AllowedEndorsement::Node(node),
+        AllowedEndorsement::Provider(keys::alice::address()),
+        AllowedEndorsement::ProviderInstanceAdmin(keys::alice::address()),
+        AllowedEndorsement::And(vec![AllowedEndorsement::Any]),
+        AllowedEndorsement::Or(vec![AllowedEndorsement::Node(node)]),
+    ];
+    let requirements = [
+        // Revoked nodes are rejected regardless of the application policy.
+        endorsement::Parameters {
+            revoked_nodes: vec![node],
+            ..Default::default()
+        },
+        // TEEs that cannot prove their CPU security version are rejected once a minimum is set.
+        endorsement::Parameters {
+            min_cpusvn: Some([0; 16]),
+            ..Default::default()
+        },
+        // Nodes unknown to the consensus layer have no entity to count instances against.
+        endorsement::Parameters {
+            max_instances_per_entity: 1,
+            ..Default::default()
+        },
+    ];
+    for params in requirements {
+        endorsement::Module::set_params(params.clone());
+        for atom in &atoms {
+            assert_eq!(
+                verify(atom.clone()),
+                Some(false),
+                "{atom:?} should be rejected with {params:?}"
+            );
+        }
+    }
+
+    endorsement::Module::set_params(Default::default());
+    assert_eq!(verify(AllowedEndorsement::Any), Some(true));
+}